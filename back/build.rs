@@ -1,4 +1,8 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("protos/jobreceiver.proto")?;
+    // The migrations are embedded into the binary, so it is rebuilt when they change
+    for directory in ["migrations", "migrations_postgres", "migrations_sqlite"] {
+        println!("cargo:rerun-if-changed={}", directory);
+    }
     Ok(())
  }
\ No newline at end of file
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE scheduling_decisions
+( id BIGINT NOT NULL AUTO_INCREMENT,
+  request_id BIGINT NOT NULL,
+  node_id TINYINT NOT NULL,
+  strategy CHAR(255) NOT NULL,
+  ranking TEXT NOT NULL DEFAULT "" COMMENT "Comma-separated node ids in order of preference",
+  candidate_count INT NOT NULL DEFAULT 0,
+  large_job BOOLEAN DEFAULT 0 NOT NULL,
+  decision_date DATETIME NOT NULL DEFAULT NOW(),
+  CONSTRAINT scheduling_decisions_pk PRIMARY KEY (id)
+);
+
+CREATE INDEX scheduling_request_id_index ON scheduling_decisions(request_id);
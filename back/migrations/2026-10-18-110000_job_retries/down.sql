@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE jobs DROP COLUMN retries;
+
+ALTER TABLE nodes DROP COLUMN failed_checks;
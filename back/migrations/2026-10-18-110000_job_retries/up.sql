@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE jobs ADD COLUMN retries INT NOT NULL DEFAULT 0 COMMENT "Number of previous attempts of the same request";
+
+ALTER TABLE nodes ADD COLUMN failed_checks INT NOT NULL DEFAULT 0 COMMENT "Consecutive failed status queries";
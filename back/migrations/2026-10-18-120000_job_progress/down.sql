@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE jobs DROP COLUMN progress;
+
+ALTER TABLE jobs DROP COLUMN stage;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE jobs ADD COLUMN stage VARCHAR(64) NOT NULL DEFAULT '' COMMENT "Last reported stage of the job";
+
+ALTER TABLE jobs ADD COLUMN progress TINYINT NOT NULL DEFAULT 0 COMMENT "Last reported percentage of the job";
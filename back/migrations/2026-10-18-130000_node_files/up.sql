@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+CREATE TABLE node_files
+( node_id TINYINT NOT NULL,
+  file_path VARCHAR(255) NOT NULL COMMENT "Path relative to the cache directory of the node",
+  file_hash CHAR(64) NOT NULL COMMENT "SHA-256 hash of the file",
+  sync_date DATETIME NOT NULL DEFAULT NOW(),
+  CONSTRAINT node_files_pk PRIMARY KEY (node_id, file_path)
+);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TABLE nodes ADD COLUMN drained BOOLEAN DEFAULT 0 NOT NULL COMMENT "Finishes its running jobs without receiving new ones";
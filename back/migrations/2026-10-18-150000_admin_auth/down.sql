@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE admin_audit_log;
+DROP TABLE admin_sessions;
+DROP TABLE admins;
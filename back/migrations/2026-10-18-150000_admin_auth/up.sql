@@ -0,0 +1,34 @@
+-- Your SQL goes here
+
+CREATE TABLE admins
+( id INT NOT NULL AUTO_INCREMENT,
+  username VARCHAR(64) NOT NULL,
+  password_hash VARCHAR(255) NOT NULL COMMENT "Argon2id hash in PHC string format",
+  active BOOLEAN DEFAULT 1 NOT NULL,
+  creation_date DATETIME NOT NULL DEFAULT NOW(),
+  CONSTRAINT admins_pk PRIMARY KEY (id),
+  CONSTRAINT admins_username_unique UNIQUE (username)
+);
+
+CREATE TABLE admin_sessions
+( token_hash CHAR(64) NOT NULL COMMENT "SHA-256 hash of the session token",
+  admin_id INT NOT NULL,
+  creation_date DATETIME NOT NULL DEFAULT NOW(),
+  expiration_date DATETIME NOT NULL,
+  CONSTRAINT admin_sessions_pk PRIMARY KEY (token_hash)
+);
+
+CREATE INDEX admin_sessions_admin_id_index ON admin_sessions(admin_id);
+
+CREATE TABLE admin_audit_log
+( id BIGINT NOT NULL AUTO_INCREMENT,
+  admin_id INT NOT NULL,
+  actor VARCHAR(64) NOT NULL,
+  action VARCHAR(255) NOT NULL COMMENT "HTTP method and path of the admin request",
+  payload TEXT NOT NULL DEFAULT "",
+  status_code INT NOT NULL,
+  action_date DATETIME NOT NULL DEFAULT NOW(),
+  CONSTRAINT admin_audit_log_pk PRIMARY KEY (id)
+);
+
+CREATE INDEX admin_audit_log_admin_id_index ON admin_audit_log(admin_id);
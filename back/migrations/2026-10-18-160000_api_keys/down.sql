@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE api_key_requests;
+DROP TABLE api_keys;
@@ -0,0 +1,25 @@
+-- Your SQL goes here
+
+CREATE TABLE api_keys
+( id INT NOT NULL AUTO_INCREMENT,
+  owner VARCHAR(64) NOT NULL,
+  key_prefix CHAR(11) NOT NULL COMMENT "First characters of the key, for its identification",
+  key_hash CHAR(64) NOT NULL COMMENT "SHA-256 hash of the key",
+  daily_quota INT NOT NULL COMMENT "Maximum requests in 24 hours, 0 for no limit",
+  active BOOLEAN DEFAULT 1 NOT NULL,
+  admin_id INT NOT NULL COMMENT "Administrator that issued the key",
+  creation_date DATETIME NOT NULL DEFAULT NOW(),
+  last_used_date DATETIME NULL,
+  CONSTRAINT api_keys_pk PRIMARY KEY (id),
+  CONSTRAINT api_keys_key_hash_unique UNIQUE (key_hash)
+);
+
+CREATE TABLE api_key_requests
+( id BIGINT NOT NULL AUTO_INCREMENT,
+  api_key_id INT NOT NULL,
+  request_id BIGINT NOT NULL,
+  submission_date DATETIME NOT NULL DEFAULT NOW(),
+  CONSTRAINT api_key_requests_pk PRIMARY KEY (id)
+);
+
+CREATE INDEX api_key_requests_key_date_index ON api_key_requests(api_key_id, submission_date);
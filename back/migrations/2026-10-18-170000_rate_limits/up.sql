@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+CREATE TABLE rate_limit_buckets
+( bucket_key VARCHAR(191) NOT NULL COMMENT "Route and client of the bucket",
+  tokens DOUBLE NOT NULL,
+  refill_time BIGINT NOT NULL COMMENT "Milliseconds since the Unix epoch",
+  CONSTRAINT rate_limit_buckets_pk PRIMARY KEY (bucket_key)
+);
+
+CREATE INDEX rate_limit_buckets_refill_time_index ON rate_limit_buckets(refill_time);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE batches
+( id CHAR(36) NOT NULL COMMENT "Random UUID, which is also the proof of the batch",
+  api_key_id INT NULL,
+  creation_date DATETIME NOT NULL DEFAULT NOW(),
+  CONSTRAINT batches_pk PRIMARY KEY (id)
+);
+
+CREATE TABLE batch_requests
+( batch_id CHAR(36) NOT NULL,
+  request_id BIGINT NOT NULL,
+  position INT NOT NULL COMMENT "Position of the reference in the submitted batch",
+  CONSTRAINT batch_requests_pk PRIMARY KEY (batch_id, request_id)
+);
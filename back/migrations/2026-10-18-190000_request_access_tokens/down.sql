@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX requests_access_token_unique ON requests;
+ALTER TABLE requests DROP COLUMN access_token;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+-- The requests that were stored before the access tokens keep their hash as the proof of access
+ALTER TABLE requests ADD access_token CHAR(64) NULL COMMENT "Random proof of access to the results of the request";
+
+CREATE UNIQUE INDEX requests_access_token_unique ON requests(access_token);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX requests_base_hash ON requests;
+ALTER TABLE requests DROP COLUMN base_hash;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Hash of the comparison of a request without its meta-analysis settings, so that a finished comparison is reused by
+-- the requests that only differ in their meta-analysis or GO term
+ALTER TABLE requests ADD base_hash CHAR(64) NULL COMMENT "Hash of the comparison without the meta-analysis settings";
+
+CREATE INDEX requests_base_hash ON requests(base_hash);
@@ -0,0 +1,29 @@
+-- Your SQL goes here
+
+-- Explicit state of each request (queued, assigned, running, downloading, extracting, completed, failed, cancelled, expired)
+ALTER TABLE requests ADD state VARCHAR(16) NOT NULL DEFAULT "queued" COMMENT "State of the request, which only changes through legal transitions";
+
+-- The state of the existing requests is derived from their latest job that was not requeued (status code -6)
+UPDATE requests SET state = (SELECT CASE WHEN jobs.status_code = 0 AND jobs.completion_date IS NULL THEN 'running'
+                                         WHEN jobs.status_code = 0 THEN 'completed'
+                                         WHEN jobs.status_code = -7 THEN 'cancelled'
+                                         WHEN jobs.status_code = -4 THEN 'expired'
+                                         ELSE 'failed' END
+                             FROM jobs WHERE jobs.request_id = requests.id AND jobs.status_code <> -6
+                             ORDER BY jobs.id DESC LIMIT 1)
+WHERE EXISTS (SELECT jobs.id FROM jobs WHERE jobs.request_id = requests.id AND jobs.status_code <> -6);
+
+CREATE INDEX requests_state_index ON requests(state);
+
+CREATE TABLE job_events
+( id BIGINT NOT NULL AUTO_INCREMENT,
+  request_id BIGINT NOT NULL,
+  job_id BIGINT NULL COMMENT "Job of the transition, if the request had one",
+  from_state VARCHAR(16) NOT NULL,
+  to_state VARCHAR(16) NOT NULL,
+  reason VARCHAR(255) NOT NULL DEFAULT "",
+  event_date DATETIME NOT NULL DEFAULT NOW(),
+  CONSTRAINT job_events_pk PRIMARY KEY (id)
+);
+
+CREATE INDEX job_events_request_id_index ON job_events(request_id);
@@ -0,0 +1,15 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE node_files MODIFY COLUMN node_id TINYINT NOT NULL;
+
+ALTER TABLE scheduling_decisions MODIFY COLUMN node_id TINYINT NOT NULL;
+
+ALTER TABLE nodes MODIFY COLUMN id TINYINT NOT NULL AUTO_INCREMENT,
+                  MODIFY COLUMN cores TINYINT NOT NULL;
+
+ALTER TABLE jobs MODIFY COLUMN node_id TINYINT NOT NULL,
+                 MODIFY COLUMN status_code TINYINT NOT NULL DEFAULT -1,
+                 MODIFY COLUMN progress TINYINT NOT NULL DEFAULT 0 COMMENT "Last reported percentage of the job";
+
+ALTER TABLE requests MODIFY COLUMN comparison_mode TINYINT NOT NULL DEFAULT 0 COMMENT "Whole structure (0), Domain (1), Segment (2)",
+                     MODIFY COLUMN alignment_level TINYINT NOT NULL DEFAULT 0 COMMENT "1D (0), 2D (1), hydrophobicity (2), mixed (3)";
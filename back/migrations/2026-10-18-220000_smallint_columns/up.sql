@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+-- The node ids, the settings of the requests and the status codes of the jobs are stored as SMALLINT, as in the other
+-- backends, so that the columns match the types of back/src/schema.rs
+ALTER TABLE requests MODIFY COLUMN comparison_mode SMALLINT NOT NULL DEFAULT 0 COMMENT "Whole structure (0), Domain (1), Segment (2)",
+                     MODIFY COLUMN alignment_level SMALLINT NOT NULL DEFAULT 0 COMMENT "1D (0), 2D (1), hydrophobicity (2), mixed (3)";
+
+ALTER TABLE jobs MODIFY COLUMN node_id SMALLINT NOT NULL,
+                 MODIFY COLUMN status_code SMALLINT NOT NULL DEFAULT -1,
+                 MODIFY COLUMN progress SMALLINT NOT NULL DEFAULT 0 COMMENT "Last reported percentage of the job";
+
+ALTER TABLE nodes MODIFY COLUMN id SMALLINT NOT NULL AUTO_INCREMENT,
+                  MODIFY COLUMN cores SMALLINT NOT NULL;
+
+ALTER TABLE scheduling_decisions MODIFY COLUMN node_id SMALLINT NOT NULL;
+
+ALTER TABLE node_files MODIFY COLUMN node_id SMALLINT NOT NULL;
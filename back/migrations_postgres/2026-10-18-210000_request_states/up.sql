@@ -0,0 +1,29 @@
+-- Your SQL goes here
+
+-- Explicit state of each request, equivalent to the migration of the MariaDB schema
+ALTER TABLE requests ADD state VARCHAR(16) NOT NULL DEFAULT 'queued';
+
+-- The state of the existing requests is derived from their latest job that was not requeued (status code -6)
+UPDATE requests SET state = (SELECT CASE WHEN jobs.status_code = 0 AND jobs.completion_date IS NULL THEN 'running'
+                                         WHEN jobs.status_code = 0 THEN 'completed'
+                                         WHEN jobs.status_code = -7 THEN 'cancelled'
+                                         WHEN jobs.status_code = -4 THEN 'expired'
+                                         ELSE 'failed' END
+                             FROM jobs WHERE jobs.request_id = requests.id AND jobs.status_code <> -6
+                             ORDER BY jobs.id DESC LIMIT 1)
+WHERE EXISTS (SELECT jobs.id FROM jobs WHERE jobs.request_id = requests.id AND jobs.status_code <> -6);
+
+CREATE INDEX requests_state_index ON requests(state);
+
+CREATE TABLE job_events
+( id BIGSERIAL NOT NULL,
+  request_id BIGINT NOT NULL,
+  job_id BIGINT NULL,
+  from_state VARCHAR(16) NOT NULL,
+  to_state VARCHAR(16) NOT NULL,
+  reason VARCHAR(255) NOT NULL DEFAULT '',
+  event_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT job_events_pk PRIMARY KEY (id)
+);
+
+CREATE INDEX job_events_request_id_index ON job_events(request_id);
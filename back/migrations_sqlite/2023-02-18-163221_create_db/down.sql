@@ -0,0 +1,17 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE batch_requests;
+DROP TABLE batches;
+DROP TABLE rate_limit_buckets;
+DROP TABLE api_key_requests;
+DROP TABLE api_keys;
+DROP TABLE admin_audit_log;
+DROP TABLE admin_sessions;
+DROP TABLE admins;
+DROP TABLE node_files;
+DROP TABLE scheduling_decisions;
+DROP TABLE candidate_lists;
+DROP TABLE cached_features;
+DROP TABLE nodes;
+DROP TABLE jobs;
+DROP TABLE requests;
@@ -0,0 +1,169 @@
+-- Your SQL goes here
+
+-- Schema of the SQLite backend, equivalent to the MariaDB schema after all the migrations in back/migrations.
+-- The dates are stored as text in UTC, which is the clock of CURRENT_TIMESTAMP in SQLite.
+
+CREATE TABLE requests
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  reference VARCHAR(255) NOT NULL,
+  candidates_list_id INT NOT NULL DEFAULT -1,
+  custom_list TEXT NOT NULL DEFAULT '',
+  uncached TEXT NOT NULL DEFAULT '',
+  hash_value VARCHAR(255) NOT NULL,
+  creation_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  meta BOOLEAN NOT NULL DEFAULT 0,
+  go_term VARCHAR(255) NOT NULL DEFAULT '',
+  comparison_mode SMALLINT NOT NULL DEFAULT 0,
+  segment_start INT NOT NULL DEFAULT -1,
+  segment_end INT NOT NULL DEFAULT -1,
+  alignment_level SMALLINT NOT NULL DEFAULT 0,
+  views BIGINT NOT NULL DEFAULT 0,
+  access_token VARCHAR(64) NULL,
+  base_hash VARCHAR(64) NULL
+);
+
+CREATE TABLE jobs
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  request_id BIGINT NOT NULL,
+  node_id SMALLINT NOT NULL,
+  assignment_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  completion_date TIMESTAMP,
+  last_checked TIMESTAMP,
+  status_code SMALLINT NOT NULL DEFAULT -1,
+  secure_hash VARCHAR(255) NOT NULL DEFAULT '',
+  retries INT NOT NULL DEFAULT 0,
+  stage VARCHAR(64) NOT NULL DEFAULT '',
+  progress SMALLINT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE nodes
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  ip VARCHAR(255) NOT NULL,
+  domain VARCHAR(255) NOT NULL DEFAULT '',
+  active BOOLEAN NOT NULL DEFAULT 0,
+  working BOOLEAN NOT NULL DEFAULT 0,
+  cores SMALLINT NOT NULL,
+  sync_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  failed_checks INT NOT NULL DEFAULT 0,
+  drained BOOLEAN NOT NULL DEFAULT 0
+);
+
+CREATE TABLE cached_features
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  structure_id VARCHAR(255) NOT NULL
+);
+
+CREATE TABLE candidate_lists
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  title VARCHAR(255) NOT NULL
+);
+
+CREATE TABLE scheduling_decisions
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  request_id BIGINT NOT NULL,
+  node_id SMALLINT NOT NULL,
+  strategy VARCHAR(255) NOT NULL,
+  ranking TEXT NOT NULL DEFAULT '',
+  candidate_count INT NOT NULL DEFAULT 0,
+  large_job BOOLEAN NOT NULL DEFAULT 0,
+  decision_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE node_files
+( node_id SMALLINT NOT NULL,
+  file_path VARCHAR(255) NOT NULL,
+  file_hash VARCHAR(64) NOT NULL,
+  sync_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT node_files_pk PRIMARY KEY (node_id, file_path)
+);
+
+CREATE TABLE admins
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  username VARCHAR(64) NOT NULL,
+  password_hash VARCHAR(255) NOT NULL,
+  active BOOLEAN NOT NULL DEFAULT 1,
+  creation_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT admins_username_unique UNIQUE (username)
+);
+
+CREATE TABLE admin_sessions
+( token_hash VARCHAR(64) NOT NULL,
+  admin_id INT NOT NULL,
+  creation_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expiration_date TIMESTAMP NOT NULL,
+  CONSTRAINT admin_sessions_pk PRIMARY KEY (token_hash)
+);
+
+CREATE TABLE admin_audit_log
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  admin_id INT NOT NULL,
+  actor VARCHAR(64) NOT NULL,
+  action VARCHAR(255) NOT NULL,
+  payload TEXT NOT NULL DEFAULT '',
+  status_code INT NOT NULL,
+  action_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE api_keys
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  owner VARCHAR(64) NOT NULL,
+  key_prefix VARCHAR(11) NOT NULL,
+  key_hash VARCHAR(64) NOT NULL,
+  daily_quota INT NOT NULL,
+  active BOOLEAN NOT NULL DEFAULT 1,
+  admin_id INT NOT NULL,
+  creation_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_used_date TIMESTAMP NULL,
+  CONSTRAINT api_keys_key_hash_unique UNIQUE (key_hash)
+);
+
+CREATE TABLE api_key_requests
+( id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  api_key_id INT NOT NULL,
+  request_id BIGINT NOT NULL,
+  submission_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE rate_limit_buckets
+( bucket_key VARCHAR(191) NOT NULL,
+  tokens DOUBLE NOT NULL,
+  refill_time BIGINT NOT NULL,
+  CONSTRAINT rate_limit_buckets_pk PRIMARY KEY (bucket_key)
+);
+
+CREATE TABLE batches
+( id VARCHAR(36) NOT NULL,
+  api_key_id INT NULL,
+  creation_date TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT batches_pk PRIMARY KEY (id)
+);
+
+CREATE TABLE batch_requests
+( batch_id VARCHAR(36) NOT NULL,
+  request_id BIGINT NOT NULL,
+  position INT NOT NULL,
+  CONSTRAINT batch_requests_pk PRIMARY KEY (batch_id, request_id)
+);
+
+CREATE INDEX structure_id_index ON cached_features(structure_id);
+CREATE INDEX hash_value_index ON requests(hash_value);
+CREATE UNIQUE INDEX requests_access_token_unique ON requests(access_token);
+CREATE INDEX requests_base_hash ON requests(base_hash);
+CREATE INDEX completion_date_index ON jobs(completion_date);
+CREATE INDEX last_checked_index ON jobs(last_checked);
+CREATE INDEX request_id_index ON jobs(request_id);
+CREATE INDEX assignment_date_index ON jobs(assignment_date);
+CREATE INDEX sync_date_index ON nodes(sync_date);
+CREATE INDEX scheduling_request_id_index ON scheduling_decisions(request_id);
+CREATE INDEX admin_sessions_admin_id_index ON admin_sessions(admin_id);
+CREATE INDEX admin_audit_log_admin_id_index ON admin_audit_log(admin_id);
+CREATE INDEX api_key_requests_key_date_index ON api_key_requests(api_key_id, submission_date);
+CREATE INDEX rate_limit_buckets_refill_time_index ON rate_limit_buckets(refill_time);
+
+INSERT INTO candidate_lists (title)
+VALUES ('Pub_Viral_PDB_Candidate_Set (41674 structures)'),
+('Human_PDB_Candidate_Set_v1 (161629 structures)'),
+('Human_AF4_Candidate_Set');
+
+INSERT INTO nodes (ip, active, cores)
+VALUES ('localhost:55555', 1, 1);
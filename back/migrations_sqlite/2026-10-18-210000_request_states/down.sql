@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE job_events;
+DROP INDEX requests_state_index;
+ALTER TABLE requests DROP COLUMN state;
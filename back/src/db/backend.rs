@@ -0,0 +1,213 @@
+use std::{env, sync::atomic::{AtomicU64, Ordering}, time::Duration};
+use diesel::{r2d2::{self, ConnectionManager, CustomizeConnection, Pool}, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+
+/*
+
+The database backend of the root node is selected at build time by one of the cargo features mysql (MariaDB, the default),
+postgres and sqlite. The queries of the access layer are written in the diesel DSL, so that they are portable, except
+for the few date computations and session settings that each backend handles differently.
+
+The queries run in the blocking thread pool of tokio on connections of an r2d2 pool, whose size and timeouts are
+set in the configuration. The database cancels the statements that exceed the statement timeout (MariaDB and PostgreSQL),
+while the callers stop waiting for them after the connection and the statement timeouts. Each pool keeps metrics on
+its connections and queries.
+
+*/
+
+#[cfg(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")))]
+compile_error!("Select a database backend with one of the features mysql, postgres or sqlite");
+
+#[cfg(any(all(feature = "mysql", feature = "postgres"), all(feature = "mysql", feature = "sqlite"),
+          all(feature = "postgres", feature = "sqlite")))]
+compile_error!("Select only one database backend, e.g. with --no-default-features --features sqlite");
+
+#[cfg(feature = "mysql")]
+pub type DbConnection = diesel::MysqlConnection;
+#[cfg(feature = "postgres")]
+pub type DbConnection = diesel::PgConnection;
+#[cfg(feature = "sqlite")]
+pub type DbConnection = diesel::SqliteConnection;
+
+pub type DbPool = Pool<ConnectionManager<DbConnection>>;
+
+// Name of the selected backend, as reported in the metrics
+#[cfg(feature = "mysql")]
+pub const BACKEND_NAME: &str = "mysql";
+#[cfg(feature = "postgres")]
+pub const BACKEND_NAME: &str = "postgres";
+#[cfg(feature = "sqlite")]
+pub const BACKEND_NAME: &str = "sqlite";
+
+// Whole units of time (in seconds) that have passed since the date of a column, according to the clock of the database.
+// The dates are compared with the clock of the database, since it provides the default values of the date columns.
+#[cfg(feature = "mysql")]
+pub fn elapsed_time(column: &str, unit_seconds: i64) -> String {
+    format!("(TIMESTAMPDIFF(SECOND, {}, NOW()) DIV {})", column, unit_seconds)
+}
+#[cfg(feature = "postgres")]
+pub fn elapsed_time(column: &str, unit_seconds: i64) -> String {
+    format!("(CAST(EXTRACT(EPOCH FROM (LOCALTIMESTAMP - {})) AS BIGINT) / {})", column, unit_seconds)
+}
+#[cfg(feature = "sqlite")]
+pub fn elapsed_time(column: &str, unit_seconds: i64) -> String {
+    format!("((CAST(strftime('%s', 'now') AS INTEGER) - CAST(strftime('%s', {}) AS INTEGER)) / {})", column, unit_seconds)
+}
+
+// Settings of the connection pool
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSettings {
+    pub size: u32,
+    pub connection_timeout: Duration,
+    // No timeout when it is not set
+    pub statement_timeout: Option<Duration>,
+}
+
+impl PoolSettings {
+
+    // Read the settings from the configuration, where the timeouts are in seconds
+    pub fn from_env() -> Result<Self> {
+        let statement_timeout = env::var("DB_STATEMENT_TIMEOUT")?.parse::<u64>()?;
+        Ok(Self { size: env::var("DB_POOL_SIZE")?.parse::<u32>()?,
+                  connection_timeout: Duration::from_secs(env::var("DB_CONNECTION_TIMEOUT")?.parse::<u64>()?),
+                  statement_timeout: (statement_timeout > 0).then(|| Duration::from_secs(statement_timeout)) })
+    }
+
+    // Time after which a caller stops waiting for a query: the connection timeout and the statement timeout
+    pub fn query_deadline(&self) -> Option<Duration> {
+        self.statement_timeout.map(|statement_timeout| self.connection_timeout + statement_timeout)
+    }
+}
+
+// Session settings of the new connections, which limit the execution time of the statements.
+// The statements of MariaDB are limited by max_statement_time (MySQL servers lack it, so the timeout should be disabled for them).
+#[cfg(feature = "mysql")]
+fn session_statements(statement_timeout: Option<Duration>) -> Vec<String> {
+    statement_timeout.map(|timeout| format!("SET SESSION max_statement_time = {}", timeout.as_secs_f64())).into_iter().collect()
+}
+#[cfg(feature = "postgres")]
+fn session_statements(statement_timeout: Option<Duration>) -> Vec<String> {
+    statement_timeout.map(|timeout| format!("SET statement_timeout = {}", timeout.as_millis())).into_iter().collect()
+}
+// SQLite cannot cancel its statements, but the connections to the same file wait for the locks of each other 
+// instead of failing at once, up to the statement timeout
+#[cfg(feature = "sqlite")]
+fn session_statements(statement_timeout: Option<Duration>) -> Vec<String> {
+    vec![format!("PRAGMA busy_timeout = {}", statement_timeout.map_or(10000, |timeout| timeout.as_millis()))]
+}
+
+#[derive(Debug)]
+struct SessionCustomizer {
+    statements: Vec<String>,
+}
+
+impl CustomizeConnection<DbConnection, r2d2::Error> for SessionCustomizer {
+    fn on_acquire(&self, connection: &mut DbConnection) -> std::result::Result<(), r2d2::Error> {
+        for statement in &self.statements {
+            diesel::sql_query(statement).execute(connection).map_err(r2d2::Error::QueryError)?;
+        }
+        Ok(())
+    }
+}
+
+// Create the connection pool of a database
+pub fn create_pool(database_url: &str, settings: &PoolSettings) -> Result<DbPool> {
+    let db_manager = ConnectionManager::<DbConnection>::new(database_url);
+    let db_pool = Pool::builder().test_on_check_out(true)
+                                 .max_size(settings.size)
+                                 .connection_timeout(settings.connection_timeout)
+                                 .connection_customizer(Box::new(SessionCustomizer { statements: session_statements(settings.statement_timeout) }))
+                                 .build(db_manager)?;
+    Ok(db_pool)
+}
+
+// Error of a query that exceeded the deadline of its caller
+#[derive(Debug)]
+pub struct QueryTimeout;
+
+impl std::fmt::Display for QueryTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The database query timed out")
+    }
+}
+
+impl std::error::Error for QueryTimeout {}
+
+// Counters of the connections and the queries of a pool
+#[derive(Debug, Default)]
+pub struct QueryCounters {
+    queries: AtomicU64,
+    failed_queries: AtomicU64,
+    timed_out_queries: AtomicU64,
+    failed_checkouts: AtomicU64,
+    checkout_micros: AtomicU64,
+    max_checkout_micros: AtomicU64,
+    query_micros: AtomicU64,
+    max_query_micros: AtomicU64,
+}
+
+impl QueryCounters {
+
+    // Record the time that a connection was awaited, or a failure to get one
+    pub fn record_checkout(&self, wait: Duration, succeeded: bool) {
+        if !succeeded {
+            self.failed_checkouts.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let micros = wait.as_micros() as u64;
+        self.checkout_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_checkout_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    // Record the execution time and the outcome of a query
+    pub fn record_query(&self, duration: Duration, succeeded: bool) {
+        let micros = duration.as_micros() as u64;
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        if !succeeded {
+            self.failed_queries.fetch_add(1, Ordering::Relaxed);
+        }
+        self.query_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_query_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    // Record a query whose caller stopped waiting for it
+    pub fn record_timeout(&self) {
+        self.timed_out_queries.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Snapshot of the state of a pool and its counters, with the times in milliseconds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PoolMetrics {
+    backend: String,
+    pool_size: u32,
+    connections: u32,
+    idle_connections: u32,
+    queries: u64,
+    failed_queries: u64,
+    timed_out_queries: u64,
+    failed_checkouts: u64,
+    average_checkout_ms: f64,
+    max_checkout_ms: f64,
+    average_query_ms: f64,
+    max_query_ms: f64,
+}
+
+impl PoolMetrics {
+
+    // Collect the metrics of a pool
+    pub fn collect(db_pool: &DbPool, counters: &QueryCounters) -> Self {
+        let state = db_pool.state();
+        let queries = counters.queries.load(Ordering::Relaxed);
+        let average = |micros: &AtomicU64, count: u64| if count == 0 { 0.0 } else { micros.load(Ordering::Relaxed) as f64 / count as f64 / 1000.0 };
+        Self { backend: BACKEND_NAME.to_string(), pool_size: db_pool.max_size(), connections: state.connections,
+               idle_connections: state.idle_connections, queries, failed_queries: counters.failed_queries.load(Ordering::Relaxed),
+               timed_out_queries: counters.timed_out_queries.load(Ordering::Relaxed),
+               failed_checkouts: counters.failed_checkouts.load(Ordering::Relaxed),
+               average_checkout_ms: average(&counters.checkout_micros, queries),
+               max_checkout_ms: counters.max_checkout_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+               average_query_ms: average(&counters.query_micros, queries),
+               max_query_ms: counters.max_query_micros.load(Ordering::Relaxed) as f64 / 1000.0 }
+    }
+}
@@ -1,22 +1,24 @@
-use diesel::{prelude::*, sql_types::{BigInt, Bool, Integer, Char, TinyInt}, dsl::{sql, count}, sql_query}; //, debug_query};
+use diesel::{prelude::*, sql_types::BigInt, dsl::{sql, count, now}}; //, debug_query};
 use dotenvy::dotenv;
-use rand::Rng;
-use std::env;
-use diesel::{
-    r2d2::{ConnectionManager, Pool},
-    MysqlConnection,
-};
+use std::{env, sync::Arc, time::{Duration, Instant}};
 use anyhow::Result;
-use crate::{db::models::{NewCachedFeatureId, CachedFeatureId}, schema::{requests::{self}, jobs::{self}, nodes::{self, working}, cached_features, candidate_lists}};
+use crate::{db::models::{NewCachedFeatureId, CachedFeatureId}, schema::{requests::{self}, jobs::{self}, nodes::{self, working}, cached_features, candidate_lists, scheduling_decisions, node_files,
+                   admins, admin_sessions, admin_audit_log, api_keys, api_key_requests, rate_limit_buckets,
+                   batches, batch_requests, job_events}};
 
-use super::models::{NewRequest, CandidateList, NewJob, Node, QueriedRequest, QueriedJob, FinalizedRequest, Uncached};
+use super::backend::{self, DbConnection, DbPool, PoolMetrics, PoolSettings, QueryCounters, QueryTimeout};
+use super::models::{JOB_REQUEUED, NewRequest, CandidateList, Job, NewJob, Node, QueriedRequest, QueriedJob, FinalizedRequest, Uncached, NewSchedulingDecision, NewNodeFile, NewNode, NodeChanges,
+                    Admin, NewAdmin, NewAdminSession, NewAuditEntry, ApiKey, NewApiKey, ApiKeyChanges, NewApiKeyRequest, KeyedRequest, RateLimitBucket,
+                    NewBatch, NewBatchRequest, BatchedRequest, NewJobEvent, RequestState, IllegalTransition, QuotaExceeded};
 
 
-// This is the MariaDB access layer of MachaonWeb
+// This is the database access layer of MachaonWeb (MariaDB, PostgreSQL or SQLite, see the backend module)
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DatabaseHandler{ 
-    connection_pool: Pool<ConnectionManager<MysqlConnection>>,
+    connection_pool: DbPool,
+    counters: Arc<QueryCounters>,
+    query_deadline: Option<Duration>,
 }
 
 impl DatabaseHandler {
@@ -25,334 +27,1047 @@ impl DatabaseHandler {
     pub fn new() -> Result<Self> {
         dotenv().ok(); 
         let database_url = env::var("DATABASE_URL")?; 
-        let db_manager = ConnectionManager::<MysqlConnection>::new(&database_url);
-        let db_pool = Pool::builder().test_on_check_out(true).build(db_manager)?; 
-        Ok(Self { connection_pool : db_pool })
+        let settings = PoolSettings::from_env()?;
+        Ok(Self { connection_pool : backend::create_pool(&database_url, &settings)?, counters: Arc::new(QueryCounters::default()),
+                  query_deadline: settings.query_deadline() })
+    }
+
+    // Run a query on a connection of the pool in the blocking threads of tokio, so that a slow database does not stall
+    // the async tasks. The caller stops waiting after the deadline of the pool, while the database cancels the statement.
+    async fn run<T, F>(&self, query: F) -> Result<T>
+    where T: Send + 'static, F: FnOnce(&mut DbConnection) -> Result<T> + Send + 'static {
+        let connection_pool = self.connection_pool.clone();
+        let counters = self.counters.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            let checkout_start = Instant::now();
+            let connection = connection_pool.get();
+            counters.record_checkout(checkout_start.elapsed(), connection.is_ok());
+            let mut connection = connection?;
+            let query_start = Instant::now();
+            let result = query(&mut connection);
+            counters.record_query(query_start.elapsed(), result.is_ok());
+            result
+        });
+        match self.query_deadline {
+            Some(deadline) => match tokio::time::timeout(deadline, task).await {
+                Ok(result) => result?,
+                Err(_) => {
+                    self.counters.record_timeout();
+                    Err(QueryTimeout.into())
+                }
+            },
+            None => task.await?
+        }
+    }
+
+    // Retrieve the metrics of the connection pool
+    pub fn get_pool_metrics(&self) -> PoolMetrics {
+        PoolMetrics::collect(&self.connection_pool, &self.counters)
     }
  
     // Update the table of processed structure IDs with a new one
     pub async fn insert_structure_ids(&mut self, data: &Vec<String>) -> Result<()> {
-        let insert_values: Vec<NewCachedFeatureId> = data.into_iter().map(|entry| NewCachedFeatureId {structure_id : entry}).collect();
-        let db_connection = &mut self.connection_pool.get()?;
-
-        diesel::insert_into(cached_features::table)
-            .values(&insert_values)
-            .execute(db_connection)?;
-        Ok(())
+        let insert_values: Vec<NewCachedFeatureId> = data.iter().map(|entry| NewCachedFeatureId {structure_id : entry.clone()}).collect();
+        self.run(move |db_connection| {
+            diesel::insert_into(cached_features::table)
+                .values(&insert_values)
+                .execute(db_connection)?;
+            Ok(())
+        }).await
     }
 
     // Retrieve all the structure IDs which have been preprocessed
     pub async fn get_cached_pbd_ids(&self) -> Result<Vec<CachedFeatureId>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let results: Vec<CachedFeatureId> = cached_features::table.load::<CachedFeatureId>(db_connection)?;
-        Ok(results)
+        self.run(move |db_connection| {
+            let results: Vec<CachedFeatureId> = cached_features::table.load::<CachedFeatureId>(db_connection)?;
+            Ok(results)
+        }).await
     }
 
     // Retrieve the available preset candidate lists 
     pub async fn get_candidate_lists(&self) -> Result<Vec<CandidateList>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let results: Vec<CandidateList> = candidate_lists::table.load::<CandidateList>(db_connection)?;
-        Ok(results)
+        self.run(move |db_connection| {
+            let results: Vec<CandidateList> = candidate_lists::table.load::<CandidateList>(db_connection)?;
+            Ok(results)
+        }).await
     }
 
     // Retrieve how many nodes are currently designated as active
     pub async fn get_active_node_count(&self) -> Result<Option<i64>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result = nodes::table.select(count(nodes::id))
-                                      .filter(nodes::active.eq(true)).load::<i64>(db_connection)?;
-        Ok(result.into_iter().next())
+        self.run(move |db_connection| {
+            let result = nodes::table.select(count(nodes::id))
+                                          .filter(nodes::active.eq(true)).load::<i64>(db_connection)?;
+            Ok(result.into_iter().next())
+        }).await
+    }
+
+    // Retrieve all the nodes of the network
+    pub async fn get_nodes(&self) -> Result<Vec<Node>> {
+        self.run(move |db_connection| {
+            let result: Vec<Node> = nodes::table.order(nodes::id.asc()).load::<Node>(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve a node by its address
+    pub async fn get_node_by_address(&self, address : &str) -> Result<Option<Node>> {
+        let address = address.to_string();
+        self.run(move |db_connection| {
+            let result = nodes::table.filter(nodes::ip.eq(address))
+                                     .first::<Node>(db_connection)
+                                     .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Store a new node
+    pub async fn insert_node(&self, data : NewNode) -> Result<()> {
+        self.run(move |db_connection| {
+            diesel::insert_into(nodes::table)
+                .values(&data)
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Update the address, the domain or the cores of a node
+    pub async fn update_node(&self, node_id : &i16, data : NodeChanges) -> Result<()> {
+        let node_id = *node_id;
+        self.run(move |db_connection| {
+            diesel::update(nodes::table)
+                .filter(nodes::id.eq(node_id))
+                .set(&data)
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Update whether a node is drained, i.e. it receives no new jobs
+    pub async fn update_node_drained_state(&self, node_id : &i16, status : bool) -> Result<()> {
+        let node_id = *node_id;
+        self.run(move |db_connection| {
+            diesel::update(nodes::table)
+                .filter(nodes::id.eq(node_id))
+                .set(nodes::drained.eq(status))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Remove a node from the network along with the record of its synchronized files
+    pub async fn delete_node(&self, node_id : &i16) -> Result<()> {
+        let node_id = *node_id;
+        self.run(move |db_connection| {
+            db_connection.transaction::<_, anyhow::Error, _>(|connection| {
+                diesel::delete(node_files::table.filter(node_files::node_id.eq(node_id)))
+                    .execute(connection)?;
+                diesel::delete(nodes::table.filter(nodes::id.eq(node_id)))
+                    .execute(connection)?;
+                Ok(())
+            })?;
+            Ok(())
+        }).await
+    }
+
+    // Count the jobs that a node is currently executing
+    pub async fn count_node_running_jobs(&self, node_id : &i16) -> Result<i64> {
+        let node_id = *node_id;
+        self.run(move |db_connection| {
+            let result: i64 = jobs::table.select(count(jobs::id))
+                                         .filter(jobs::node_id.eq(node_id)
+                                         .and(jobs::completion_date.is_null())
+                                         .and(jobs::status_code.eq(0)))
+                                         .first(db_connection)?;
+            Ok(result)
+        }).await
     }
 
     // Retrieve information on the nodes that are currently designated as active
-    pub async fn get_available_nodes(&self) -> Result<Vec<Node>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result: Vec<Node> = nodes::table.filter(nodes::working.eq(false).and(nodes::active.eq(true)))
-                                            .order(nodes::sync_date.asc()).load::<Node>(db_connection)?;
-        Ok(result)
+    pub async fn get_active_nodes(&self) -> Result<Vec<Node>> {
+        self.run(move |db_connection| {
+            let result: Vec<Node> = nodes::table.filter(nodes::active.eq(true).and(nodes::drained.eq(false)))
+                                                .order(nodes::sync_date.asc()).load::<Node>(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve how many jobs each node is currently executing
+    pub async fn get_running_job_counts(&self) -> Result<Vec<(i16, i64)>> {
+        self.run(move |db_connection| {
+            let result = jobs::table.group_by(jobs::node_id)
+                                    .select((jobs::node_id, count(jobs::id)))
+                                    .filter(jobs::completion_date.is_null().and(jobs::status_code.eq(0)))
+                                    .load::<(i16, i64)>(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve the highest core count among the active nodes
+    pub async fn get_max_active_cores(&self) -> Result<Option<i16>> {
+        self.run(move |db_connection| {
+            let result = nodes::table.select(nodes::cores)
+                                     .filter(nodes::active.eq(true).and(nodes::drained.eq(false)))
+                                     .order(nodes::cores.desc())
+                                     .first::<i16>(db_connection)
+                                     .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve the date of the latest job assignment of each node
+    pub async fn get_last_assignment_dates(&self) -> Result<Vec<(i16, Option<chrono::NaiveDateTime>)>> {
+        self.run(move |db_connection| {
+            let result = jobs::table.group_by(jobs::node_id)
+                                    .select((jobs::node_id, diesel::dsl::max(jobs::assignment_date)))
+                                    .load::<(i16, Option<chrono::NaiveDateTime>)>(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Store the outcome of a scheduling decision for later analysis
+    pub async fn insert_scheduling_decision(&self, data : NewSchedulingDecision) -> Result<()> {
+        self.run(move |db_connection| {
+            diesel::insert_into(scheduling_decisions::table)
+                .values(&data)
+                .execute(db_connection)?;
+            Ok(())
+        }).await
     }
 
     // Retrieve how many jobs are currently being executed
     pub async fn get_running_jobs_count(&self) -> Result<Option<i64>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result = requests::table.select(count(requests::id))
-                                    .inner_join(jobs::table.on(requests::id.eq(jobs::request_id)))
-                                    .filter(jobs::completion_date.is_null().and(jobs::status_code.eq(0))).load::<i64>(db_connection)?;
-        Ok(result.into_iter().next())
+        self.run(move |db_connection| {
+            let result = requests::table.select(count(requests::id))
+                                        .inner_join(jobs::table.on(requests::id.eq(jobs::request_id)))
+                                        .filter(jobs::completion_date.is_null().and(jobs::status_code.eq(0))).load::<i64>(db_connection)?;
+            Ok(result.into_iter().next())
+        }).await
     }
 
     // Retrieve how many requests are currently being queued
     pub async fn get_queued_requests_count(&self) -> Result<Option<i64>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result = requests::table.select(count(requests::id))
-                                    .left_join(jobs::table.on(requests::id.eq(jobs::request_id)))
-                                    .filter(jobs::id.is_null()).load::<i64>(db_connection)?;
-        Ok(result.into_iter().next())
+        self.run(move |db_connection| {
+            let result = requests::table.select(count(requests::id))
+                                        .filter(requests::state.eq(RequestState::Queued.as_str())).load::<i64>(db_connection)?;
+            Ok(result.into_iter().next())
+        }).await
     }
 
     // Check if a request has a finished job. A finished job has a registered SHA-256 file hash.
     pub async fn get_fullfilled_request(&self, hash: &str, meta: &bool, go_term: &str) -> Result<Option<String>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result = jobs::table.select(jobs::secure_hash)
-                                    .left_join(requests::table.on(jobs::request_id.eq(requests::id)))
-                                    .filter(jobs::status_code.eq(0)
-                                    .and(requests::hash_value.eq(&hash))
-                                    .and(requests::meta.eq(meta))
-                                    .and(requests::go_term.eq(go_term)))
-                                    .order(requests::id.desc())
-                                    .limit(1)
-                                    .load::<String>(db_connection)?;
-        Ok(result.into_iter().next())
-    }
-
-    // Get the row id of a request using its hash
-    pub async fn get_request_by_hash(&self, hash_code : &str) -> Result<Option<i64>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result = requests::table.select(requests::id)
-                                    .filter(requests::hash_value.eq(hash_code))
-                                    .order(requests::id.desc())
-                                    .limit(1)
-                                    .load::<i64>(db_connection)?;
-        Ok(result.into_iter().next())
-    }
-
-    // Retrieve information of a request using its id and hash
-    pub async fn get_request_with_proof(&self, request_id: &i64, hash_code : &str) -> Result<Option<FinalizedRequest>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result = sql_query("SELECT r.*, 
-                                                        j.secure_hash AS secure_hash, 
-                                                        cl.title As list_name,
-                                                        j.status_code AS status_code
-                                                        FROM requests AS r
-                                                        INNER JOIN jobs as j 
-                                                        ON r.id = j.request_id 
-                                                        LEFT JOIN candidate_lists as cl 
-                                                        ON r.candidates_list_id  = cl.id
-                                                        WHERE r.id = ?
-                                                        AND r.hash_value = ?
-                                                        ORDER BY j.completion_date DESC 
-                                                        LIMIT 1;")
-                                                        .bind::<BigInt, _>(request_id) 
-                                                        .bind::<Char, _>(hash_code) 
-                                                        .get_results(db_connection)?;
-        Ok(result.into_iter().next())
-    }
-
-    // Retrieve how many nodes are currently not executing a job
-    pub async fn get_available_node_count(&self) -> Result<Option<i64>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result = nodes::table.select(count(nodes::id))
-                                    .filter(nodes::active.eq(true).and(nodes::working.eq(false))).load::<i64>(db_connection)?;
-        Ok(result.into_iter().next())
+        let hash = hash.to_string();
+        let meta = *meta;
+        let go_term = go_term.to_string();
+        self.run(move |db_connection| {
+            let result = jobs::table.select(jobs::secure_hash)
+                                        .left_join(requests::table.on(jobs::request_id.eq(requests::id)))
+                                        .filter(jobs::status_code.eq(0)
+                                        .and(requests::state.eq(RequestState::Completed.as_str()))
+                                        .and(requests::hash_value.eq(&hash))
+                                        .and(requests::meta.eq(meta))
+                                        .and(requests::go_term.eq(go_term)))
+                                        .order(requests::id.desc())
+                                        .limit(1)
+                                        .load::<String>(db_connection)?;
+            Ok(result.into_iter().next())
+        }).await
+    }
+
+    // Find the latest finished job whose request has the same comparison (base hash) as a request with meta-analysis.
+    // The hash of that request and the node that ran the job are returned, so that the node reuses the outputs of the comparison.
+    pub async fn get_fullfilled_base(&self, base_hash: &str) -> Result<Option<(String, i16)>> {
+        let base_hash = base_hash.to_string();
+        self.run(move |db_connection| {
+            let result = jobs::table.inner_join(requests::table.on(jobs::request_id.eq(requests::id)))
+                                        .select((requests::hash_value, jobs::node_id))
+                                        .filter(jobs::status_code.eq(0)
+                                        .and(jobs::completion_date.is_not_null())
+                                        .and(jobs::node_id.ne(-1))
+                                        .and(requests::base_hash.eq(base_hash)))
+                                        .order(jobs::id.desc())
+                                        .first::<(String, i16)>(db_connection)
+                                        .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Get the row id of a request using its access token
+    pub async fn get_request_by_access_token(&self, access_token : &str) -> Result<Option<i64>> {
+        let access_token = access_token.to_string();
+        self.run(move |db_connection| {
+            let result = requests::table.select(requests::id)
+                                        .filter(requests::access_token.eq(access_token))
+                                        .first::<i64>(db_connection)
+                                        .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve information of a request using its id and its proof: the access token or,
+    // for the requests that were stored before the access tokens, the hash
+    pub async fn get_request_with_proof(&self, request_id: &i64, proof : &str) -> Result<Option<FinalizedRequest>> {
+        let request_id = *request_id;
+        let proof = proof.to_string();
+        self.run(move |db_connection| {
+            let result = requests::table.inner_join(jobs::table.on(requests::id.eq(jobs::request_id)))
+                                        .left_join(candidate_lists::table.on(requests::candidates_list_id.eq(candidate_lists::id)))
+                                        .select((requests::id, requests::reference, requests::candidates_list_id, requests::custom_list,
+                                                 requests::uncached, requests::hash_value, requests::creation_date, requests::meta,
+                                                 requests::go_term, requests::comparison_mode, requests::segment_start, requests::segment_end,
+                                                 requests::alignment_level, requests::views, jobs::secure_hash, candidate_lists::title.nullable(),
+                                                 jobs::status_code))
+                                        .filter(requests::id.eq(request_id)
+                                        .and(requests::access_token.eq(&proof)
+                                        .or(requests::access_token.is_null().and(requests::hash_value.eq(&proof))))
+                                        .and(jobs::status_code.ne(JOB_REQUEUED)))
+                                        .order(jobs::completion_date.desc())
+                                        .first::<FinalizedRequest>(db_connection)
+                                        .optional()?;
+            Ok(result)
+        }).await
     }
 
     // Create a new request row
-    pub async fn insert_request(&self, data : &NewRequest<'_>) -> Result<()> {
-        let db_connection = &mut self.connection_pool.get()?; 
+    pub async fn insert_request(&self, data : NewRequest) -> Result<()> {
+        self.run(move |db_connection| {
+            diesel::insert_into(requests::table)
+                .values(&data)
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
 
-        diesel::insert_into(requests::table)
-            .values(data)
-            .execute(db_connection)?;
-        Ok(())
+    // Create a new job row for a queued request and move the request to the given state. The job is not stored
+    // when the transition is not legal, e.g. when the request was cancelled in the meantime. The id of the job is returned.
+    pub async fn insert_job(&self, data : NewJob, next : RequestState, reason : &str) -> Result<i64> {
+        let reason = reason.to_string();
+        self.run(move |db_connection| {
+            db_connection.transaction::<_, anyhow::Error, _>(|connection| {
+                diesel::insert_into(jobs::table)
+                    .values(&data)
+                    .execute(connection)?;
+                let job_id = jobs::table.select(jobs::id)
+                                        .filter(jobs::request_id.eq(data.request_id))
+                                        .order(jobs::id.desc())
+                                        .first::<i64>(connection)?;
+                Self::change_state(connection, data.request_id, Some(job_id), next, &reason)?;
+                Ok(job_id)
+            })
+        }).await
     }
 
-    // Create a new job row
-    pub async fn insert_job(&self, data : &NewJob<'_>) -> Result<()> {
-        let db_connection = &mut self.connection_pool.get()?; 
+    // Move a request to another state, as long as the transition is legal from its current state
+    pub async fn transition_request(&self, request_id : &i64, job_id : Option<i64>, next : RequestState, reason : &str) -> Result<()> {
+        let request_id = *request_id;
+        let reason = reason.to_string();
+        self.run(move |db_connection| {
+            db_connection.transaction::<_, anyhow::Error, _>(|connection| {
+                Self::change_state(connection, request_id, job_id, next, &reason)
+            })
+        }).await
+    }
 
-        diesel::insert_into(jobs::table)
-            .values(data)
-            .execute(db_connection)?;
+    // Change the state of a request within a transaction and record the transition. The update only applies to 
+    // the state that was read, so that a concurrent transition of the same request makes this one fail.
+    fn change_state(connection: &mut DbConnection, request_id: i64, job_id: Option<i64>, next: RequestState, reason: &str) -> Result<()> {
+        let current = requests::table.select(requests::state)
+                                     .filter(requests::id.eq(request_id))
+                                     .first::<String>(connection)?;
+        let legal = current.parse::<RequestState>().is_ok_and(|state| state.can_transition_to(next));
+        let updated = match legal {
+            true => diesel::update(requests::table.filter(requests::id.eq(request_id).and(requests::state.eq(&current))))
+                        .set(requests::state.eq(next.as_str()))
+                        .execute(connection)?,
+            false => 0
+        };
+        if updated == 0 {
+            return Err(IllegalTransition { request_id, from: current, to: next }.into());
+        }
+        let event = NewJobEvent { request_id, job_id, from_state: current, to_state: next.as_str().to_string(), reason: reason.to_string() };
+        diesel::insert_into(job_events::table)
+            .values(&event)
+            .execute(connection)?;
         Ok(())
     }
 
+    // Retrieve the state, the comparison mode and the candidate list of a request
+    pub async fn get_request_settings(&self, request_id : &i64) -> Result<Option<(RequestState, i16, i32)>> {
+        let request_id = *request_id;
+        self.run(move |db_connection| {
+            let result = requests::table.select((requests::state, requests::comparison_mode, requests::candidates_list_id))
+                                        .filter(requests::id.eq(request_id))
+                                        .first::<(String, i16, i32)>(db_connection)
+                                        .optional()?;
+            result.map(|(state, comparison_mode, list_id)| -> Result<(RequestState, i16, i32)> {
+                Ok((state.parse::<RequestState>()?, comparison_mode, list_id))
+            }).transpose()
+        }).await
+    }
+
+    // Retrieve the position of a queued request in the queue, in the order that the requests are assigned (1 for the next one)
+    pub async fn get_queue_position(&self, request_id : &i64) -> Result<i64> {
+        let request_id = *request_id;
+        self.run(move |db_connection| {
+            let result: i64 = requests::table.select(count(requests::id))
+                                             .filter(requests::state.eq(RequestState::Queued.as_str()).and(requests::id.le(request_id)))
+                                             .first(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve the seconds that have passed since the assignment of a job
+    pub async fn get_job_elapsed_seconds(&self, job_id : &i64) -> Result<Option<i64>> {
+        let job_id = *job_id;
+        self.run(move |db_connection| {
+            let elapsed_seconds = backend::elapsed_time("jobs.assignment_date", 1);
+            let result = jobs::table.select(sql::<BigInt>(&elapsed_seconds))
+                                    .filter(jobs::id.eq(job_id))
+                                    .first::<i64>(db_connection)
+                                    .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve the durations in seconds of the latest jobs that were completed on the nodes, optionally only those
+    // of a comparison mode and a candidate list
+    pub async fn get_job_durations(&self, comparison_mode : Option<i16>, list_id : Option<i32>, limit : i64) -> Result<Vec<i64>> {
+        self.run(move |db_connection| {
+            let mut query = jobs::table.inner_join(requests::table.on(requests::id.eq(jobs::request_id)))
+                                       .select((jobs::assignment_date, jobs::completion_date))
+                                       .filter(jobs::status_code.eq(0)
+                                       .and(jobs::node_id.ne(-1))
+                                       .and(jobs::completion_date.is_not_null())
+                                       .and(requests::state.eq(RequestState::Completed.as_str())))
+                                       .order(jobs::id.desc())
+                                       .limit(limit)
+                                       .into_boxed();
+            if let Some(comparison_mode) = comparison_mode {
+                query = query.filter(requests::comparison_mode.eq(comparison_mode));
+            }
+            if let Some(list_id) = list_id {
+                query = query.filter(requests::candidates_list_id.eq(list_id));
+            }
+            let result = query.load::<(chrono::NaiveDateTime, Option<chrono::NaiveDateTime>)>(db_connection)?;
+            Ok(result.into_iter()
+                     .filter_map(|(assignment_date, completion_date)| completion_date.map(|date| (date - assignment_date).num_seconds().max(0)))
+                     .collect())
+        }).await
+    }
+
+    // Retrieve the state of a request
+    pub async fn get_request_state(&self, request_id : &i64) -> Result<Option<RequestState>> {
+        let request_id = *request_id;
+        self.run(move |db_connection| {
+            let result = requests::table.select(requests::state)
+                                        .filter(requests::id.eq(request_id))
+                                        .first::<String>(db_connection)
+                                        .optional()?;
+            result.map(|state| state.parse::<RequestState>()).transpose()
+        }).await
+    }
+
     // Update the views of a request
     pub async fn update_view_count(&self, request_id : &i64) -> Result<()> {
-        let db_connection = &mut self.connection_pool.get()?; 
-        diesel::update(requests::table).set(requests::views.eq(requests::views + 1))
-            .filter(requests::id.eq(request_id))
-            .execute(db_connection)?; 
-        Ok(())
+        let request_id = *request_id;
+        self.run(move |db_connection| {
+            diesel::update(requests::table).set(requests::views.eq(requests::views + 1))
+                .filter(requests::id.eq(request_id))
+                .execute(db_connection)?; 
+            Ok(())
+        }).await
     }
 
     // Identify which of the provided structure IDs are not cached
-    pub async fn get_uncached_structure_ids(&self, data: &Vec<String>) -> Result<Vec<String>> { 
-        let db_connection = &mut self.connection_pool.get()?;
-        let query = cached_features::table.select(cached_features::structure_id)
-                                                        .filter(cached_features::structure_id.eq_any(data));
-        let results: Vec<String> = query.load::<String>(db_connection)?;
-        let uncached_ids: Vec<String> =  data.into_iter().filter(|&item| !results.contains(&item)).cloned().collect();
-        Ok(uncached_ids)
+    pub async fn get_uncached_structure_ids(&self, data: &[String]) -> Result<Vec<String>> {
+        let data = data.to_vec();
+        self.run(move |db_connection| {
+            let query = cached_features::table.select(cached_features::structure_id)
+                                                            .filter(cached_features::structure_id.eq_any(&data));
+            let results: Vec<String> = query.load::<String>(db_connection)?;
+            let uncached_ids: Vec<String> =  data.into_iter().filter(|item| !results.contains(item)).collect();
+            Ok(uncached_ids)
+        }).await
     }
 
     // Update the availability of a node
-    pub async fn update_node_working_state(&self, node_id : &i8, status :  bool) -> Result<()> {
-        let db_connection = &mut self.connection_pool.get()?; 
-        diesel::update(nodes::table)
-            .filter(crate::schema::nodes::id.eq(node_id))
-            .set(working.eq(status))
-            .execute(db_connection)?;
-        Ok(())
+    pub async fn update_node_working_state(&self, node_id : &i16, status : bool) -> Result<()> {
+        let node_id = *node_id;
+        self.run(move |db_connection| {
+            diesel::update(nodes::table)
+                .filter(crate::schema::nodes::id.eq(node_id))
+                .set(working.eq(status))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Check that a request id and its proof (the access token or, for the requests that were stored before 
+    // the access tokens, the hash) belong to the same request and retrieve the hash of the request
+    pub async fn verify_request_proof(&self, request_id : &i64, proof : &str) -> Result<Option<String>> {
+        let request_id = *request_id;
+        let proof = proof.to_string();
+        self.run(move |db_connection| {
+            let result = requests::table.select(requests::hash_value)
+                                        .filter(requests::id.eq(request_id)
+                                        .and(requests::access_token.eq(&proof)
+                                        .or(requests::access_token.is_null().and(requests::hash_value.eq(&proof)))))
+                                        .first::<String>(db_connection)
+                                        .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve the latest job of a request that was not requeued
+    pub async fn get_latest_job(&self, request_id : &i64) -> Result<Option<Job>> {
+        let request_id = *request_id;
+        self.run(move |db_connection| {
+            let result = jobs::table.filter(jobs::request_id.eq(request_id).and(jobs::status_code.ne(JOB_REQUEUED)))
+                                    .order(jobs::id.desc())
+                                    .first::<Job>(db_connection)
+                                    .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Store the last reported progress of a job
+    pub async fn update_job_progress(&self, job_id : &i64, stage : &str, progress : &i16) -> Result<()> {
+        let job_id = *job_id;
+        let stage = stage.to_string();
+        let progress = *progress;
+        self.run(move |db_connection| {
+            diesel::update(jobs::table)
+                .filter(jobs::id.eq(job_id))
+                .set((jobs::stage.eq(stage), jobs::progress.eq(progress)))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Retrieve a computing node by its id
+    pub async fn get_node(&self, node_id : &i16) -> Result<Option<Node>> {
+        let node_id = *node_id;
+        self.run(move |db_connection| {
+            let result = nodes::table.filter(nodes::id.eq(node_id))
+                                     .first::<Node>(db_connection)
+                                     .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Count the abandoned jobs of a request
+    pub async fn count_requeued_jobs(&self, request_id : &i64) -> Result<i64> {
+        let request_id = *request_id;
+        self.run(move |db_connection| {
+            let result: i64 = jobs::table.select(count(jobs::id))
+                                         .filter(jobs::request_id.eq(request_id).and(jobs::status_code.eq(JOB_REQUEUED)))
+                                         .first(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Update the consecutive failed status queries of a node and return their number
+    pub async fn record_node_check(&self, node_id : &i16, reachable : bool) -> Result<i32> {
+        let node_id = *node_id;
+        self.run(move |db_connection| {
+            let query = diesel::update(nodes::table).filter(nodes::id.eq(node_id));
+            if reachable {
+                query.set(nodes::failed_checks.eq(0)).execute(db_connection)?;
+            }
+            else {
+                query.set(nodes::failed_checks.eq(nodes::failed_checks + 1)).execute(db_connection)?;
+            }
+            let result: i32 = nodes::table.select(nodes::failed_checks)
+                                          .filter(nodes::id.eq(node_id))
+                                          .first(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Update whether a node participates in the network
+    pub async fn update_node_active_state(&self, node_id : &i16, status : bool) -> Result<()> {
+        let node_id = *node_id;
+        self.run(move |db_connection| {
+            diesel::update(nodes::table)
+                .filter(nodes::id.eq(node_id))
+                .set(nodes::active.eq(status))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Mark a node as working as long as it executes at least one job
+    pub async fn refresh_node_working_state(&self, node_id : &i16) -> Result<()> {
+        let running_jobs = self.count_node_running_jobs(node_id).await?;
+        self.update_node_working_state(node_id, running_jobs > 0).await
     }
 
-    // Retrieve the id of a request made recently during the specified timeframe
-    pub async fn check_early_request(&self) -> Result<usize> { 
-        let minute_interval = rand::thread_rng().gen_range(2..5);
-        let db_connection = &mut self.connection_pool.get()?;
-        let result: Vec<i64> = requests::table.select(requests::id)
-                                            .filter(sql::<Bool>("TIMESTAMPDIFF(MINUTE,  creation_date, NOW()) <= ")
-                                            .bind::<Integer,_>(minute_interval))
-                                            .limit(1)
-                                            .load(db_connection)?;
-        Ok(result.len())
-    }
-
-    // Retrieve a recent pending request
-    pub async fn get_early_pending_request(&self) -> Result<Vec<QueriedRequest>> { 
-        let db_connection = &mut self.connection_pool.get()?;
-        let entries = sql_query("SELECT r.*, c.title AS list_name FROM requests as r
-                                            INNER JOIN
-                                            (SELECT MIN(r.id) as id
-                                            FROM requests as r
-                                            LEFT JOIN jobs as j
-                                            ON r.id = j.request_id
-                                            WHERE j.id IS NULL) AS j
-                                            ON r.id = j.id
-                                            LEFT JOIN candidate_lists AS c
-                                            ON r.candidates_list_id = c.id 
-                                            LIMIT 1")
-                                            .get_results(db_connection)?; 
-        Ok(entries)
+    // Retrieve the pending requests, the earliest first
+    pub async fn get_early_pending_requests(&self) -> Result<Vec<QueriedRequest>> {
+        self.run(move |db_connection| {
+            let entries = requests::table.left_join(candidate_lists::table.on(requests::candidates_list_id.eq(candidate_lists::id)))
+                                         .select((requests::id, requests::reference, requests::candidates_list_id, requests::custom_list,
+                                                  requests::uncached, requests::hash_value, requests::creation_date, requests::meta,
+                                                  requests::go_term, requests::comparison_mode, requests::segment_start, requests::segment_end,
+                                                  requests::alignment_level, requests::views, candidate_lists::title.nullable(), requests::base_hash))
+                                         .filter(requests::state.eq(RequestState::Queued.as_str()))
+                                         .order(requests::id)
+                                         .load::<QueriedRequest>(db_connection)?;
+            Ok(entries)
+        }).await
     }
 
     // Get the name of a candidate list by using its ID
     pub async fn get_candidate_list_name(&self, list_id: &i32) -> Result<String> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result: String = candidate_lists::table.select(candidate_lists::title)
-                                            .filter(candidate_lists::id.eq(list_id))
-                                            .first(db_connection)?;
-        Ok(result)
+        let list_id = *list_id;
+        self.run(move |db_connection| {
+            let result: String = candidate_lists::table.select(candidate_lists::title)
+                                                .filter(candidate_lists::id.eq(list_id))
+                                                .first(db_connection)?;
+            Ok(result)
+        }).await
     }
 
     // Verify that the provided candidate list ID exists
     pub async fn verify_candidate_list(&self, list_id: &i32) -> Result<i32> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let result = candidate_lists::table.select(candidate_lists::id)
-                                                          .filter(candidate_lists::id.eq(list_id))
-                                                          .first::<i32>(db_connection)?;
-        Ok(result)
-    }
-
-    // Update the status of a job
-    pub async fn update_job_status(&self, job_id: &i64, status: &i8) -> Result<()> {
-        let db_connection = &mut self.connection_pool.get()?; 
-
-        diesel::update(jobs::table)
-            .filter(crate::schema::jobs::id.eq(job_id))
-            .set(jobs::status_code.eq(status))
-            .execute(db_connection)?;
-        Ok(())
+        let list_id = *list_id;
+        self.run(move |db_connection| {
+            let result = candidate_lists::table.select(candidate_lists::id)
+                                                              .filter(candidate_lists::id.eq(list_id))
+                                                              .first::<i32>(db_connection)?;
+            Ok(result)
+        }).await
     }
 
     // Store the timestamp of the last check on a job
     pub async fn update_job_check(&self, job_id : &i64) -> Result<()> {
-        let db_connection = &mut self.connection_pool.get()?; 
-        let query = diesel::sql_query("UPDATE jobs SET last_checked = NOW() WHERE id = ?")
-        .bind::<BigInt, _>(job_id);
-        query.execute(db_connection)?; 
-        Ok(())
+        let job_id = *job_id;
+        self.run(move |db_connection| {
+            diesel::update(jobs::table)
+                .filter(jobs::id.eq(job_id))
+                .set(jobs::last_checked.eq(now.nullable()))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
     }
 
     // Store the timestamp of the last sync of a node in MachaonWeb network
-    pub async fn update_node_sync_date(&self, node_id : &i8) -> Result<()> {
-        let db_connection = &mut self.connection_pool.get()?; 
-
-        diesel::sql_query("UPDATE nodes SET sync_date = NOW() WHERE id = ?")
-        .bind::<TinyInt, _>(node_id)
-        .execute(db_connection)?; 
-        Ok(())
+    pub async fn update_node_sync_date(&self, node_id : &i16, sync_date: chrono::NaiveDateTime) -> Result<()> {
+        let node_id = *node_id;
+        self.run(move |db_connection| {
+            diesel::update(nodes::table)
+                .filter(nodes::id.eq(node_id))
+                .set(nodes::sync_date.eq(sync_date))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
     }
 
-    // Update certain columns of a job row that mark it as complete.
+    // Update certain columns of a job row that mark it as complete and move its request to the given state.
+    // The job is not finalized when the transition is not legal, e.g. when the request was cancelled in the meantime.
     // secure hash: SHA-256 filehash of the compressed file that contains the results
-    pub async fn finalize_job(&self, job_id: &i64, hash: &str, status: &i8) -> Result<()> {
-        let db_connection = &mut self.connection_pool.get()?; 
-
-        diesel::sql_query("UPDATE jobs SET last_checked = NOW(), completion_date =NOW(),
-                           secure_hash = ?, status_code = ? WHERE id = ?")
-                            .bind::<Char, _>(hash)
-                            .bind::<TinyInt, _>(status)
-                            .bind::<BigInt, _>(job_id) 
-                            .execute(db_connection)?;  
-        Ok(())
+    pub async fn finalize_job(&self, request_id: &i64, job_id: &i64, hash: &str, status: &i16, next: RequestState, reason: &str) -> Result<()> {
+        let (request_id, job_id, status) = (*request_id, *job_id, *status);
+        let hash = hash.to_string();
+        let reason = reason.to_string();
+        self.run(move |db_connection| {
+            db_connection.transaction::<_, anyhow::Error, _>(|connection| {
+                Self::change_state(connection, request_id, Some(job_id), next, &reason)?;
+                diesel::update(jobs::table)
+                    .filter(jobs::id.eq(job_id))
+                    .set((jobs::last_checked.eq(now.nullable()), jobs::completion_date.eq(now.nullable()),
+                          jobs::secure_hash.eq(hash), jobs::status_code.eq(status)))
+                    .execute(connection)?;
+                Ok(())
+            })
+        }).await
+    }
+
+    // Retrieve information of the currently running jobs
+    pub async fn get_running_jobs(&self) -> Result<Vec<QueriedJob>> {
+        self.run(move |db_connection| {
+            let elapsed_minutes = backend::elapsed_time("jobs.assignment_date", 60);
+            let entries = jobs::table.inner_join(requests::table.on(requests::id.eq(jobs::request_id)))
+                                     .inner_join(nodes::table.on(jobs::node_id.eq(nodes::id)))
+                                     .select((jobs::id, requests::hash_value, requests::id, nodes::id, nodes::ip, nodes::domain, nodes::active,
+                                              requests::comparison_mode, sql::<BigInt>(&elapsed_minutes), jobs::retries, requests::base_hash,
+                                              requests::state))
+                                     .filter(jobs::completion_date.is_null().and(jobs::status_code.eq(0)))
+                                     .order(jobs::id)
+                                     .load::<QueriedJob>(db_connection)?;
+            Ok(entries)
+        }).await
     }
 
-    // Retrieve information of a currentl running job by random selection
-    pub async fn get_running_job(&self) -> Result<Option<QueriedJob>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let entry = sql_query("SELECT j.id AS id, 
-                                                r.hash_value AS hash_value,
-                                                r.id AS request_id,
-                                                n.id AS node_id, 
-                                                n.ip AS node_ip,
-                                                n.domain AS node_domain,
-                                                r.comparison_mode AS comparison_mode
-                                                FROM jobs as j
-                                                INNER JOIN
-                                                (SELECT MIN(rr.id) as id
-                                                FROM requests as rr
-                                                INNER JOIN jobs as jj
-                                                ON rr.id = jj.request_id
-                                                WHERE jj.completion_date IS NULL
-                                                AND jj.status_code = 0) AS m
-                                                ON m.id = j.request_id
-                                                INNER JOIN requests AS r
-                                                ON r.id = m.id
-                                                INNER JOIN nodes AS n
-                                                ON j.node_id = n.id
-                                                ORDER BY RAND()
-                                                LIMIT 1;")
-                                        .load::<QueriedJob>(db_connection)?; 
-        Ok(entry.into_iter().next())
-    }
-
-    // Retrieve information of a computing node in the MachaonWeb network that has outdated data
-    pub async fn get_outdated_node(&self) -> Result<Option<Node>> {
-        let db_connection = &mut self.connection_pool.get()?;
-        let query = sql_query("SELECT n.*
-                                    FROM nodes as n
-                                    INNER JOIN
-                                    (SELECT MIN(sync_date) as last_synced
-                                    FROM nodes) AS l
-                                    ON n.sync_date = l.last_synced
-                                    WHERE active = 1
-                                    AND working = 0
-                                    ORDER BY cores
-                                    LIMIT 1;");
-        // println!("{}", debug_query::<diesel::mysql::Mysql, _>(&query));
-        let entry = query.load::<Node>(db_connection)?; 
-        Ok(entry.into_iter().next())
+    // Retrieve information of a computing node in the MachaonWeb network that has outdated data and a free job slot
+    pub async fn get_outdated_node(&self, cores_per_job: i64) -> Result<Option<Node>> {
+        self.run(move |db_connection| {
+            // Only the active nodes that were synchronized the earliest among the active nodes are considered
+            let last_synced = nodes::table.select(diesel::dsl::min(nodes::sync_date))
+                                          .filter(nodes::active.eq(true))
+                                          .first::<Option<chrono::NaiveDateTime>>(db_connection)?;
+            let last_synced = match last_synced {
+                Some(last_synced) => last_synced,
+                None => return Ok(None)
+            };
+            let candidates = nodes::table.filter(nodes::sync_date.eq(last_synced).and(nodes::active.eq(true)))
+                                         .order(nodes::cores)
+                                         .load::<Node>(db_connection)?;
+            // A node is synchronized while it has a free job slot, i.e. it runs fewer jobs than cores / cores_per_job
+            for node in candidates {
+                let running_jobs = jobs::table.select(count(jobs::id))
+                                              .filter(jobs::node_id.eq(node.id)
+                                              .and(jobs::completion_date.is_null())
+                                              .and(jobs::status_code.eq(0)))
+                                              .first::<i64>(db_connection)?;
+                if running_jobs < (i64::from(node.cores) / cores_per_job).max(1) {
+                    return Ok(Some(node));
+                }
+            }
+            Ok(None)
+        }).await
     } 
 
-    // Retrieve the uncached structure IDs for each request that were made in a specified time interval
-    pub async fn get_uncached_by_date(&self, node_id: i8, target_date: chrono::NaiveDateTime) -> Result<Vec<Uncached>> { 
-        let db_connection = &mut self.connection_pool.get()?;
-        let query = sql_query("SELECT rr.uncached 
-                                            FROM requests as rr
-                                            INNER JOIN jobs as jj
-                                            ON rr.id = jj.request_id
-                                            INNER JOIN nodes AS n
-                                            ON n.id = jj.node_id  
-                                            AND n.id <> ?
-                                            WHERE rr.uncached <> ''
-                                            AND jj.assignment_date > ?
-                                            AND status_code = 0;")
-                                            .bind::<TinyInt, _>(node_id) 
-                                            .bind::<diesel::sql_types::Datetime,_>(target_date);
-        // println!("{}", debug_query::<diesel::mysql::Mysql, _>(&query));
-        let entry = query.load::<Uncached>(db_connection)?; 
-        Ok(entry)
-    }
-
-
-
-}
\ No newline at end of file
+    // Retrieve the paths and hashes of the given files that a node has received
+    pub async fn get_synced_files(&self, node_id: &i16, paths: &[String]) -> Result<Vec<(String, String)>> { 
+        let node_id = *node_id;
+        let paths = paths.to_vec();
+        self.run(move |db_connection| {
+            let result = node_files::table.select((node_files::file_path, node_files::file_hash))
+                                          .filter(node_files::node_id.eq(node_id).and(node_files::file_path.eq_any(paths)))
+                                          .load::<(String, String)>(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Store that a node holds a file
+    pub async fn record_synced_file(&self, data : NewNodeFile) -> Result<()> {
+        self.run(move |db_connection| {
+            #[cfg(not(feature = "postgres"))]
+            diesel::replace_into(node_files::table)
+                .values(&data)
+                .execute(db_connection)?;
+            #[cfg(feature = "postgres")]
+            diesel::insert_into(node_files::table)
+                .values(&data)
+                .on_conflict((node_files::node_id, node_files::file_path))
+                .do_update()
+                .set((node_files::file_hash.eq(diesel::upsert::excluded(node_files::file_hash)), node_files::sync_date.eq(now)))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Retrieve the current date of the database server, to compare it with the dates that it records
+    pub async fn get_database_date(&self) -> Result<chrono::NaiveDateTime> {
+        self.run(move |db_connection| {
+            let date = diesel::select(now).get_result::<chrono::NaiveDateTime>(db_connection)?;
+            Ok(date)
+        }).await
+    }
+
+    // Retrieve the uncached structure IDs of the successful jobs of other nodes that completed after a specified date
+    pub async fn get_uncached_by_date(&self, node_id: i16, target_date: chrono::NaiveDateTime) -> Result<Vec<Uncached>> {
+        self.run(move |db_connection| {
+            let entry = requests::table.inner_join(jobs::table.on(requests::id.eq(jobs::request_id)))
+                                       .inner_join(nodes::table.on(nodes::id.eq(jobs::node_id)))
+                                       .select((requests::uncached,))
+                                       .filter(jobs::node_id.ne(node_id)
+                                       .and(requests::uncached.ne(""))
+                                       .and(jobs::completion_date.is_not_null())
+                                       .and(jobs::completion_date.gt(target_date))
+                                       .and(jobs::status_code.eq(0)))
+                                       .load::<Uncached>(db_connection)?;
+            Ok(entry)
+        }).await
+    }
+
+    // Retrieve an administrator by username
+    pub async fn get_admin_by_username(&self, username : &str) -> Result<Option<Admin>> {
+        let username = username.to_string();
+        self.run(move |db_connection| {
+            let result = admins::table.filter(admins::username.eq(username))
+                                      .first::<Admin>(db_connection)
+                                      .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Store a new administrator
+    pub async fn insert_admin(&self, data : NewAdmin) -> Result<()> {
+        self.run(move |db_connection| {
+            diesel::insert_into(admins::table)
+                .values(&data)
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Update the password hash of an administrator
+    pub async fn update_admin_password(&self, admin_id : &i32, password_hash : &str) -> Result<()> {
+        let admin_id = *admin_id;
+        let password_hash = password_hash.to_string();
+        self.run(move |db_connection| {
+            diesel::update(admins::table)
+                .filter(admins::id.eq(admin_id))
+                .set(admins::password_hash.eq(password_hash))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Store a new session of an administrator
+    pub async fn insert_admin_session(&self, data : NewAdminSession) -> Result<()> {
+        self.run(move |db_connection| {
+            diesel::insert_into(admin_sessions::table)
+                .values(&data)
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Retrieve the active administrator of a session that has not expired
+    pub async fn get_session_admin(&self, token_hash : &str, current_date: chrono::NaiveDateTime) -> Result<Option<Admin>> {
+        let token_hash = token_hash.to_string();
+        self.run(move |db_connection| {
+            let result = admin_sessions::table.inner_join(admins::table.on(admins::id.eq(admin_sessions::admin_id)))
+                                              .select(admins::all_columns)
+                                              .filter(admin_sessions::token_hash.eq(token_hash)
+                                              .and(admin_sessions::expiration_date.gt(current_date))
+                                              .and(admins::active.eq(true)))
+                                              .first::<Admin>(db_connection)
+                                              .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Remove a session of an administrator along with the expired sessions
+    pub async fn delete_admin_session(&self, token_hash : &str, current_date: chrono::NaiveDateTime) -> Result<()> {
+        let token_hash = token_hash.to_string();
+        self.run(move |db_connection| {
+            diesel::delete(admin_sessions::table.filter(admin_sessions::token_hash.eq(token_hash)
+                                                .or(admin_sessions::expiration_date.le(current_date))))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Record an action of an administrator
+    pub async fn insert_audit_entry(&self, data : NewAuditEntry) -> Result<()> {
+        self.run(move |db_connection| {
+            diesel::insert_into(admin_audit_log::table)
+                .values(&data)
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Retrieve all the API keys
+    pub async fn get_api_keys(&self) -> Result<Vec<ApiKey>> {
+        self.run(move |db_connection| {
+            let result = api_keys::table.order(api_keys::id)
+                                        .load::<ApiKey>(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve an API key by its id
+    pub async fn get_api_key(&self, key_id : &i32) -> Result<Option<ApiKey>> {
+        let key_id = *key_id;
+        self.run(move |db_connection| {
+            let result = api_keys::table.filter(api_keys::id.eq(key_id))
+                                        .first::<ApiKey>(db_connection)
+                                        .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Retrieve an active API key by the hash of the key
+    pub async fn get_active_api_key(&self, key_hash : &str) -> Result<Option<ApiKey>> {
+        let key_hash = key_hash.to_string();
+        self.run(move |db_connection| {
+            let result = api_keys::table.filter(api_keys::key_hash.eq(key_hash)
+                                        .and(api_keys::active.eq(true)))
+                                        .first::<ApiKey>(db_connection)
+                                        .optional()?;
+            Ok(result)
+        }).await
+    }
+
+    // Store a new API key and return its id
+    pub async fn insert_api_key(&self, data : NewApiKey) -> Result<i32> {
+        self.run(move |db_connection| {
+            let key_id = db_connection.transaction::<_, diesel::result::Error, _>(|connection| {
+                diesel::insert_into(api_keys::table)
+                    .values(&data)
+                    .execute(connection)?;
+                api_keys::table.select(api_keys::id)
+                               .filter(api_keys::key_hash.eq(data.key_hash))
+                               .first::<i32>(connection)
+            })?;
+            Ok(key_id)
+        }).await
+    }
+
+    // Update the owner, the quota or the state of an API key
+    pub async fn update_api_key(&self, key_id : &i32, data : ApiKeyChanges) -> Result<()> {
+        let key_id = *key_id;
+        self.run(move |db_connection| {
+            diesel::update(api_keys::table)
+                .filter(api_keys::id.eq(key_id))
+                .set(&data)
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Count the requests of an API key during the specified number of last hours
+    pub async fn count_api_key_requests(&self, key_id : &i32, hour_interval: i32) -> Result<i64> {
+        let key_id = *key_id;
+        self.run(move |db_connection| {
+            let result = api_key_requests::table.select(count(api_key_requests::id))
+                                                .filter(api_key_requests::api_key_id.eq(key_id)
+                                                .and(sql::<BigInt>(&backend::elapsed_time("api_key_requests.submission_date", 1))
+                                                     .lt(i64::from(hour_interval) * 3600)))
+                                                .first::<i64>(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Store new requests that are submitted with an API key and record them in the history of the key, as long as they
+    // do not exceed its daily quota (0 for no quota). The row of the key is updated first, so that the concurrent submissions
+    // of the same key wait for each other before they count its requests. The ids of the requests are returned.
+    pub async fn insert_keyed_requests(&self, key_id : &i32, daily_quota : &i32, data : Vec<NewRequest>) -> Result<Vec<i64>> {
+        let key_id = *key_id;
+        let daily_quota = *daily_quota;
+        self.run(move |db_connection| {
+            db_connection.transaction::<_, anyhow::Error, _>(|connection| {
+                diesel::update(api_keys::table)
+                    .filter(api_keys::id.eq(key_id))
+                    .set(api_keys::last_used_date.eq(now.nullable()))
+                    .execute(connection)?;
+                let used_quota = api_key_requests::table.select(count(api_key_requests::id))
+                                                        .filter(api_key_requests::api_key_id.eq(key_id)
+                                                        .and(sql::<BigInt>(&backend::elapsed_time("api_key_requests.submission_date", 1))
+                                                             .lt(24 * 3600)))
+                                                        .first::<i64>(connection)?;
+                if daily_quota > 0 && used_quota + data.len() as i64 > i64::from(daily_quota) {
+                    return Err(QuotaExceeded { api_key_id: key_id, daily_quota }.into());
+                }
+                let mut request_ids = Vec::new();
+                for request in &data {
+                    diesel::insert_into(requests::table)
+                        .values(request)
+                        .execute(connection)?;
+                    let request_id = requests::table.select(requests::id)
+                                                    .filter(requests::access_token.eq(&request.access_token))
+                                                    .first::<i64>(connection)?;
+                    diesel::insert_into(api_key_requests::table)
+                        .values(&NewApiKeyRequest{ api_key_id: key_id, request_id })
+                        .execute(connection)?;
+                    request_ids.push(request_id);
+                }
+                Ok(request_ids)
+            })
+        }).await
+    }
+
+    // Retrieve the latest requests of an API key, the most recent first
+    pub async fn get_api_key_requests(&self, key_id : &i32, limit : i64) -> Result<Vec<KeyedRequest>> {
+        let key_id = *key_id;
+        self.run(move |db_connection| {
+            let result = api_key_requests::table.inner_join(requests::table.on(requests::id.eq(api_key_requests::request_id)))
+                                                .select((requests::id, requests::hash_value, requests::access_token, requests::reference,
+                                                         requests::comparison_mode, api_key_requests::submission_date))
+                                                .filter(api_key_requests::api_key_id.eq(key_id))
+                                                .order(api_key_requests::id.desc())
+                                                .limit(limit)
+                                                .load::<KeyedRequest>(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+    // Update a bucket of the rate limiter atomically, given the function that computes its new state.
+    // SQLite has no row locks, so the whole database is locked for writing from the start of the transaction instead.
+    pub async fn update_rate_limit_bucket<T: Send + 'static>(&self, bucket_key : &str, 
+                                             update: impl FnOnce(Option<RateLimitBucket>) -> (RateLimitBucket, T) + Send + 'static) -> Result<T> {
+        let bucket_key = bucket_key.to_string();
+        self.run(move |db_connection| {
+            let transaction = |connection: &mut DbConnection| -> diesel::QueryResult<T> {
+                let query = rate_limit_buckets::table.filter(rate_limit_buckets::bucket_key.eq(&bucket_key));
+                #[cfg(not(feature = "sqlite"))]
+                let query = query.for_update();
+                let bucket = query.first::<RateLimitBucket>(connection).optional()?;
+                let (bucket, outcome) = update(bucket);
+                #[cfg(not(feature = "postgres"))]
+                diesel::replace_into(rate_limit_buckets::table)
+                    .values(&bucket)
+                    .execute(connection)?;
+                #[cfg(feature = "postgres")]
+                diesel::insert_into(rate_limit_buckets::table)
+                    .values(&bucket)
+                    .on_conflict(rate_limit_buckets::bucket_key)
+                    .do_update()
+                    .set((rate_limit_buckets::tokens.eq(bucket.tokens), rate_limit_buckets::refill_time.eq(bucket.refill_time)))
+                    .execute(connection)?;
+                Ok(outcome)
+            };
+            #[cfg(feature = "sqlite")]
+            let outcome = db_connection.immediate_transaction(transaction)?;
+            #[cfg(not(feature = "sqlite"))]
+            let outcome = db_connection.transaction(transaction)?;
+            Ok(outcome)
+        }).await
+    }
+
+    // Remove the buckets of the rate limiter that were not used since the specified time
+    pub async fn delete_idle_rate_limit_buckets(&self, refill_time : i64) -> Result<()> {
+        self.run(move |db_connection| {
+            diesel::delete(rate_limit_buckets::table.filter(rate_limit_buckets::refill_time.lt(refill_time)))
+                .execute(db_connection)?;
+            Ok(())
+        }).await
+    }
+
+    // Store a batch along with its requests
+    pub async fn insert_batch(&self, data : NewBatch, batched_requests: Vec<NewBatchRequest>) -> Result<()> {
+        self.run(move |db_connection| {
+            db_connection.transaction::<_, diesel::result::Error, _>(|connection| {
+                diesel::insert_into(batches::table)
+                    .values(&data)
+                    .execute(connection)?;
+                diesel::insert_into(batch_requests::table)
+                    .values(&batched_requests)
+                    .execute(connection)?;
+                Ok(())
+            })?;
+            Ok(())
+        }).await
+    }
+
+    // Check if a batch exists
+    pub async fn verify_batch(&self, batch_id : &str) -> Result<bool> {
+        let batch_id = batch_id.to_string();
+        self.run(move |db_connection| {
+            let result = batches::table.select(batches::id)
+                                       .filter(batches::id.eq(batch_id))
+                                       .first::<String>(db_connection)
+                                       .optional()?;
+            Ok(result.is_some())
+        }).await
+    }
+
+    // Retrieve the requests of a batch in the order of submission
+    pub async fn get_batched_requests(&self, batch_id : &str) -> Result<Vec<BatchedRequest>> {
+        let batch_id = batch_id.to_string();
+        self.run(move |db_connection| {
+            let result = batch_requests::table.inner_join(requests::table.on(requests::id.eq(batch_requests::request_id)))
+                                              .select((requests::id, requests::hash_value, requests::access_token, requests::reference,
+                                                       requests::state))
+                                              .filter(batch_requests::batch_id.eq(batch_id))
+                                              .order(batch_requests::position)
+                                              .load::<BatchedRequest>(db_connection)?;
+            Ok(result)
+        }).await
+    }
+
+}
@@ -0,0 +1,162 @@
+use std::env;
+use diesel::{prelude::*, sql_types::BigInt, dsl::sql};
+use diesel::migration::MigrationSource;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use anyhow::{anyhow, Result};
+use super::backend::DbConnection;
+
+/*
+
+The schema migrations of the selected backend are embedded into the binary at build time. The root node applies the
+pending ones at startup when it is started with --migrate, otherwise it refuses to start with an outdated schema.
+The applied versions are recorded by diesel in the table __diesel_schema_migrations.
+
+A database whose schema was created by running up.sql files by hand has the table requests but not the table
+__diesel_schema_migrations. Before the pending migrations are looked up, each migration whose characteristic object
+(a table, a column or the type of a column, see MIGRATION_OBJECTS) exists in it is recorded as applied, so that only
+the remaining migrations are applied to it. Every new migration needs its entry in MIGRATION_OBJECTS, and schema
+changes that were applied by hand otherwise have to be recorded in __diesel_schema_migrations before --migrate.
+
+The migrations run on a dedicated connection without the statement timeout of the pool, since they may rebuild large tables.
+
+*/
+
+#[cfg(feature = "mysql")]
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+#[cfg(feature = "postgres")]
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_postgres");
+#[cfg(feature = "sqlite")]
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_sqlite");
+
+// Object of the schema whose existence shows that a migration was applied
+enum SchemaObject {
+    Table(&'static str),
+    Column(&'static str, &'static str),
+    // Column of the given type, in lowercase
+    ColumnType(&'static str, &'static str, &'static str),
+}
+
+// Object that each migration creates, by the version of the migration. The migrations of PostgreSQL and SQLite start
+// from the complete schema of 2026-10-18-200000, so they only have the first and the later versions.
+const MIGRATION_OBJECTS: [(&str, SchemaObject); 14] = [
+    ("20230218163221", SchemaObject::Table("requests")),
+    ("20261018100000", SchemaObject::Table("scheduling_decisions")),
+    ("20261018110000", SchemaObject::Column("jobs", "retries")),
+    ("20261018120000", SchemaObject::Column("jobs", "progress")),
+    ("20261018130000", SchemaObject::Table("node_files")),
+    ("20261018140000", SchemaObject::Column("nodes", "drained")),
+    ("20261018150000", SchemaObject::Table("admins")),
+    ("20261018160000", SchemaObject::Table("api_keys")),
+    ("20261018170000", SchemaObject::Table("rate_limit_buckets")),
+    ("20261018180000", SchemaObject::Table("batches")),
+    ("20261018190000", SchemaObject::Column("requests", "access_token")),
+    ("20261018200000", SchemaObject::Column("requests", "base_hash")),
+    ("20261018210000", SchemaObject::Table("job_events")),
+    ("20261018220000", SchemaObject::ColumnType("nodes", "id", "smallint")),
+];
+
+// Check if an object exists in the schema of the database
+fn exists(connection: &mut DbConnection, object: &SchemaObject) -> Result<bool> {
+    #[cfg(not(feature = "sqlite"))]
+    let query = match object {
+        SchemaObject::Table(table) => format!("(SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = {} AND table_name = '{}')",
+                                              CURRENT_SCHEMA, table),
+        SchemaObject::Column(table, column) => format!("(SELECT COUNT(*) FROM information_schema.columns WHERE table_schema = {} \
+                                                        AND table_name = '{}' AND column_name = '{}')", CURRENT_SCHEMA, table, column),
+        SchemaObject::ColumnType(table, column, data_type) => format!("(SELECT COUNT(*) FROM information_schema.columns WHERE table_schema = {} \
+                                                                       AND table_name = '{}' AND column_name = '{}' AND LOWER(data_type) = '{}')",
+                                                                      CURRENT_SCHEMA, table, column, data_type),
+    };
+    #[cfg(feature = "sqlite")]
+    let query = match object {
+        SchemaObject::Table(table) => format!("(SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '{}')", table),
+        SchemaObject::Column(table, column) => format!("(SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = '{}')", table, column),
+        SchemaObject::ColumnType(table, column, data_type) => format!("(SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = '{}' \
+                                                                       AND LOWER(type) = '{}')", table, column, data_type),
+    };
+    Ok(diesel::select(sql::<BigInt>(&query)).get_result::<i64>(connection)? > 0)
+}
+
+#[cfg(feature = "mysql")]
+const CURRENT_SCHEMA: &str = "DATABASE()";
+#[cfg(feature = "postgres")]
+const CURRENT_SCHEMA: &str = "current_schema()";
+
+// Versions of the embedded migrations
+fn embedded_versions() -> Result<Vec<String>> {
+    let migrations = MigrationSource::<<DbConnection as Connection>::Backend>::migrations(&MIGRATIONS).map_err(|e| anyhow!(e))?;
+    Ok(migrations.iter().map(|migration| migration.name().version().to_string()).collect())
+}
+
+// Record the migrations whose objects exist as applied, in a database whose schema was created by hand
+fn adopt_unversioned_schema(connection: &mut DbConnection) -> Result<()> {
+    if !exists(connection, &SchemaObject::Table("requests"))? || exists(connection, &SchemaObject::Table("__diesel_schema_migrations"))? {
+        return Ok(());
+    }
+    // The table of the applied versions is created by diesel when they are first retrieved
+    connection.applied_migrations().map_err(|e| anyhow!(e))?;
+    let versions = embedded_versions()?;
+    for (version, object) in MIGRATION_OBJECTS.iter().filter(|(version, _)| versions.iter().any(|embedded| embedded == version)) {
+        if exists(connection, object)? {
+            diesel::sql_query(format!("INSERT INTO __diesel_schema_migrations (version) VALUES ('{}')", version))
+                .execute(connection)?;
+        }
+    }
+    Ok(())
+}
+
+// Apply the pending migrations in the order of their versions and return the applied versions
+pub async fn apply_pending_migrations() -> Result<Vec<String>> {
+    run(|connection| {
+        adopt_unversioned_schema(connection)?;
+        let versions = connection.run_pending_migrations(MIGRATIONS).map_err(|e| anyhow!(e))?;
+        Ok(versions.iter().map(|version| version.to_string()).collect())
+    }).await
+}
+
+// Retrieve the names of the migrations that have not been applied yet
+pub async fn get_pending_migrations() -> Result<Vec<String>> {
+    run(|connection| {
+        adopt_unversioned_schema(connection)?;
+        let migrations = connection.pending_migrations(MIGRATIONS).map_err(|e| anyhow!(e))?;
+        Ok(migrations.iter().map(|migration| migration.name().to_string()).collect())
+    }).await
+}
+
+// Run a task on a new connection to the database, in the blocking threads of tokio
+async fn run<T, F>(task: F) -> Result<T>
+where T: Send + 'static, F: FnOnce(&mut DbConnection) -> Result<T> + Send + 'static {
+    let database_url = env::var("DATABASE_URL")?;
+    tokio::task::spawn_blocking(move || {
+        let mut connection = DbConnection::establish(&database_url)?;
+        task(&mut connection)
+    }).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "sqlite")]
+    use diesel::connection::SimpleConnection;
+
+    #[test]
+    fn every_migration_has_an_object() {
+        for version in embedded_versions().unwrap() {
+            assert!(MIGRATION_OBJECTS.iter().any(|(object_version, _)| *object_version == version), "{}", version);
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn adopt_hand_applied_schema() {
+        let mut connection = DbConnection::establish(":memory:").unwrap();
+        connection.batch_execute(include_str!("../../migrations_sqlite/2023-02-18-163221_create_db/up.sql")).unwrap();
+        adopt_unversioned_schema(&mut connection).unwrap();
+        let pending = connection.pending_migrations(MIGRATIONS).unwrap();
+        assert_eq!(pending.iter().map(|migration| migration.name().version().to_string()).collect::<Vec<_>>(), vec!["20261018210000"]);
+        connection.batch_execute(include_str!("../../migrations_sqlite/2026-10-18-210000_request_states/up.sql")).unwrap();
+        connection.batch_execute("DROP TABLE __diesel_schema_migrations").unwrap();
+        adopt_unversioned_schema(&mut connection).unwrap();
+        assert!(connection.pending_migrations(MIGRATIONS).unwrap().is_empty());
+    }
+}
@@ -1,2 +1,4 @@
+pub mod backend;
 pub mod dbhandler;
+pub mod migrations;
 pub mod models;
\ No newline at end of file
@@ -1,12 +1,16 @@
-use diesel::{prelude::*, sql_types::Char};
+use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
-use crate::schema::{cached_features, requests, jobs, joined_requests, joined_jobs, nodes, finalized_requests};
+use crate::schema::{cached_features, requests, jobs, nodes, scheduling_decisions, node_files,
+                    admins, admin_sessions, admin_audit_log, api_keys, api_key_requests, rate_limit_buckets,
+                    batches, batch_requests, job_events};
 use anyhow::Result;
 
 /* 
 
 Here are the models for each table in the database. There are also structs that model the results of specific 
-queries (e.g. QueriedJob) and were contructed manually, consisting of multiple joins.
+queries (e.g. QueriedJob) and were contructed manually, consisting of multiple joins. Their fields follow the order 
+of the columns that the queries select. The structs of the new rows own their values, so that they can be moved to 
+the threads that run the queries.
 
 */
 
@@ -21,11 +25,14 @@ pub struct Request {
     pub creation_date: chrono::NaiveDateTime,
     pub meta: bool,
     pub go_term: String,
-    pub comparison_mode : i8,
+    pub comparison_mode : i16,
     pub segment_start: i32,
     pub segment_end: i32,
-    pub alignment_level : i8,
+    pub alignment_level : i16,
     pub views : i64,
+    pub access_token : Option<String>,
+    pub base_hash : Option<String>,
+    pub state : String,
 }
 
 impl Request {
@@ -34,13 +41,13 @@ impl Request {
             custom_list: String::from(""), uncached: String::from(""),   
             hash_value: String::from(""), creation_date: chrono::NaiveDateTime::MIN,
             meta: false, go_term: String::from(""), comparison_mode: -1, segment_start: -1,
-            segment_end: -1, alignment_level: -1, views: -1}
+            segment_end: -1, alignment_level: -1, views: -1, access_token: None, base_hash: None,
+            state: String::from("queued")}
     }
 }
 
 
-#[derive(QueryableByName, Debug, Serialize, Deserialize)]
-#[diesel(table_name = finalized_requests)]
+#[derive(Queryable, Debug, Serialize, Deserialize)]
 pub struct FinalizedRequest {
     pub id: i64, 
     pub reference: String,
@@ -51,14 +58,14 @@ pub struct FinalizedRequest {
     pub creation_date: chrono::NaiveDateTime,
     pub meta: bool,
     pub go_term: String,
-    pub comparison_mode : i8,
+    pub comparison_mode : i16,
     pub segment_start: i32,
     pub segment_end: i32,
-    pub alignment_level : i8,
+    pub alignment_level : i16,
     pub views : i64,
     pub secure_hash: String,
     pub list_name: Option<String>,
-    pub status_code: i8,
+    pub status_code: i16,
 }
 
 impl FinalizedRequest {
@@ -74,8 +81,7 @@ impl FinalizedRequest {
 }
 
 
-#[derive(QueryableByName,  Clone)]
-#[diesel(table_name = joined_requests)]
+#[derive(Queryable,  Clone)]
 pub struct QueriedRequest {
     pub id: i64, 
     pub reference: String,
@@ -86,12 +92,14 @@ pub struct QueriedRequest {
     pub creation_date: chrono::NaiveDateTime,
     pub meta: bool,
     pub go_term: String,
-    pub comparison_mode : i8, 
+    pub comparison_mode : i16, 
     pub segment_start: i32,
     pub segment_end: i32,
-    pub alignment_level: i8,
+    pub alignment_level: i16,
     pub views : i64,
     pub list_name: Option<String>,
+    // Hash of the comparison without the meta-analysis settings
+    pub base_hash: Option<String>,
 }
 
 impl QueriedRequest{
@@ -112,7 +120,8 @@ impl QueriedRequest{
             segment_start: -1,
             segment_end: -1,
             alignment_level: -1,
-            views: -1
+            views: -1,
+            base_hash: None
           })
     }
 }
@@ -121,33 +130,174 @@ impl QueriedRequest{
 pub struct Job {
     pub id: i64, 
     pub request_id: i64,
-    pub node_id: i64,
+    pub node_id: i16,
     pub assignment_date: chrono::NaiveDateTime,
     pub completion_date: Option<chrono::NaiveDateTime>,
     pub last_checked_date: Option<chrono::NaiveDateTime>,
-    pub status_code: i8,
+    pub status_code: i16,
     pub secure_hash: String,
+    pub retries: i32,
+    pub stage: String,
+    pub progress: i16,
 }
 
-#[derive(QueryableByName, Queryable)]
-#[diesel(table_name = nodes)]
+// Status codes of the jobs that the root node finalizes on its own
+// The job exceeded the deadline of its comparison mode
+pub const JOB_TIMED_OUT: i16 = -4;
+// The node of the job became unreachable or lost track of the job
+pub const JOB_NODE_LOST: i16 = -5;
+// The job was abandoned and its request was queued again
+pub const JOB_REQUEUED: i16 = -6;
+// The request was cancelled by its owner or its node
+pub const JOB_CANCELLED: i16 = -7;
+
+// State of a request, stored in the state column of the requests table. A request is queued until a node accepts
+// its job, then it is assigned until the node confirms that the job runs, and its result is downloaded and extracted
+// before it is completed. An abandoned job returns its request to the queue, as long as retries remain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestState {
+    Queued,
+    Assigned,
+    Running,
+    Downloading,
+    Extracting,
+    Completed,
+    Failed,
+    Cancelled,
+    // The job exceeded the deadline of its comparison mode and no retries remain
+    Expired,
+}
+
+impl RequestState {
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestState::Queued => "queued",
+            RequestState::Assigned => "assigned",
+            RequestState::Running => "running",
+            RequestState::Downloading => "downloading",
+            RequestState::Extracting => "extracting",
+            RequestState::Completed => "completed",
+            RequestState::Failed => "failed",
+            RequestState::Cancelled => "cancelled",
+            RequestState::Expired => "expired",
+        }
+    }
+
+    // The request has a job on a node
+    pub fn is_active(&self) -> bool {
+        matches!(self, RequestState::Assigned | RequestState::Running | RequestState::Downloading | RequestState::Extracting)
+    }
+
+    // The request will not change anymore
+    pub fn is_final(&self) -> bool {
+        matches!(self, RequestState::Completed | RequestState::Failed | RequestState::Cancelled | RequestState::Expired)
+    }
+
+    // Check if a request can move from this state to another one
+    pub fn can_transition_to(&self, next: RequestState) -> bool {
+        use RequestState::*;
+        match (self, next) {
+            // A request is served by an identical finished one, accepted by a node or rejected by all of them
+            (Queued, Assigned | Completed | Failed | Cancelled) => true,
+            (Assigned, Running) => true,
+            (Running, Downloading) => true,
+            // The download is retried when the extraction of the archive failed
+            (Downloading, Extracting) | (Extracting, Downloading) => true,
+            (Extracting, Completed) => true,
+            // An active job is abandoned, fails on its node or is cancelled
+            (Assigned | Running | Downloading | Extracting, Queued | Failed | Cancelled | Expired) => true,
+            _ => false
+        }
+    }
+}
+
+impl std::fmt::Display for RequestState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for RequestState {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        use RequestState::*;
+        [Queued, Assigned, Running, Downloading, Extracting, Completed, Failed, Cancelled, Expired]
+            .into_iter()
+            .find(|state| state.as_str() == value)
+            .ok_or_else(|| anyhow::anyhow!("Unknown request state: {}", value))
+    }
+}
+
+// Error of a transition that is not legal from the current state of a request, e.g. after a concurrent cancellation
+#[derive(Debug)]
+pub struct IllegalTransition {
+    pub request_id: i64,
+    pub from: String,
+    pub to: RequestState,
+}
+
+impl std::fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request {} cannot move from {} to {}", self.request_id, self.from, self.to)
+    }
+}
+
+impl std::error::Error for IllegalTransition {}
+
+// Error of a submission with an API key that would exceed the daily quota of the key
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub api_key_id: i32,
+    pub daily_quota: i32,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The requests exceed the quota of API key {} ({} per 24 hours)", self.api_key_id, self.daily_quota)
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+#[derive(Queryable, Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
-    pub id: i8, 
+    pub id: i16, 
     pub ip: String,
     pub domain: String,
     pub active: bool,
     pub working: bool,
     pub sync_date: chrono::NaiveDateTime,
-    pub cores: i8, 
+    pub cores: i16, 
+    pub failed_checks: i32,
+    pub drained: bool,
 }
 
 impl Node {
     pub fn construct() -> Self {
         Self{ id: -1, ip: String::from(""), domain: String::from(""),active: false, 
-              working: false, sync_date: chrono::NaiveDateTime::MIN, cores: -1}
+              working: false, sync_date: chrono::NaiveDateTime::MIN, cores: -1, failed_checks: 0, drained: false}
     }
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = nodes)]
+pub struct NewNode { 
+    pub ip: String,
+    pub domain: String,
+    pub active: bool,
+    pub cores: i16, 
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = nodes)]
+pub struct NodeChanges { 
+    pub ip: Option<String>,
+    pub domain: Option<String>,
+    pub cores: Option<i16>, 
+}
+
 #[derive(Queryable)]
 pub struct CachedFeatureId {
     pub id: i64, 
@@ -162,46 +312,204 @@ pub struct CandidateList{
 
 #[derive(Insertable)]
 #[diesel(table_name = cached_features)]
-pub struct NewCachedFeatureId<'a> { 
-    pub structure_id: &'a str,
+pub struct NewCachedFeatureId { 
+    pub structure_id: String,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = jobs)]
-pub struct NewJob<'a> { 
-    pub request_id:&'a i64,
-    pub node_id:&'a i8, 
+pub struct NewJob { 
+    pub request_id: i64,
+    pub node_id: i16, 
     pub completion_date: Option<chrono::NaiveDateTime>,
-    pub status_code:&'a i8,
-    pub secure_hash: &'a str,
+    pub status_code: i16,
+    pub secure_hash: String,
+    pub retries: i32,
 }
  
 #[derive(Insertable)]
 #[diesel(table_name = requests)]
-pub struct NewRequest<'a> { 
-    pub reference: &'a str,
-    pub candidates_list_id:&'a i32,
-    pub custom_list: &'a str,
-    pub uncached: &'a str,
-    pub hash_value: &'a str,
+pub struct NewRequest { 
+    pub reference: String,
+    pub candidates_list_id: i32,
+    pub custom_list: String,
+    pub uncached: String,
+    pub hash_value: String,
     pub meta: bool,
-    pub go_term: &'a str,
-    pub segment_start:&'a i32,
-    pub segment_end:&'a i32,
-    pub comparison_mode:&'a i8,
-    pub alignment_level:&'a i8,
+    pub go_term: String,
+    pub segment_start: i32,
+    pub segment_end: i32,
+    pub comparison_mode: i16,
+    pub alignment_level: i16,
+    pub access_token: String,
+    pub base_hash: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = scheduling_decisions)]
+pub struct NewSchedulingDecision { 
+    pub request_id: i64,
+    pub node_id: i16, 
+    pub strategy: String,
+    pub ranking: String,
+    pub candidate_count: i32,
+    pub large_job: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = node_files)]
+pub struct NewNodeFile { 
+    pub node_id: i16, 
+    pub file_path: String,
+    pub file_hash: String,
+}
+
+#[derive(Queryable, Debug, Clone)]
+pub struct Admin {
+    pub id: i32,
+    pub username: String,
+    pub password_hash: String,
+    pub active: bool,
+    pub creation_date: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = admins)]
+pub struct NewAdmin { 
+    pub username: String,
+    pub password_hash: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = admin_sessions)]
+pub struct NewAdminSession { 
+    pub token_hash: String,
+    pub admin_id: i32,
+    pub expiration_date: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = admin_audit_log)]
+pub struct NewAuditEntry { 
+    pub admin_id: i32,
+    pub actor: String,
+    pub action: String,
+    pub payload: String,
+    pub status_code: i32,
+}
+
+// The hash of the key is never sent to the clients
+#[derive(Queryable, Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: i32,
+    pub owner: String,
+    pub key_prefix: String,
+    #[serde(skip_serializing, default)]
+    pub key_hash: String,
+    pub daily_quota: i32,
+    pub active: bool,
+    pub admin_id: i32,
+    pub creation_date: chrono::NaiveDateTime,
+    pub last_used_date: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = api_keys)]
+pub struct NewApiKey { 
+    pub owner: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub daily_quota: i32,
+    pub admin_id: i32,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = api_keys)]
+pub struct ApiKeyChanges { 
+    pub owner: Option<String>,
+    pub daily_quota: Option<i32>,
+    pub active: Option<bool>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = api_key_requests)]
+pub struct NewApiKeyRequest { 
+    pub api_key_id: i32,
+    pub request_id: i64,
+}
+
+// Request that was submitted with an API key
+#[derive(Queryable, Debug, Serialize, Deserialize)]
+pub struct KeyedRequest {
+    pub request_id: i64,
+    pub hash: String,
+    pub access_token: Option<String>,
+    pub reference: String,
+    pub comparison_mode: i16,
+    pub submission_date: chrono::NaiveDateTime,
+}
+
+// Token bucket of a client for a route
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = rate_limit_buckets)]
+pub struct RateLimitBucket {
+    pub bucket_key: String,
+    pub tokens: f64,
+    // Milliseconds since the Unix epoch
+    pub refill_time: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = batches)]
+pub struct NewBatch { 
+    pub id: String,
+    pub api_key_id: Option<i32>,
 }
 
-#[derive(QueryableByName,  Clone)]
-#[diesel(table_name = joined_jobs)]
+#[derive(Insertable)]
+#[diesel(table_name = batch_requests)]
+pub struct NewBatchRequest { 
+    pub batch_id: String,
+    pub request_id: i64,
+    pub position: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = job_events)]
+pub struct NewJobEvent { 
+    pub request_id: i64,
+    pub job_id: Option<i64>,
+    pub from_state: String,
+    pub to_state: String,
+    pub reason: String,
+}
+
+// Request of a batch, in the order of submission
+#[derive(Queryable, Debug, Serialize, Deserialize)]
+pub struct BatchedRequest {
+    pub request_id: i64,
+    pub hash: String,
+    pub access_token: Option<String>,
+    pub reference: String,
+    pub state: String,
+}
+
+#[derive(Queryable,  Clone, Debug)]
 pub struct QueriedJob {
     pub id: i64,  
     pub hash_value: String,
     pub request_id: i64,  
-    pub node_id: i8,
+    pub node_id: i16,
     pub node_ip: String,
     pub node_domain: String,
-    pub comparison_mode: i8
+    pub node_active: bool,
+    pub comparison_mode: i16,
+    // Minutes since the assignment of the job
+    pub elapsed_minutes: i64,
+    pub retries: i32,
+    pub base_hash: Option<String>,
+    // State of the request of the job
+    pub state: String,
 }
 
 impl QueriedJob{
@@ -212,14 +520,74 @@ impl QueriedJob{
             node_id: -1,
             node_ip: String::from(""), 
             node_domain: String::from(""),
-            comparison_mode: -1
+            node_active: false,
+            comparison_mode: -1,
+            elapsed_minutes: 0,
+            retries: 0,
+            base_hash: None,
+            state: String::from("")
           })
     }
 }
 
 
-#[derive(QueryableByName)]
+#[derive(Queryable)]
 pub struct Uncached {
-    #[diesel(sql_type = Char)]
     pub uncached: String,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::RequestState::{self, *};
+
+    const STATES: [RequestState; 9] = [Queued, Assigned, Running, Downloading, Extracting, Completed, Failed, Cancelled, Expired];
+
+    #[test]
+    fn legal_transitions() {
+        let legal = [(Queued, Assigned), (Queued, Completed), (Queued, Failed), (Queued, Cancelled),
+                     (Assigned, Running), (Running, Downloading), (Downloading, Extracting), (Extracting, Downloading),
+                     (Extracting, Completed)];
+        for (from, to) in legal {
+            assert!(from.can_transition_to(to), "{} -> {}", from, to);
+        }
+        for from in [Assigned, Running, Downloading, Extracting] {
+            for to in [Queued, Failed, Cancelled, Expired] {
+                assert!(from.can_transition_to(to), "{} -> {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn illegal_transitions() {
+        let illegal = [(Queued, Running), (Queued, Downloading), (Queued, Extracting), (Queued, Expired), (Queued, Queued),
+                       (Assigned, Downloading), (Assigned, Completed), (Running, Extracting), (Running, Completed),
+                       (Downloading, Completed)];
+        for (from, to) in illegal {
+            assert!(!from.can_transition_to(to), "{} -> {}", from, to);
+        }
+        // The final states do not change anymore
+        for from in [Completed, Failed, Cancelled, Expired] {
+            for to in STATES {
+                assert!(!from.can_transition_to(to), "{} -> {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn active_and_final_states() {
+        for state in STATES {
+            assert!(!(state.is_active() && state.is_final()), "{}", state);
+        }
+        assert!(!Queued.is_active() && !Queued.is_final());
+    }
+
+    #[test]
+    fn string_round_trip() {
+        for state in STATES {
+            assert_eq!(state.as_str().parse::<RequestState>().unwrap(), state);
+            assert_eq!(state.to_string(), state.as_str());
+        }
+        assert!("".parse::<RequestState>().is_err());
+        assert!("Queued".parse::<RequestState>().is_err());
+        assert!("finished".parse::<RequestState>().is_err());
+    }
+}
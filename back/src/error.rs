@@ -0,0 +1,199 @@
+use std::fmt;
+use axum::{extract::rejection::{JsonRejection, PathRejection, QueryRejection}, http::{header, StatusCode}, response::{IntoResponse, Response}};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use crate::db::{backend::QueryTimeout, models::{IllegalTransition, QuotaExceeded}};
+
+/*
+
+This module defines the errors of MachaonWeb's REST API. Each error is sent with its HTTP status code and a JSON body
+with a stable code, a message and the input field that caused it, if any.
+
+*/
+
+pub type ApiResult<T> = std::result::Result<T, ApiError>;
+
+#[derive(Debug)]
+pub enum ApiError {
+    // 400: the input of the request is not valid
+    InvalidInput { field: String, message: String },
+    // 400: several fields of the request are not valid
+    Validation(Vec<FieldError>),
+    // 401: the request has no valid credentials
+    Unauthorized(String),
+    // 403: the request was not verified as human
+    CaptchaFailed(String),
+    // 404: the requested entity does not exist or its proof is wrong
+    NotFound(String),
+    // 409: the entity is in a state that does not allow the operation
+    Conflict(String),
+    // 429: the request was throttled
+    TooManyRequests(String),
+    // 429: the client exceeded the rate limit of the route and may retry after the given seconds
+    RateLimited { retry_after: u64 },
+    // 416: the requested byte range is outside of a file of the given length
+    RangeNotSatisfiable { length: u64 },
+    // 503: the database or the computing nodes cannot be reached
+    Unavailable(String),
+    // 500: any other failure, which is logged but not exposed
+    Internal(anyhow::Error),
+}
+
+// Invalid field of a request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldError {
+    field: String,
+    message: String,
+}
+
+impl FieldError {
+
+    pub fn new(field: &str, message: &str) -> Self {
+        Self { field: field.to_string(), message: message.to_string() }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+// JSON body of an error response, where the field is the first invalid field and the errors list all of them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorBody {
+    code: String,
+    message: String,
+    field: Option<String>,
+    errors: Vec<FieldError>,
+}
+
+impl ApiError {
+
+    pub fn invalid_input(field: &str, message: &str) -> Self {
+        ApiError::InvalidInput { field: field.to_string(), message: message.to_string() }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidInput { .. } | ApiError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::CaptchaFailed(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::TooManyRequests(_) | ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::RangeNotSatisfiable { .. } => StatusCode::RANGE_NOT_SATISFIABLE,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Stable identifier of the error for the clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidInput { .. } | ApiError::Validation(_) => "invalid_input",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::CaptchaFailed(_) => "captcha_failed",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::TooManyRequests(_) | ApiError::RateLimited { .. } => "too_many_requests",
+            ApiError::RangeNotSatisfiable { .. } => "range_not_satisfiable",
+            ApiError::Unavailable(_) => "unavailable",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            ApiError::InvalidInput { field, .. } => Some(field.as_str()),
+            ApiError::Validation(errors) => errors.first().map(|error| error.field.as_str()),
+            _ => None
+        }
+    }
+
+    pub fn field_errors(&self) -> Vec<FieldError> {
+        match self {
+            ApiError::InvalidInput { field, message } => vec![FieldError::new(field, message)],
+            ApiError::Validation(errors) => errors.clone(),
+            _ => Vec::new()
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::InvalidInput { message, .. } | ApiError::Unauthorized(message) | ApiError::CaptchaFailed(message) |
+            ApiError::NotFound(message) | ApiError::Conflict(message) | ApiError::TooManyRequests(message) |
+            ApiError::Unavailable(message) => message.clone(),
+            ApiError::RateLimited { retry_after } => format!("Too many requests. Please try again in {} seconds.", retry_after),
+            ApiError::RangeNotSatisfiable { length } => format!("The requested range is not within the {} bytes of the file.", length),
+            ApiError::Validation(errors) => errors.iter().map(|error| error.message.as_str()).collect::<Vec<&str>>().join(" "),
+            ApiError::Internal(_) => String::from("Unknown error. Please check your input or the status of the service."),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Internal(e) => write!(f, "{}: {}", self.code(), e),
+            _ => write!(f, "{}: {}", self.code(), self.message())
+        }
+    }
+}
+
+// Failures of the database connections and queries that timed out are reported as unavailability, transitions of 
+// requests that changed state concurrently as conflicts and the rest as internal errors
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        if let Some(transition) = e.downcast_ref::<IllegalTransition>() {
+            debug!("{}", transition);
+            return ApiError::Conflict(String::from("The request has changed its state in the meantime."));
+        }
+        if let Some(quota) = e.downcast_ref::<QuotaExceeded>() {
+            debug!("{}", quota);
+            return ApiError::TooManyRequests(format!("The requests exceed the quota of the API key, which is {} requests per 24 hours.",
+                                                     quota.daily_quota));
+        }
+        if e.downcast_ref::<diesel::r2d2::PoolError>().is_some() || e.downcast_ref::<diesel::ConnectionError>().is_some()
+           || e.downcast_ref::<QueryTimeout>().is_some() {
+            debug!("Database error: {}", e);
+            return ApiError::Unavailable(String::from("The database is currently unavailable. Please try again later."));
+        }
+        ApiError::Internal(e)
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::invalid_input("body", &rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::invalid_input("path", &rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::invalid_input("query", &rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        debug!("Error: {}", self);
+        let body = ErrorBody { code: self.code().to_string(), message: self.message(), 
+                               field: self.field().map(|field| field.to_string()), errors: self.field_errors() };
+        match self {
+            ApiError::RateLimited { retry_after } => (self.status_code(), [(header::RETRY_AFTER, retry_after.to_string())], 
+                                                      axum::Json(body)).into_response(),
+            ApiError::RangeNotSatisfiable { length } => (self.status_code(), [(header::CONTENT_RANGE, format!("bytes */{}", length))], 
+                                                         axum::Json(body)).into_response(),
+            _ => (self.status_code(), axum::Json(body)).into_response()
+        }
+    }
+}
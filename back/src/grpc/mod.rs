@@ -1,14 +1,18 @@
-use std::env;
+use std::{env, io::SeekFrom, path::Path};
 use prost::bytes::BytesMut;
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
-use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use anyhow::{bail, Result};
 use tokio::io::AsyncWriteExt;
+use sha2::{Digest, Sha256};
 use tracing::debug;
-use crate::jobreceiver::uncached_data::SyncData;
-use crate::jobreceiver::{job_receiver_client::JobReceiverClient, JobRequest, StatusRequest, JobStatus, ResultRequest, self, JobDetails, ServerStatus, UncachedData};
+use crate::utils;
+use crate::jobreceiver::cache_upload::UploadData;
+use crate::jobreceiver::{job_receiver_client::JobReceiverClient, node_registry_client::NodeRegistryClient, NodeAnnouncement, JobRequest, StatusRequest, CancelRequest, CacheFile, CacheManifest, CacheUpload, FileChunk, JobProgress, JobStatus, ResultRequest, self, JobDetails};
 use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
-use tonic::Request;
+use tonic::{Request, Streaming};
+
+pub mod registry;
 
 /*
 
@@ -17,6 +21,9 @@ distributed computational network.
 
 */
 
+// Size of the chunks of an uploaded cache file
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
 #[derive(Debug)]
 pub struct GrpcClient{
     url: String,
@@ -27,11 +34,16 @@ impl GrpcClient{
 
     // Create a new instance
     pub fn new(endpoint: String, domain: &str) -> Result<Self> { 
+        Self::with_identity(endpoint, domain, "node0")
+    }
+
+    // Create a new instance that presents the certificate of the given node (e.g. a computing node towards the root node)
+    pub fn with_identity(endpoint: String, domain: &str, identity_name: &str) -> Result<Self> { 
         let data_dir = std::path::PathBuf::from_iter([env::var("MTLS_CERTS_PATH")?]);
         let server_root_ca_cert = std::fs::read_to_string(data_dir.join("machaonlocalca.cert"))?;
         let server_root_ca_cert = Certificate::from_pem(server_root_ca_cert);
-        let client_cert = std::fs::read_to_string(data_dir.join("node0.cert"))?;
-        let client_key = std::fs::read_to_string(data_dir.join("node0.key"))?;
+        let client_cert = std::fs::read_to_string(data_dir.join([identity_name, ".cert"].join("")))?;
+        let client_key = std::fs::read_to_string(data_dir.join([identity_name, ".key"].join("")))?;
         let client_identity = Identity::from_pem(client_cert, client_key);
     
         let tls = ClientTlsConfig::new()
@@ -42,14 +54,19 @@ impl GrpcClient{
         Ok(Self { url: endpoint, tls_config: tls.into() })
     }
 
-    // Establish a secure connection with mutual TLS protocol
-    pub async fn establish_secure_connection(&self) -> Result<JobReceiverClient<Channel>>  { 
+    // Open a channel with mutual TLS protocol
+    async fn open_secure_channel(&self) -> Result<Channel>  { 
         let channel = Channel::from_static(Box::leak(self.url.to_string().into_boxed_str()))
         .tls_config(self.tls_config.to_owned())?
         .connect()
         .await?;
 
-        Ok(JobReceiverClient::new(channel))
+        Ok(channel)
+    }
+
+    // Establish a secure connection with mutual TLS protocol
+    pub async fn establish_secure_connection(&self) -> Result<JobReceiverClient<Channel>>  { 
+        Ok(JobReceiverClient::new(self.open_secure_channel().await?))
     }
 
     // Establish an unencrypted connection
@@ -65,11 +82,31 @@ impl GrpcClient{
         Ok(Self::establish_secure_connection(&self).await?)
     }
 
+    // Announce a computing node to the registry of the root node
+    pub async fn register_node(&self, address: &str, domain: &str, cores: i32) -> Result<i32> {
+        let mut client = NodeRegistryClient::new(self.open_secure_channel().await?);
+
+        let request = tonic::Request::new(NodeAnnouncement { address: address.to_string(), domain: domain.to_string(), cores });
+        let response = client.register_node(request).await?;
+
+        Ok(response.into_inner().status_code)
+    }
+
     // Query the status of a computing node in MachaonWeb 
     pub async fn get_server_status(&self) -> Result<i32> {
         let mut client = Self::establish_connection(&self).await?;
 
-        let request = tonic::Request::new(StatusRequest {});
+        let request = tonic::Request::new(StatusRequest { request_id: -1, hash: String::from("") });
+        let response = client.get_status(request).await?;
+
+        Ok(response.into_inner().status_code)
+    }
+
+    // Query the status of a job that runs on a computing node
+    pub async fn get_job_status(&self, request_id: i64, hash: &str) -> Result<i32> {
+        let mut client = self.establish_connection().await?;
+
+        let request = tonic::Request::new(StatusRequest { request_id, hash: hash.to_string() });
         let response = client.get_status(request).await?;
 
         Ok(response.into_inner().status_code)
@@ -85,27 +122,80 @@ impl GrpcClient{
         Ok(response.into_inner())
     }
 
+    // Stop a running job on a node
+    pub async fn cancel_job(&self, request_id: i64, hash: &str) -> Result<i32> {
+        let mut client = self.establish_connection().await?;
+
+        let request = tonic::Request::new(CancelRequest { request_id, hash: hash.to_string() });
+        let response = client.cancel_job(request).await?;
+
+        Ok(response.into_inner().status_code)
+    }
+
+    // Follow the progress of a job that runs on a node
+    pub async fn watch_job(&self, request_id: i64, hash: &str) -> Result<Streaming<JobProgress>> {
+        let mut client = self.establish_connection().await?;
+
+        let request = tonic::Request::new(StatusRequest { request_id, hash: hash.to_string() });
+        let response = client.watch_job(request).await?;
+
+        Ok(response.into_inner())
+    }
+
     // Download the result of a finished job from a node
+    // The archive is written to a partial file next to the target path, which is renamed once its SHA-256 hash is
+    // verified. An interrupted download is resumed from the end of the partial file by the next call.
     pub async fn download_result(&self, result_request : &ResultRequest, file_path: &str) -> Result<JobDetails> {
-        let mut client = Self::establish_connection(&self).await?;
-        let request = tonic::Request::new( result_request.clone());
+        let partial_path = [file_path, ".part"].join("");
+        // Hash the part that was downloaded previously, so that the whole archive is verified at the end
+        let mut hasher = Sha256::new();
+        let mut offset: i64 = 0;
+        if Path::new(&partial_path).exists() {
+            let mut partial_file = std::fs::File::open(&partial_path)?;
+            offset = std::io::copy(&mut partial_file, &mut hasher)? as i64;
+        }
+
+        let mut client = self.establish_connection().await?;
+        let request = tonic::Request::new(ResultRequest { offset, ..result_request.clone() });
         // Calling proto method
         let mut response = client.download_result(request).await?.into_inner();
-        // Create a file to store the downloaded data
+        // Open the partial file to append the downloaded data
         let mut result_archive = tokio::fs::OpenOptions::new()
                                         .append(true)
                                         .create(true)
-                                        .open(&file_path)
+                                        .open(&partial_path)
                                         .await?;
-        let mut job_info = JobDetails{request_id: -1, hash: String::from(""), secure_hash: String::from(""), status_code: -1};
+        let mut job_info = JobDetails{request_id: -1, hash: String::from(""), secure_hash: String::from(""), status_code: -1, file_size: 0};
 
         // Download data via streaming using chunks
         let mut streaming_finished = false;
         while let Some(stream_chunk) = response.message().await?{
             match stream_chunk.job_data {
-                Some(jobreceiver::job_result::JobData::FileInfo(info)) => {job_info = info.clone();}
+                Some(jobreceiver::job_result::JobData::FileInfo(info)) => {
+                    job_info = info.clone();
+                    if job_info.status_code == 0 && job_info.file_size < offset {
+                        // The archive changed on the node since the partial download
+                        tokio::fs::remove_file(&partial_path).await?;
+                        bail!("Partial download of {} is larger than the archive", result_request.hash);
+                    }
+                }
+                Some(jobreceiver::job_result::JobData::Chunk(file_chunk)) => {
+                    if file_chunk.offset != offset || utils::compute_chunk_hash(&file_chunk.data) != file_chunk.checksum {
+                        bail!("Corrupt chunk at offset {} of {}", file_chunk.offset, result_request.hash);
+                    }
+                    result_archive.write_all(&file_chunk.data).await?;
+                    hasher.update(&file_chunk.data);
+                    offset += file_chunk.data.len() as i64;
+                }
                 Some(jobreceiver::job_result::JobData::ChunkData(file_chunk)) => {
-                    result_archive.write_all(&file_chunk).await?; 
+                    // Nodes without resumable downloads always send the whole archive
+                    if offset > 0 {
+                        result_archive.set_len(0).await?;
+                        hasher = Sha256::new();
+                        offset = 0;
+                    }
+                    result_archive.write_all(&file_chunk).await?;
+                    hasher.update(&file_chunk);
                 }
                 None =>  { streaming_finished = true; }
             };
@@ -113,44 +203,66 @@ impl GrpcClient{
                 break;
             }
         }
+        result_archive.flush().await?;
+
+        if job_info.status_code == 0 {
+            if format!("{:x}", hasher.finalize()) == job_info.secure_hash {
+                tokio::fs::rename(&partial_path, file_path).await?;
+            }
+            else {
+                // Start over in the next attempt
+                tokio::fs::remove_file(&partial_path).await?;
+                job_info.status_code = -1;
+            }
+        }
+        else if Path::new(&partial_path).exists() {
+            tokio::fs::remove_file(&partial_path).await?;
+        }
         Ok(job_info)
     }
 
-    // Transfer data to the node
-    pub async fn synchronize_node(&self, data_path: &str, secure_hash: String) -> Result<ServerStatus> {
-        let mut client = Self::establish_connection(&self).await?;
-        let mut archive = File::open(data_path).await?;
-        let eof : usize = 0;
+    // Ask a node which of the given cache files it holds
+    pub async fn get_manifest(&self, files: Vec<CacheFile>) -> Result<Vec<CacheFile>> {
+        let mut client = self.establish_connection().await?;
+
+        let request = tonic::Request::new(CacheManifest { files });
+        let response = client.get_manifest(request).await?;
+
+        Ok(response.into_inner().files)
+    }
+
+    // Upload a cache file to a node in verified chunks, starting from the bytes that the node has already received
+    pub async fn upload_file(&self, data_path: &str, cache_file: &CacheFile) -> Result<i32> {
+        let mut client = self.establish_connection().await?;
+        let mut file = File::open(data_path).await?;
+        file.seek(SeekFrom::Start(cache_file.received.max(0) as u64)).await?;
+        let file_info = cache_file.clone();
 
-        // Create a stream for the upload including the archive file and its hash
+        // Create a stream for the upload including the file information and its chunks
         let stream = async_stream::stream! {
-            let mut uncached_data = UncachedData::default();
-            uncached_data.sync_data = Some(SyncData::SecureHash(secure_hash));
-            yield uncached_data;
+            let mut offset = file_info.received.max(0);
+            yield CacheUpload { upload_data: Some(UploadData::FileInfo(file_info)) };
             loop {
-                let mut buf = BytesMut::with_capacity(1024);
-                match &archive.read_buf(&mut buf).await {
-                    Ok(n) if *n == eof => {
-                        break;
-                    },
-                    Ok(_n) => {
-                        let mut uncached_data = UncachedData::default();
-                        uncached_data.sync_data = Some(SyncData::ChunkData(buf.to_vec()));
-                        yield uncached_data;
+                let mut buf = BytesMut::with_capacity(UPLOAD_CHUNK_SIZE);
+                match file.read_buf(&mut buf).await {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        let chunk = FileChunk { offset, checksum: utils::compute_chunk_hash(&buf), data: buf.to_vec() };
+                        offset += n as i64;
+                        yield CacheUpload { upload_data: Some(UploadData::Chunk(chunk)) };
                     },
                     Err(e) => {
                         debug!("{}", e);
                         break;
                     },
                 }
-            } 
+            }
         };
-        
-        // Send the stream to the node
+
         let request = Request::new(stream);
-        let response = client.synchronize(request).await?.into_inner();
+        let response = client.upload_file(request).await?.into_inner();
 
-        Ok(response)
+        Ok(response.status_code)
     }
 
 
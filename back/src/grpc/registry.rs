@@ -0,0 +1,94 @@
+use std::{env, fs, net::{Ipv4Addr, SocketAddr}, path::PathBuf};
+use anyhow::Result;
+use tonic::{transport::{Certificate, Identity, Server, ServerTlsConfig}, Request, Response, Status};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+use tracing::{debug, instrument};
+use crate::{db::dbhandler::DatabaseHandler, error::ApiError, jobreceiver::{node_registry_server::{NodeRegistry, NodeRegistryServer}, NodeAnnouncement, ServerStatus}, logic::admin};
+
+/*
+
+This module serves the registry of the root node, where the computing nodes announce their address, the domain of
+their certificate and their cores. Only the nodes with a certificate that is signed by the local CA are accepted, and
+the announced domain must be one of the names of that certificate (a DNS name of its SANs or its CN).
+
+*/
+
+#[derive(Debug)]
+pub struct RegistryServer {
+    ip: Ipv4Addr,
+    port: u16,
+    db_handler: DatabaseHandler,
+}
+
+impl RegistryServer {
+
+    // Create a new instance
+    pub fn new(ip_string: String, port: u16) -> Result<Self> {
+        let db_handler = DatabaseHandler::new()?;
+        let ip = ip_string.parse::<Ipv4Addr>()?;
+        Ok(Self { ip, port, db_handler })
+    }
+
+    pub async fn start(self) -> Result<bool> {
+        // Mutual TLS configuration: the root node presents its own certificate and accepts only nodes signed by the local CA
+        let data_dir = PathBuf::from(env::var("MTLS_CERTS_PATH")?);
+        let client_ca_cert = Certificate::from_pem(fs::read_to_string(data_dir.join("machaonlocalca.cert"))?);
+        let server_cert = fs::read_to_string(data_dir.join("node0.cert"))?;
+        let server_key = fs::read_to_string(data_dir.join("node0.key"))?;
+        let tls = ServerTlsConfig::new()
+                    .identity(Identity::from_pem(server_cert, server_key))
+                    .client_ca_root(client_ca_cert);
+
+        let registry_socket = SocketAddr::from((self.ip, self.port));
+        Server::builder()
+            .tls_config(tls)?
+            .add_service(NodeRegistryServer::new(self))
+            .serve(registry_socket)
+            .await?;
+        Ok(true)
+    }
+}
+
+// Retrieve the DNS names of the SANs and the CN of a DER certificate
+fn certificate_names(der: &[u8]) -> Vec<String> {
+    let certificate = match X509Certificate::from_der(der) {
+        Ok((_, certificate)) => certificate,
+        Err(e) => { debug!("Invalid peer certificate: {}", e); return Vec::new(); }
+    };
+    let mut names: Vec<String> = match certificate.subject_alternative_name() {
+        Ok(Some(extension)) => extension.value.general_names.iter()
+                                        .filter_map(|name| match name { GeneralName::DNSName(dns_name) => Some(dns_name.to_string()), _ => None })
+                                        .collect(),
+        _ => Vec::new()
+    };
+    names.extend(certificate.subject().iter_common_name().filter_map(|common_name| common_name.as_str().ok()).map(String::from));
+    names
+}
+
+#[tonic::async_trait]
+impl NodeRegistry for RegistryServer {
+
+    // Add an announced node or refresh a known one
+    #[instrument(level="debug", skip(self))]
+    async fn register_node(&self, request: Request<NodeAnnouncement>) -> Result<Response<ServerStatus>, Status> {
+        // The domain of the node has to match the certificate that the node presented
+        let peer_names: Vec<String> = request.peer_certs()
+                                             .and_then(|certs| certs.first().map(|cert| certificate_names(cert.get_ref())))
+                                             .unwrap_or_default();
+        let announcement = request.into_inner();
+        if !peer_names.iter().any(|name| name.eq_ignore_ascii_case(announcement.domain.trim())) {
+            debug!("Registration rejected: the domain {} is not a name of the certificate of the node ({})",
+                   announcement.domain, peer_names.join(", "));
+            return Ok(Response::new(ServerStatus { status_code: -2 }));
+        }
+        // 0: registered, -2: invalid announcement, -4: error
+        let status_code = match admin::register_node(&self.db_handler, announcement.address.trim(),
+                                                     announcement.domain.trim(), announcement.cores).await {
+            Ok(_) => 0,
+            Err(e @ ApiError::InvalidInput { .. }) => { debug!("Registration rejected: {}", e); -2 },
+            Err(e) => { debug!("Registration failed: {}", e); -4 }
+        };
+        debug!("Registration of node {}: {}", announcement.address, status_code);
+        Ok(Response::new(ServerStatus { status_code }))
+    }
+}
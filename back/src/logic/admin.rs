@@ -0,0 +1,168 @@
+use crate::{db::{backend::PoolMetrics, dbhandler::DatabaseHandler, models::{Node, NewNode, NodeChanges}}, error::{ApiError, ApiResult}, utils};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+
+/*
+
+This module implements the administrative operations on the computing nodes, along with the metrics of the database.
+
+*/
+
+// Nodes after a successful operation (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodeResponse {
+    status_code: i32,
+    nodes: Vec<Node>
+}
+
+impl NodeResponse {
+
+    pub fn new(status : i32, nodes: Vec<Node>) -> Self {
+        Self{ status_code: status, nodes }
+    }
+}
+
+// Metrics of the connection pool of the REST API (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseResponse {
+    status_code: i32,
+    #[serde(flatten)]
+    metrics: PoolMetrics,
+}
+
+// Check an address in the form host:port, as it is stored for the nodes
+pub fn check_node_address(address: &str) -> bool {
+    match address.rsplit_once(':') {
+        Some((host, port)) => !host.is_empty() && address.len() <= 255 && port.parse::<u16>().is_ok(),
+        None => false
+    }
+}
+
+// Check the domain of a node's certificate
+fn check_node_domain(domain: &str) -> bool {
+    !domain.is_empty() && domain.len() <= 255 && !domain.contains(char::is_whitespace)
+}
+
+// Check the given fields of a node
+fn validate_node_fields(address: Option<&str>, domain: Option<&str>, cores: Option<i64>) -> ApiResult<()> {
+    if matches!(address, Some(value) if !check_node_address(value)) {
+        return Err(ApiError::invalid_input("ip", "The address of the node must be in the form host:port."));
+    }
+    if matches!(domain, Some(value) if !check_node_domain(value)) {
+        return Err(ApiError::invalid_input("domain", "The domain of the node is not valid."));
+    }
+    if matches!(cores, Some(value) if value < 1 || value > i64::from(i16::MAX)) {
+        return Err(ApiError::invalid_input("cores", "The cores of the node must be between 1 and 32767."));
+    }
+    Ok(())
+}
+
+// Retrieve a node that must exist
+async fn require_node(db_handler: &DatabaseHandler, node_id : &i16) -> ApiResult<Node> {
+    match db_handler.get_node(node_id).await? {
+        Some(node) => Ok(node),
+        None => Err(ApiError::NotFound(String::from("There is no such node.")))
+    }
+}
+
+// Retrieve all the nodes
+#[instrument(level="debug")]
+pub async fn list_nodes(db_handler: &DatabaseHandler) -> ApiResult<NodeResponse>{
+    Ok(NodeResponse::new(0, db_handler.get_nodes().await?))
+}
+
+// Retrieve the state of the connection pool and the counters of its queries
+pub fn get_database_metrics(db_handler: &DatabaseHandler) -> DatabaseResponse {
+    DatabaseResponse { status_code: 0, metrics: db_handler.get_pool_metrics() }
+}
+
+// Add a new node, which is active unless it is specified otherwise
+#[instrument(level="debug")]
+pub async fn add_node(db_handler: &DatabaseHandler, data : &serde_json::Value) -> ApiResult<NodeResponse>{
+    let address = utils::retrieve_json_str(data, "ip").trim();
+    let domain = utils::retrieve_json_str(data, "domain").trim();
+    let cores = utils::retrieve_json_int(data, "cores");
+    let active = data.get("active").and_then(|value| value.as_bool()).unwrap_or(true);
+    validate_node_fields(Some(address), Some(domain), Some(cores))?;
+    if db_handler.get_node_by_address(address).await?.is_some() {
+        return Err(ApiError::invalid_input("ip", "There is already a node with this address."));
+    }
+    db_handler.insert_node(NewNode{ ip: address.to_string(), domain: domain.to_string(), active, cores: cores as i16 }).await?;
+    let node = db_handler.get_node_by_address(address).await?;
+    Ok(NodeResponse::new(0, node.into_iter().collect()))
+}
+
+// Update the address, the domain or the cores of a node, only for the fields that are given
+#[instrument(level="debug")]
+pub async fn update_node(db_handler: &DatabaseHandler, node_id : &i16, data : &serde_json::Value) -> ApiResult<NodeResponse>{
+    require_node(db_handler, node_id).await?;
+    let address = data.get("ip").and_then(|value| value.as_str()).map(|value| value.trim());
+    let domain = data.get("domain").and_then(|value| value.as_str()).map(|value| value.trim());
+    let cores = data.get("cores").and_then(|value| value.as_i64());
+    validate_node_fields(address, domain, cores)?;
+    if let Some(value) = address {
+        if matches!(db_handler.get_node_by_address(value).await?, Some(node) if node.id != *node_id) {
+            return Err(ApiError::invalid_input("ip", "There is already a node with this address."));
+        }
+    }
+    let cores = cores.map(|value| value as i16);
+    if address.is_some() || domain.is_some() || cores.is_some() {
+        db_handler.update_node(node_id, NodeChanges{ ip: address.map(String::from), domain: domain.map(String::from), cores }).await?;
+    }
+    Ok(NodeResponse::new(0, vec![require_node(db_handler, node_id).await?]))
+}
+
+// Drain a node: its running jobs are finished but no new jobs are assigned to it
+#[instrument(level="debug")]
+pub async fn drain_node(db_handler: &DatabaseHandler, node_id : &i16) -> ApiResult<NodeResponse>{
+    require_node(db_handler, node_id).await?;
+    db_handler.update_node_drained_state(node_id, true).await?;
+    Ok(NodeResponse::new(0, vec![require_node(db_handler, node_id).await?]))
+}
+
+// Deactivate a node: its running jobs are abandoned and requeued by the job monitor
+#[instrument(level="debug")]
+pub async fn deactivate_node(db_handler: &DatabaseHandler, node_id : &i16) -> ApiResult<NodeResponse>{
+    require_node(db_handler, node_id).await?;
+    db_handler.update_node_active_state(node_id, false).await?;
+    Ok(NodeResponse::new(0, vec![require_node(db_handler, node_id).await?]))
+}
+
+// Return a drained or deactivated node to service
+#[instrument(level="debug")]
+pub async fn activate_node(db_handler: &DatabaseHandler, node_id : &i16) -> ApiResult<NodeResponse>{
+    require_node(db_handler, node_id).await?;
+    db_handler.record_node_check(node_id, true).await?;
+    db_handler.update_node_drained_state(node_id, false).await?;
+    db_handler.update_node_active_state(node_id, true).await?;
+    Ok(NodeResponse::new(0, vec![require_node(db_handler, node_id).await?]))
+}
+
+// Remove a node, which is refused while it still executes jobs
+#[instrument(level="debug")]
+pub async fn remove_node(db_handler: &DatabaseHandler, node_id : &i16) -> ApiResult<NodeResponse>{
+    require_node(db_handler, node_id).await?;
+    if db_handler.count_node_running_jobs(node_id).await? > 0 {
+        return Err(ApiError::Conflict(String::from("The node is still executing jobs. Please drain it first.")));
+    }
+    db_handler.delete_node(node_id).await?;
+    Ok(NodeResponse::new(0, Vec::new()))
+}
+
+// Register a node that announced itself: a new node is added as active, while a known node
+// keeps its active and drained state and only its domain and cores are refreshed
+#[instrument(level="debug")]
+pub async fn register_node(db_handler: &DatabaseHandler, address: &str, domain: &str, cores: i32) -> ApiResult<NodeResponse>{
+    let cores = i16::try_from(cores.max(0)).unwrap_or(i16::MAX);
+    validate_node_fields(Some(address), Some(domain), Some(i64::from(cores)))?;
+    match db_handler.get_node_by_address(address).await? {
+        Some(node) => {
+            db_handler.update_node(&node.id, NodeChanges{ ip: None, domain: Some(domain.to_string()), cores: Some(cores) }).await?;
+            db_handler.record_node_check(&node.id, true).await?;
+        },
+        None => db_handler.insert_node(NewNode{ ip: address.to_string(), domain: domain.to_string(), active: true, cores }).await?
+    }
+    let node = db_handler.get_node_by_address(address).await?;
+    Ok(NodeResponse::new(0, node.into_iter().collect()))
+}
@@ -0,0 +1,172 @@
+use crate::{db::{dbhandler::DatabaseHandler, models::{ApiKey, ApiKeyChanges, KeyedRequest, NewApiKey}}, error::{ApiError, ApiResult}, utils};
+use super::auth::{self, AdminIdentity};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+
+/*
+
+This module manages the API keys, which allow the submission of requests from scripts without the human
+verification. The keys are issued by the administrators and only the SHA-256 hash of each key is stored. Each key
+has a quota of requests per 24 hours and its requests are recorded for its history.
+
+*/
+
+// Prefix of the keys, so that they can be told apart from the session tokens
+const KEY_PREFIX: &str = "mw_";
+// Characters of a key that are stored in clear for its identification
+const KEY_PREFIX_LENGTH: usize = 11;
+// Quota of a new key when none is specified
+const DEFAULT_DAILY_QUOTA: i32 = 20;
+// Maximum number of requests in a history response
+const HISTORY_LIMIT: i64 = 500;
+
+// API key of an authenticated request
+#[derive(Debug, Clone)]
+pub struct ApiKeyIdentity {
+    pub id: i32,
+    pub owner: String,
+    pub daily_quota: i32,
+}
+
+// API keys after a successful operation (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyResponse {
+    status_code: i32,
+    api_keys: Vec<ApiKey>,
+}
+
+impl ApiKeyResponse {
+
+    pub fn new(status : i32, api_keys: Vec<ApiKey>) -> Self {
+        Self{ status_code: status, api_keys }
+    }
+}
+
+// Newly issued key, which is shown only once (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssuedKeyResponse {
+    status_code: i32,
+    key: String,
+    api_key: ApiKey,
+}
+
+impl IssuedKeyResponse {
+
+    pub fn new(status : i32, key: String, api_key: ApiKey) -> Self {
+        Self{ status_code: status, key, api_key }
+    }
+}
+
+// Usage and latest requests of a key (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyHistoryResponse {
+    status_code: i32,
+    owner: String,
+    daily_quota: i32,
+    used_quota: i64,
+    requests: Vec<KeyedRequest>,
+}
+
+impl KeyHistoryResponse {
+
+    pub fn new(status : i32, owner: String, daily_quota: i32, used_quota: i64, requests: Vec<KeyedRequest>) -> Self {
+        Self{ status_code: status, owner, daily_quota, used_quota, requests }
+    }
+}
+
+// Check the owner and the quota of a key, if they are given
+fn validate_key_fields(owner: Option<&str>, daily_quota: Option<i64>) -> ApiResult<()> {
+    if matches!(owner, Some(value) if value.is_empty() || value.len() > 64) {
+        return Err(ApiError::invalid_input("owner", "The owner of the key must have from 1 to 64 characters."));
+    }
+    if matches!(daily_quota, Some(value) if value < 0 || value > i32::MAX as i64) {
+        return Err(ApiError::invalid_input("dailyQuota", "The daily quota must be a positive number, or 0 for no limit."));
+    }
+    Ok(())
+}
+
+// Retrieve a key that must exist
+async fn require_key(db_handler: &DatabaseHandler, key_id : &i32) -> ApiResult<ApiKey> {
+    match db_handler.get_api_key(key_id).await? {
+        Some(api_key) => Ok(api_key),
+        None => Err(ApiError::NotFound(String::from("There is no such API key.")))
+    }
+}
+
+// Number of requests of a key during the last 24 hours
+async fn count_daily_requests(db_handler: &DatabaseHandler, key_id : &i32) -> Result<i64> {
+    db_handler.count_api_key_requests(key_id, 24).await
+}
+
+// Check that a string has the format of the keys, before it is looked up
+pub fn is_key_format(key: &str) -> bool {
+    key.strip_prefix(KEY_PREFIX).is_some_and(auth::is_token_format)
+}
+
+// Retrieve the key of an Authorization header, which must be active
+#[instrument(level="debug", skip_all)]
+pub async fn authenticate(db_handler: &DatabaseHandler, key: &str) -> ApiResult<ApiKeyIdentity>{
+    match db_handler.get_active_api_key(&utils::compute_chunk_hash(key.as_bytes())).await? {
+        Some(api_key) => Ok(ApiKeyIdentity{ id: api_key.id, owner: api_key.owner, daily_quota: api_key.daily_quota }),
+        None => Err(ApiError::Unauthorized(String::from("The API key is not valid.")))
+    }
+}
+
+// Retrieve the usage and the latest requests of the key of a request
+#[instrument(level="debug", skip(db_handler))]
+pub async fn get_key_history(db_handler: &DatabaseHandler, identity: &ApiKeyIdentity) -> ApiResult<KeyHistoryResponse>{
+    let used_quota = count_daily_requests(db_handler, &identity.id).await?;
+    let requests = db_handler.get_api_key_requests(&identity.id, HISTORY_LIMIT).await?;
+    Ok(KeyHistoryResponse::new(0, identity.owner.clone(), identity.daily_quota, used_quota, requests))
+}
+
+// Retrieve all the keys
+#[instrument(level="debug")]
+pub async fn list_api_keys(db_handler: &DatabaseHandler) -> ApiResult<ApiKeyResponse>{
+    Ok(ApiKeyResponse::new(0, db_handler.get_api_keys().await?))
+}
+
+// Issue a new key for an owner
+#[instrument(level="debug", skip(db_handler))]
+pub async fn issue_api_key(db_handler: &DatabaseHandler, admin: &AdminIdentity, data : &serde_json::Value) -> ApiResult<IssuedKeyResponse>{
+    let owner = utils::retrieve_json_str(data, "owner").trim();
+    let daily_quota = data.get("dailyQuota").and_then(|value| value.as_i64()).unwrap_or(i64::from(DEFAULT_DAILY_QUOTA));
+    validate_key_fields(Some(owner), Some(daily_quota))?;
+    let key = [KEY_PREFIX, &auth::generate_token()].concat();
+    let key_id = db_handler.insert_api_key(NewApiKey{ owner: owner.to_string(), key_prefix: key[..KEY_PREFIX_LENGTH].to_string(),
+                                                      key_hash: utils::compute_chunk_hash(key.as_bytes()),
+                                                      daily_quota: daily_quota as i32, admin_id: admin.id }).await?;
+    Ok(IssuedKeyResponse::new(0, key, require_key(db_handler, &key_id).await?))
+}
+
+// Update the owner, the quota or the state of a key, only for the fields that are given
+#[instrument(level="debug")]
+pub async fn update_api_key(db_handler: &DatabaseHandler, key_id : &i32, data : &serde_json::Value) -> ApiResult<ApiKeyResponse>{
+    require_key(db_handler, key_id).await?;
+    let owner = data.get("owner").and_then(|value| value.as_str()).map(|value| value.trim());
+    let daily_quota = data.get("dailyQuota").and_then(|value| value.as_i64());
+    let active = data.get("active").and_then(|value| value.as_bool());
+    validate_key_fields(owner, daily_quota)?;
+    let daily_quota = daily_quota.map(|value| value as i32);
+    if owner.is_some() || daily_quota.is_some() || active.is_some() {
+        db_handler.update_api_key(key_id, ApiKeyChanges{ owner: owner.map(String::from), daily_quota, active }).await?;
+    }
+    Ok(ApiKeyResponse::new(0, vec![require_key(db_handler, key_id).await?]))
+}
+
+// Revoke a key, whose history is kept
+#[instrument(level="debug")]
+pub async fn revoke_api_key(db_handler: &DatabaseHandler, key_id : &i32) -> ApiResult<ApiKeyResponse>{
+    require_key(db_handler, key_id).await?;
+    db_handler.update_api_key(key_id, ApiKeyChanges{ owner: None, daily_quota: None, active: Some(false) }).await?;
+    Ok(ApiKeyResponse::new(0, vec![require_key(db_handler, key_id).await?]))
+}
+
+// Retrieve the usage and the latest requests of any key
+#[instrument(level="debug")]
+pub async fn get_api_key_history(db_handler: &DatabaseHandler, key_id : &i32) -> ApiResult<KeyHistoryResponse>{
+    let api_key = require_key(db_handler, key_id).await?;
+    get_key_history(db_handler, &ApiKeyIdentity{ id: api_key.id, owner: api_key.owner, daily_quota: api_key.daily_quota }).await
+}
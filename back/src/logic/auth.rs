@@ -0,0 +1,141 @@
+use argon2::{password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString}, Argon2};
+use chrono::{Duration, NaiveDateTime, Utc};
+use rand::{rngs::OsRng, RngCore};
+use crate::{db::{dbhandler::DatabaseHandler, models::{NewAdmin, NewAdminSession, NewAuditEntry}}, error::{ApiError, ApiResult}, utils};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+
+/*
+
+This module authenticates the administrators of MachaonWeb. Passwords are stored as Argon2id hashes and each login
+opens a session with a random token, of which only the SHA-256 hash is stored. Every administrative action is recorded
+in the audit log.
+
+*/
+
+// Size of a session token in bytes, before its hexadecimal encoding
+const TOKEN_SIZE: usize = 32;
+// Maximum length of an audited payload in characters, so that it fits in a TEXT column
+const MAX_AUDIT_PAYLOAD: usize = 16000;
+
+// Administrator of an authenticated request
+#[derive(Debug, Clone)]
+pub struct AdminIdentity {
+    pub id: i32,
+    pub username: String,
+}
+
+// Session of a successful login (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginResponse {
+    status_code: i32,
+    token: String,
+    expiration_date: NaiveDateTime,
+}
+
+impl LoginResponse {
+
+    pub fn new(status : i32, token: String, expiration_date: NaiveDateTime) -> Self {
+        Self{ status_code: status, token, expiration_date }
+    }
+}
+
+// Outcome of a successful logout (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogoutResponse {
+    status_code: i32,
+}
+
+impl LogoutResponse {
+
+    pub fn new(status : i32) -> Self {
+        Self{ status_code: status }
+    }
+}
+
+// Compute the Argon2id hash of a password with a random salt
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = Argon2::default().hash_password(password.as_bytes(), &salt)
+                                         .map_err(|e| anyhow!("Password hashing failed: {}", e))?;
+    Ok(password_hash.to_string())
+}
+
+// Check a password against its stored hash
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed_hash) => Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok(),
+        Err(_) => false
+    }
+}
+
+// Generate a random token in hexadecimal
+pub fn generate_token() -> String {
+    let mut token = [0u8; TOKEN_SIZE];
+    OsRng.fill_bytes(&mut token);
+    token.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Check that a string has the format of the generated tokens, before it is looked up
+pub fn is_token_format(token: &str) -> bool {
+    token.len() == 2 * TOKEN_SIZE && token.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+// Create an administrator or replace the password of an existing one
+pub async fn create_admin(db_handler: &DatabaseHandler, username: &str, password: &str) -> Result<()> {
+    let username = username.trim();
+    if username.is_empty() || username.len() > 64 || password.is_empty() {
+        return Err(anyhow!("Invalid username or password"));
+    }
+    let password_hash = hash_password(password)?;
+    match db_handler.get_admin_by_username(username).await? {
+        Some(admin) => db_handler.update_admin_password(&admin.id, &password_hash).await?,
+        None => db_handler.insert_admin(NewAdmin{ username: username.to_string(), password_hash }).await?
+    }
+    Ok(())
+}
+
+// Open a session for an administrator with valid credentials, recording the attempt in the audit log
+#[instrument(level="debug", skip(db_handler, data))]
+pub async fn login(db_handler: &DatabaseHandler, data : &serde_json::Value, session_minutes: i64) -> ApiResult<LoginResponse>{
+    let username = utils::retrieve_json_str(data, "username").trim();
+    let password = utils::retrieve_json_str(data, "password");
+    let admin = match db_handler.get_admin_by_username(username).await? {
+        Some(admin) if admin.active && verify_password(password, &admin.password_hash) => admin,
+        other => {
+            let admin_id = other.map_or(-1, |admin| admin.id);
+            record_action(db_handler, admin_id, username, "POST /admin/login", "", -1).await?;
+            return Err(ApiError::Unauthorized(String::from("Invalid username or password.")));
+        }
+    };
+    let token = generate_token();
+    let expiration_date = Utc::now().naive_utc() + Duration::minutes(session_minutes);
+    db_handler.insert_admin_session(NewAdminSession{ token_hash: utils::compute_chunk_hash(token.as_bytes()),
+                                                     admin_id: admin.id, expiration_date }).await?;
+    record_action(db_handler, admin.id, &admin.username, "POST /admin/login", "", 0).await?;
+    Ok(LoginResponse::new(0, token, expiration_date))
+}
+
+// Close the session of a token, along with any expired sessions
+#[instrument(level="debug", skip_all)]
+pub async fn logout(db_handler: &DatabaseHandler, token: &str) -> ApiResult<LogoutResponse>{
+    db_handler.delete_admin_session(&utils::compute_chunk_hash(token.as_bytes()), Utc::now().naive_utc()).await?;
+    Ok(LogoutResponse::new(0))
+}
+
+// Retrieve the administrator of a session token, if the session is valid
+#[instrument(level="debug", skip_all)]
+pub async fn authenticate(db_handler: &DatabaseHandler, token: &str) -> Result<Option<AdminIdentity>>{
+    let admin = db_handler.get_session_admin(&utils::compute_chunk_hash(token.as_bytes()), Utc::now().naive_utc()).await?;
+    Ok(admin.map(|admin| AdminIdentity{ id: admin.id, username: admin.username }))
+}
+
+// Record an action of an administrator in the audit log
+pub async fn record_action(db_handler: &DatabaseHandler, admin_id: i32, actor: &str, action: &str, payload: &str, status_code: i32) -> Result<()>{
+    let payload = utils::get_substring(&payload.to_string(), MAX_AUDIT_PAYLOAD);
+    let actor = utils::get_substring(&actor.to_string(), 64);
+    let action = utils::get_substring(&action.to_string(), 255);
+    db_handler.insert_audit_entry(NewAuditEntry{ admin_id, actor, action, payload, status_code }).await
+}
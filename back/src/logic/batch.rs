@@ -0,0 +1,152 @@
+use std::path::{Path, PathBuf};
+use crate::{db::{dbhandler::DatabaseHandler, models::{NewBatch, NewBatchRequest, RequestState}}, error::{ApiError, ApiResult}, utils};
+use super::{apikeys::ApiKeyIdentity, input::BatchInput, verifier::HumanVerifier, RequestResponse};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+
+
+/*
+
+This module handles the batches of requests, which compare several references with the same candidates and settings.
+Each reference has its own request with its own access token, and the batch keeps track of them so that their status
+and results are available together. Identical requests are still computed once, since the request monitor serves a
+request from the result of an identical one.
+
+*/
+
+// Batch after its submission (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchResponse {
+    status_code: i32,
+    batch_id: String,
+    requests: Vec<RequestResponse>,
+}
+
+// State of a request of a batch (one of the states of the requests), along with the status code of its job
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchedRequestStatus {
+    request_id: i64,
+    hash: String,
+    access_token: String,
+    reference: String,
+    state: String,
+    status_code: i32,
+}
+
+// Aggregated status of the requests of a batch (status code 0). The requests with a job on a node are counted as running
+// and the expired requests as failed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchStatus {
+    status_code: i32,
+    batch_id: String,
+    total: usize,
+    queued: usize,
+    running: usize,
+    completed: usize,
+    failed: usize,
+    cancelled: usize,
+    requests: Vec<BatchedRequestStatus>,
+}
+
+// Check that a batch id is a UUID that exists
+async fn require_batch(db_handler: &DatabaseHandler, batch_id: &str) -> ApiResult<()> {
+    if uuid::Uuid::parse_str(batch_id).is_err() || !db_handler.verify_batch(batch_id).await? {
+        return Err(ApiError::NotFound(String::from("There is no such batch.")));
+    }
+    Ok(())
+}
+
+// Handle a batch of requests, which is verified either by the human verifier or by an API key
+#[instrument(level="debug")]
+pub async fn create_batch(db_handler: &DatabaseHandler, verifier: &dyn HumanVerifier, input : &BatchInput,
+                          api_key: Option<&ApiKeyIdentity>) -> ApiResult<BatchResponse>{
+    super::verify_submission(verifier, &input.settings, api_key).await?;
+
+    // Check the settings and every reference of the batch, including the existence of the preset candidate list
+    let mut errors = Vec::new();
+    let batch = match input.validate() {
+        Ok(batch) => Some(batch),
+        Err(field_errors) => { errors = field_errors; None }
+    };
+    errors.extend(super::check_candidate_list(db_handler, &input.settings).await);
+    let batch = match batch {
+        Some(batch) if errors.is_empty() => batch,
+        _ => return Err(ApiError::Validation(errors))
+    };
+
+    // The requests of an API key are also limited by its daily quota, which has to cover the whole batch
+    let mut hashes = Vec::new();
+    let mut new_requests = Vec::new();
+    for parameters in batch {
+        let hash = super::calculate_request_hash(&parameters)?;
+        new_requests.push(super::prepare_request(db_handler, &parameters, &hash).await);
+        hashes.push(hash);
+    }
+    let requests: Vec<RequestResponse> = super::store_requests(db_handler, new_requests, api_key).await?.into_iter().zip(hashes)
+                                                .map(|((request_id, access_token), hash)| RequestResponse::new(0, hash, access_token, request_id))
+                                                .collect();
+
+    // Store the batch along with the order of its references
+    let batch_id = uuid::Uuid::new_v4().to_string();
+    let batched_requests: Vec<NewBatchRequest> = requests.iter().zip(0..)
+                                                         .map(|(request, position)| NewBatchRequest{ batch_id: batch_id.clone(),
+                                                                                                     request_id: request.request_id, position })
+                                                         .collect();
+    db_handler.insert_batch(NewBatch{ id: batch_id.clone(), api_key_id: api_key.map(|identity| identity.id) }, batched_requests).await?;
+    debug!("Batch {} with {} requests", batch_id, requests.len());
+    Ok(BatchResponse{ status_code: 0, batch_id, requests })
+}
+
+// Retrieve the aggregated status of the requests of a batch
+#[instrument(level="debug")]
+pub async fn get_batch_status(db_handler: &DatabaseHandler, batch_id: &str) -> ApiResult<BatchStatus>{
+    require_batch(db_handler, batch_id).await?;
+    let mut batch_status = BatchStatus { batch_id: batch_id.to_string(), ..Default::default() };
+    for request in db_handler.get_batched_requests(batch_id).await? {
+        let state = request.state.parse::<RequestState>()?;
+        match state {
+            RequestState::Queued => batch_status.queued += 1,
+            RequestState::Completed => batch_status.completed += 1,
+            RequestState::Cancelled => batch_status.cancelled += 1,
+            RequestState::Failed | RequestState::Expired => batch_status.failed += 1,
+            _ => batch_status.running += 1
+        }
+        // The failed and the cancelled requests report the status code of their job
+        let status_code = match state {
+            RequestState::Failed | RequestState::Cancelled | RequestState::Expired => 
+                db_handler.get_latest_job(&request.request_id).await?.map_or(-1, |job| i32::from(job.status_code)),
+            _ => 0
+        };
+        let access_token = request.access_token.unwrap_or_else(|| request.hash.clone());
+        batch_status.requests.push(BatchedRequestStatus { request_id: request.request_id, hash: request.hash, access_token,
+                                                          reference: request.reference, state: request.state, status_code });
+    }
+    batch_status.total = batch_status.requests.len();
+    Ok(batch_status)
+}
+
+// Bundle the archives of the completed requests of a batch and return the path of the bundle.
+// A bundle is reused until more requests of the batch are completed.
+#[instrument(level="debug")]
+pub async fn bundle_batch_results(db_handler: &DatabaseHandler, batch_id: &str, output_path: &str) -> ApiResult<PathBuf>{
+    let batch_status = get_batch_status(db_handler, batch_id).await?;
+    let archives: Vec<(String, PathBuf)> = batch_status.requests.iter()
+                        .filter(|request| request.state == "completed")
+                        .map(|request| ([request.reference.as_str(), "_", &request.hash, ".zip"].join(""),
+                                        Path::new(output_path).join(&request.hash).join([&request.hash, ".zip"].join(""))))
+                        .filter(|(_, archive_path)| archive_path.exists())
+                        .collect();
+    if archives.is_empty() {
+        return Err(ApiError::NotFound(String::from("No results of this batch are available yet.")));
+    }
+    let fingerprint = utils::compute_chunk_hash(archives.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>().join("\n").as_bytes());
+    let bundle_directory = PathBuf::from(output_path).join("batches");
+    let bundle_path = bundle_directory.join([batch_id, "-", &fingerprint[..16], ".zip"].join(""));
+    if !bundle_path.exists() {
+        let target_path = bundle_path.clone();
+        let batch_prefix = [batch_id, "-"].join("");
+        tokio::task::spawn_blocking(move || utils::bundle_files(&archives, &bundle_directory, &target_path, &batch_prefix))
+            .await.map_err(anyhow::Error::from)??;
+    }
+    Ok(bundle_path)
+}
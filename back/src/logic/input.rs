@@ -0,0 +1,485 @@
+use serde::{Deserialize, Serialize};
+use crate::{error::FieldError, utils};
+
+
+/*
+
+This module defines the typed input of a comparison request and of a batch of requests (version 1 of the REST API)
+and their validation, which reports every invalid field at once.
+
+*/
+
+// Maximum number of characters of the reference and the custom list of candidates
+const MAX_REFERENCE_LENGTH: usize = 40;
+const MAX_CUSTOM_LIST_LENGTH: usize = 5000;
+// Maximum number of references in a batch
+pub const MAX_BATCH_REFERENCES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComparisonMode {
+    Whole = 0,
+    Domain = 1,
+    Segment = 2,
+}
+
+impl TryFrom<i64> for ComparisonMode {
+    type Error = ();
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ComparisonMode::Whole),
+            1 => Ok(ComparisonMode::Domain),
+            2 => Ok(ComparisonMode::Segment),
+            _ => Err(())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlignmentLevel {
+    Primary = 0,
+    Secondary = 1,
+    Hydrophobicity = 2,
+    Mixed = 3,
+}
+
+impl TryFrom<i64> for AlignmentLevel {
+    type Error = ();
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(AlignmentLevel::Primary),
+            1 => Ok(AlignmentLevel::Secondary),
+            2 => Ok(AlignmentLevel::Hydrophobicity),
+            3 => Ok(AlignmentLevel::Mixed),
+            _ => Err(())
+        }
+    }
+}
+
+// Comparison request as it is sent by the clients
+// The fields are optional so that every missing or invalid field is reported by the validation
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestInput {
+    pub reference: Option<String>,
+    // Id of a preset candidate list, or -1 for a custom list
+    pub candidate_list: Option<i64>,
+    // Comma-separated structure ids
+    pub custom_list: Option<String>,
+    pub comparison_mode: Option<i64>,
+    // Required for the segment comparisons
+    pub alignment_level: Option<i64>,
+    pub segment_start: Option<i64>,
+    pub segment_end: Option<i64>,
+    pub meta: Option<bool>,
+    // Gene Ontology term (GO:NNNNNNN) of the meta-analysis
+    pub go_term: Option<String>,
+    // Captcha token
+    pub token: Option<String>,
+}
+
+// Batch of requests for several references with the same candidates and comparison settings
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BatchInput {
+    pub references: Option<Vec<String>>,
+    // The reference of the settings is ignored
+    #[serde(flatten)]
+    pub settings: RequestInput,
+}
+
+// Candidates of a valid request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Candidates {
+    PresetList(i32),
+    CustomList(Vec<String>),
+}
+
+// Valid parameters of a comparison request
+#[derive(Debug, Clone)]
+pub struct RequestParameters {
+    // Reference in the form <structure id>_<chain id>
+    pub reference: String,
+    pub reference_structure: String,
+    pub candidates: Candidates,
+    pub comparison_mode: ComparisonMode,
+    pub alignment_level: Option<AlignmentLevel>,
+    // Residue range of the segment comparisons, -1 otherwise
+    pub segment_start: i32,
+    pub segment_end: i32,
+    pub meta: bool,
+    // Gene Ontology term in the form GO:NNNNNNN, empty if there is none
+    pub go_term: String,
+}
+
+impl RequestInput {
+
+    // Read a request of the unversioned endpoint, whose earlier clients send the fields without a fixed schema
+    // A field of an unexpected type is treated as missing, as the untyped parser of those clients did
+    pub fn from_legacy(data: &serde_json::Value) -> Self {
+        let string = |field: &str| data.get(field).and_then(|value| value.as_str()).map(String::from);
+        let int = |field: &str| data.get(field).and_then(|value| value.as_i64());
+        Self {
+            reference: string("reference"),
+            candidate_list: int("candidateList"),
+            custom_list: string("customList"),
+            comparison_mode: int("comparisonMode"),
+            alignment_level: int("alignmentLevel"),
+            segment_start: int("segmentStart"),
+            segment_end: int("segmentEnd"),
+            meta: data.get("meta").and_then(|value| value.as_bool()),
+            go_term: string("goTerm"),
+            token: string("token"),
+        }
+    }
+
+    // Check every field of the request and convert it to its parameters, or report all the invalid fields
+    pub fn validate(&self) -> Result<RequestParameters, Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        // Reference structure and chain
+        let reference_input = utils::get_substring(&self.reference.clone().unwrap_or_default().trim().to_uppercase(),
+                                                   MAX_REFERENCE_LENGTH).replace("-MODEL_V", "-model_v");
+        let reference_parts = utils::check_composite_id(&reference_input).unwrap_or_default();
+        if self.reference.is_none() {
+            errors.push(FieldError::new("reference", "The reference structure is required."));
+        }
+        else if reference_parts.is_empty() {
+            errors.push(FieldError::new("reference", "There is a problem with the provided reference id."));
+        }
+
+        // Comparison mode and alignment level
+        let comparison_mode = match self.comparison_mode.map(ComparisonMode::try_from) {
+            Some(Ok(mode)) => Some(mode),
+            Some(Err(_)) => { errors.push(FieldError::new("comparisonMode", "Invalid option for comparison mode.")); None },
+            None => { errors.push(FieldError::new("comparisonMode", "The comparison mode is required.")); None }
+        };
+        let segment_mode = comparison_mode == Some(ComparisonMode::Segment);
+        let alignment_level = match self.alignment_level.map(AlignmentLevel::try_from) {
+            Some(Ok(level)) => Some(level),
+            Some(Err(_)) | None if segment_mode => {
+                errors.push(FieldError::new("alignmentLevel", "Invalid option for segment alignment level.")); None
+            },
+            _ => None
+        };
+
+        // Residue range of the segment comparisons
+        let (mut segment_start, mut segment_end) = (-1, -1);
+        if segment_mode {
+            let start = self.segment_start.unwrap_or(-1);
+            let end = self.segment_end.unwrap_or(-1);
+            if !(1..=10000).contains(&start) {
+                errors.push(FieldError::new("segmentStart", "The residue range must start between 1 and 10000."));
+            }
+            if !(1..=10000).contains(&end) {
+                errors.push(FieldError::new("segmentEnd", "The residue range must end between 1 and 10000."));
+            }
+            else if end - start > 600 || end - start < 2 {
+                errors.push(FieldError::new("segmentEnd", "The residue range must span from 3 to 601 residues."));
+            }
+            segment_start = start as i32;
+            segment_end = end as i32;
+        }
+
+        // Preset or custom list of candidates
+        let candidate_list = self.candidate_list.unwrap_or(-1);
+        let candidates = if candidate_list > -1 {
+            if segment_mode {
+                errors.push(FieldError::new("candidateList", "Segment comparisons require a custom list of candidates."));
+            }
+            else if candidate_list > i32::MAX as i64 {
+                errors.push(FieldError::new("candidateList", "The selected candidate list identifier is not present in the system."));
+            }
+            Candidates::PresetList(candidate_list as i32)
+        }
+        else {
+            let custom_input = utils::get_substring(&self.custom_list.clone().unwrap_or_default().trim().to_uppercase(),
+                                                    MAX_CUSTOM_LIST_LENGTH).replace("-MODEL_V", "-model_v");
+            let mut structure_ids: Vec<String> = Vec::new();
+            for id in custom_input.split(',') {
+                if id.is_empty() {
+                    structure_ids.clear();
+                    break;
+                }
+                if !structure_ids.iter().any(|existing| existing == id) {
+                    structure_ids.push(id.to_string());
+                }
+            }
+            if structure_ids.is_empty() {
+                errors.push(FieldError::new("customList", "Please re-check the custom list of candidates."));
+            }
+            Candidates::CustomList(structure_ids)
+        };
+
+        // Gene Ontology term of the meta-analysis
+        let meta = self.meta.unwrap_or(false);
+        let go_term = self.go_term.clone().unwrap_or_default().trim().to_uppercase();
+        if !go_term.is_empty() {
+            if !is_go_term(&go_term) {
+                errors.push(FieldError::new("goTerm", "The GO term must be in the form GO:NNNNNNN (e.g. GO:0001525)."));
+            }
+            else if !meta {
+                errors.push(FieldError::new("goTerm", "A GO term requires the meta-analysis."));
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(RequestParameters { reference: reference_parts.join("_"), reference_structure: reference_parts[0].clone(), candidates,
+                               comparison_mode: comparison_mode.unwrap_or(ComparisonMode::Whole), alignment_level,
+                               segment_start, segment_end, meta, go_term })
+    }
+}
+
+impl BatchInput {
+
+    // Check the settings and every reference of the batch and convert them to the parameters of its requests,
+    // once for each distinct reference, or report all the invalid fields
+    pub fn validate(&self) -> Result<Vec<RequestParameters>, Vec<FieldError>> {
+        let references = self.references.clone().unwrap_or_default();
+        if references.is_empty() {
+            return Err(vec![FieldError::new("references", "At least one reference structure is required.")]);
+        }
+        if references.len() > MAX_BATCH_REFERENCES {
+            return Err(vec![FieldError::new("references", &format!("A batch can have up to {} references.", MAX_BATCH_REFERENCES))]);
+        }
+        let mut errors: Vec<FieldError> = Vec::new();
+        let mut batch: Vec<RequestParameters> = Vec::new();
+        for (index, reference) in references.into_iter().enumerate() {
+            let input = RequestInput { reference: Some(reference), ..self.settings.clone() };
+            match input.validate() {
+                Ok(parameters) => {
+                    if !batch.iter().any(|existing| existing.reference == parameters.reference) {
+                        batch.push(parameters);
+                    }
+                },
+                // The errors of the shared settings are reported once
+                Err(field_errors) => for error in field_errors {
+                    if error.field() == "reference" {
+                        errors.push(FieldError::new(&format!("references[{}]", index), error.message()));
+                    }
+                    else if !errors.iter().any(|existing| existing.field() == error.field()) {
+                        errors.push(error);
+                    }
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(batch)
+    }
+}
+
+// Version of the canonical form of the requests, which is part of their hash
+const CANONICAL_FORM_VERSION: u32 = 2;
+
+// Check that a term is a Gene Ontology identifier (GO: followed by 7 digits)
+fn is_go_term(term: &str) -> bool {
+    match term.strip_prefix("GO:") {
+        Some(digits) => digits.len() == 7 && digits.chars().all(|digit| digit.is_ascii_digit()),
+        None => false
+    }
+}
+
+// Fields that identify the outcome of a request, in a fixed order.
+// The meta-analysis settings are left out of the base comparison and of the requests without meta-analysis.
+#[derive(Serialize)]
+struct CanonicalRequest<'a> {
+    version: u32,
+    reference: &'a str,
+    candidate_list: Option<i32>,
+    custom_list: Option<Vec<&'a str>>,
+    comparison_mode: i16,
+    alignment_level: Option<i16>,
+    segment_start: i32,
+    segment_end: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    go_term: Option<&'a str>,
+}
+
+impl RequestParameters {
+
+    // Serialize the parameters that identify the outcome of the request, including its meta-analysis
+    pub fn canonical_form(&self) -> String {
+        self.serialize_canonically(self.meta)
+    }
+
+    // Serialize the parameters of the comparison without the meta-analysis, which is shared by the requests 
+    // that differ only in their meta-analysis settings
+    pub fn base_canonical_form(&self) -> String {
+        self.serialize_canonically(false)
+    }
+
+    // Serialize the parameters so that equivalent requests have the same form: the custom candidates are sorted 
+    // and the alignment level is only kept for the segment comparisons
+    fn serialize_canonically(&self, with_meta: bool) -> String {
+        let (candidate_list, custom_list) = match &self.candidates {
+            Candidates::PresetList(list_id) => (Some(*list_id), None),
+            Candidates::CustomList(structure_ids) => {
+                let mut structure_ids: Vec<&str> = structure_ids.iter().map(|id| id.as_str()).collect();
+                structure_ids.sort_unstable();
+                structure_ids.dedup();
+                (None, Some(structure_ids))
+            }
+        };
+        let alignment_level = match self.alignment_level {
+            Some(level) if self.comparison_mode == ComparisonMode::Segment => Some(level as i16),
+            _ => None
+        };
+        let canonical_request = CanonicalRequest { version: CANONICAL_FORM_VERSION, reference: &self.reference, candidate_list,
+                                                   custom_list, comparison_mode: self.comparison_mode as i16, alignment_level,
+                                                   segment_start: self.segment_start, segment_end: self.segment_end,
+                                                   meta: if with_meta { Some(true) } else { None },
+                                                   go_term: if with_meta { Some(&self.go_term) } else { None } };
+        // The serialization of plain fields does not fail
+        serde_json::to_string(&canonical_request).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn whole_comparison(candidates: Candidates) -> RequestParameters {
+        RequestParameters { reference: String::from("1ABC_A"), reference_structure: String::from("1ABC"), candidates,
+                            comparison_mode: ComparisonMode::Whole, alignment_level: Some(AlignmentLevel::Mixed),
+                            segment_start: -1, segment_end: -1, meta: true, go_term: String::from("GO:0001525") }
+    }
+
+    fn custom_list(structure_ids: &[&str]) -> Candidates {
+        Candidates::CustomList(structure_ids.iter().map(|id| id.to_string()).collect())
+    }
+
+    fn whole_input(reference: &str, custom_list: &str) -> RequestInput {
+        RequestInput { reference: Some(reference.to_string()), candidate_list: Some(-1), custom_list: Some(custom_list.to_string()),
+                       comparison_mode: Some(0), ..Default::default() }
+    }
+
+    fn fields(errors: &[FieldError]) -> Vec<String> {
+        errors.iter().map(|error| error.field().to_string()).collect()
+    }
+
+    #[test]
+    fn legacy_fields_of_unexpected_types_are_missing() {
+        let data = serde_json::json!({ "reference": "1abc_a", "candidateList": "3", "customList": "2DEF,1XYZ", "comparisonMode": 0,
+                                       "alignmentLevel": null, "meta": "true", "goTerm": 1525, "token": "captcha" });
+        let input = RequestInput::from_legacy(&data);
+        assert_eq!(input.reference.as_deref(), Some("1abc_a"));
+        assert_eq!(input.candidate_list, None);
+        assert_eq!(input.custom_list.as_deref(), Some("2DEF,1XYZ"));
+        assert_eq!((input.comparison_mode, input.alignment_level, input.segment_start, input.segment_end), (Some(0), None, None, None));
+        assert_eq!((input.meta, input.go_term.as_deref(), input.token.as_deref()), (None, None, Some("captcha")));
+        assert!(input.validate().is_ok());
+    }
+
+    // The canonical forms and their hashes identify the stored requests, so they must not change within a version
+    #[test]
+    fn canonical_form_golden_values() {
+        let parameters = whole_comparison(custom_list(&["2DEF", "1XYZ"]));
+        let canonical_form = r#"{"version":2,"reference":"1ABC_A","candidate_list":null,"custom_list":["1XYZ","2DEF"],"comparison_mode":0,"alignment_level":null,"segment_start":-1,"segment_end":-1,"meta":true,"go_term":"GO:0001525"}"#;
+        let base_canonical_form = r#"{"version":2,"reference":"1ABC_A","candidate_list":null,"custom_list":["1XYZ","2DEF"],"comparison_mode":0,"alignment_level":null,"segment_start":-1,"segment_end":-1}"#;
+        assert_eq!(parameters.canonical_form(), canonical_form);
+        assert_eq!(parameters.base_canonical_form(), base_canonical_form);
+        assert_eq!(utils::compute_chunk_hash(parameters.canonical_form().as_bytes()), "b40ae35adce4eff8de83e7c67fb606fc24ba4c82a69d2bf8f208e99908f3c7e2");
+        assert_eq!(utils::compute_chunk_hash(parameters.base_canonical_form().as_bytes()), "1d0b77cc575a0a89dc75aeaefdfeb6376daf763fe28362780142ef3e2c9f3c33");
+    }
+
+    #[test]
+    fn reordered_and_duplicated_candidates_have_the_same_hash() {
+        let hash = |structure_ids: &[&str]| utils::compute_chunk_hash(whole_comparison(custom_list(structure_ids)).canonical_form().as_bytes());
+        let expected = hash(&["1XYZ", "2DEF", "3GHI"]);
+        assert_eq!(hash(&["3GHI", "1XYZ", "2DEF"]), expected);
+        assert_eq!(hash(&["2DEF", "3GHI", "2DEF", "1XYZ", "3GHI"]), expected);
+        assert_ne!(hash(&["1XYZ", "2DEF"]), expected);
+    }
+
+    #[test]
+    fn canonical_form_ignores_irrelevant_settings() {
+        // The alignment level only matters for the segment comparisons, and the meta-analysis only for the full form
+        let mut parameters = whole_comparison(Candidates::PresetList(1));
+        let canonical_form = parameters.canonical_form();
+        parameters.alignment_level = None;
+        assert_eq!(parameters.canonical_form(), canonical_form);
+        parameters.meta = false;
+        assert_ne!(parameters.canonical_form(), canonical_form);
+        assert_eq!(parameters.canonical_form(), parameters.base_canonical_form());
+    }
+
+    #[test]
+    fn valid_request() {
+        let parameters = whole_input(" 1abc_a ", "2def,1xyz,2DEF").validate().unwrap();
+        assert_eq!(parameters.reference, "1ABC_A");
+        assert_eq!(parameters.reference_structure, "1ABC");
+        assert_eq!(parameters.candidates, custom_list(&["2DEF", "1XYZ"]));
+        assert_eq!(parameters.comparison_mode, ComparisonMode::Whole);
+        assert_eq!(parameters.alignment_level, None);
+        assert_eq!((parameters.segment_start, parameters.segment_end), (-1, -1));
+        assert!(!parameters.meta);
+    }
+
+    #[test]
+    fn missing_fields_are_all_reported() {
+        let errors = RequestInput::default().validate().unwrap_err();
+        assert_eq!(fields(&errors), ["reference", "comparisonMode", "customList"]);
+    }
+
+    #[test]
+    fn invalid_fields_are_all_reported() {
+        let input = RequestInput { reference: Some(String::from("not a reference")), candidate_list: Some(3), custom_list: None,
+                                   comparison_mode: Some(2), alignment_level: Some(9), segment_start: Some(0), segment_end: Some(20000),
+                                   meta: Some(true), go_term: Some(String::from("GO:12")), token: None };
+        let errors = input.validate().unwrap_err();
+        assert_eq!(fields(&errors), ["reference", "alignmentLevel", "segmentStart", "segmentEnd", "candidateList", "goTerm"]);
+
+        let input = RequestInput { comparison_mode: Some(7), go_term: Some(String::from("GO:0001525")), ..whole_input("1ABC_A", ",") };
+        let errors = input.validate().unwrap_err();
+        assert_eq!(fields(&errors), ["comparisonMode", "customList", "goTerm"]);
+    }
+
+    #[test]
+    fn segment_ranges() {
+        let segment = |start: i64, end: i64| RequestInput { comparison_mode: Some(2), alignment_level: Some(1), segment_start: Some(start),
+                                                            segment_end: Some(end), ..whole_input("1ABC_A", "2DEF") }.validate();
+        let parameters = segment(10, 12).unwrap();
+        assert_eq!((parameters.segment_start, parameters.segment_end), (10, 12));
+        assert_eq!(parameters.alignment_level, Some(AlignmentLevel::Secondary));
+        assert!(segment(10, 610).is_ok());
+        assert_eq!(fields(&segment(10, 11).unwrap_err()), ["segmentEnd"]);
+        assert_eq!(fields(&segment(10, 611).unwrap_err()), ["segmentEnd"]);
+    }
+
+    #[test]
+    fn valid_batch() {
+        let input = BatchInput { references: Some(vec![String::from("1abc_a"), String::from("2DEF_B"), String::from("1ABC_A")]),
+                                 settings: whole_input("ignored", "3GHI") };
+        let batch = input.validate().unwrap();
+        // The repeated references are compared once
+        let references: Vec<&str> = batch.iter().map(|parameters| parameters.reference.as_str()).collect();
+        assert_eq!(references, ["1ABC_A", "2DEF_B"]);
+    }
+
+    #[test]
+    fn batch_errors_are_reported_per_reference() {
+        let input = BatchInput { references: Some(vec![String::from("1ABC_A"), String::from("bad"), String::from("2DEF_B"), String::new()]),
+                                 settings: RequestInput { comparison_mode: Some(5), ..whole_input("ignored", "3GHI") } };
+        let errors = input.validate().unwrap_err();
+        // The errors of the shared settings are reported once
+        assert_eq!(fields(&errors), ["comparisonMode", "references[1]", "references[3]"]);
+    }
+
+    #[test]
+    fn batch_sizes() {
+        let batch = |count: usize| BatchInput { references: Some(vec![String::from("1ABC_A"); count]),
+                                                settings: whole_input("ignored", "3GHI") }.validate();
+        assert_eq!(fields(&batch(0).unwrap_err()), ["references"]);
+        assert_eq!(fields(&BatchInput { references: None, settings: whole_input("ignored", "3GHI") }.validate().unwrap_err()), ["references"]);
+        assert!(batch(MAX_BATCH_REFERENCES).is_ok());
+        assert_eq!(fields(&batch(MAX_BATCH_REFERENCES + 1).unwrap_err()), ["references"]);
+    }
+}
@@ -1,11 +1,24 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use crate::db::models::FinalizedRequest;
-use crate::{db::{dbhandler::DatabaseHandler, models::{NewRequest, CandidateList}}, utils};
+use crate::{db::{dbhandler::DatabaseHandler, models::{NewRequest, NewJob, CandidateList, RequestState, JOB_CANCELLED}}, error::{ApiError, ApiResult, FieldError}, grpc::GrpcClient, utils};
+use self::{apikeys::ApiKeyIdentity, input::{Candidates, ComparisonMode, RequestInput, RequestParameters}, verifier::{Challenge, HumanVerifier}};
+use chrono::Utc;
+use futures::Stream;
 use anyhow::Result; 
 use serde::{Deserialize, Serialize};
 use tracing::{debug, instrument};
 
+pub mod admin;
+pub mod apikeys;
+pub mod auth;
+pub mod batch;
+pub mod input;
+pub mod results;
+pub mod status;
+pub mod tables;
+pub mod verifier;
+
 
 /*
 
@@ -13,6 +26,9 @@ This module is the middle layer between the REST API requests and the database.
 
 */
 
+// Interval between the progress checks of a request that is not running
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
 
 // Struct definitions that are used for structured responses (JSON)
 
@@ -20,13 +36,62 @@ This module is the middle layer between the REST API requests and the database.
 pub struct RequestResponse {
     status_code: i32,
     hash : String,
+    // Proof of access to the results of the request
+    access_token : String,
     request_id : i64
 }
 
 impl RequestResponse {
 
-    pub fn new(status : i32, hash_code: String, request_id: i64) -> Self { 
-        Self{ status_code: status, hash: hash_code, request_id: request_id}
+    pub fn new(status : i32, hash_code: String, access_token: String, request_id: i64) -> Self { 
+        Self{ status_code: status, hash: hash_code, access_token, request_id }
+    }
+}
+
+// Human verifier of the requests, along with a challenge for the built-in verifiers
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    status_code: i32,
+    verifier: String,
+    challenge: Option<Challenge>
+}
+
+impl ChallengeResponse {
+
+    pub fn new(status : i32, verifier: &str, challenge: Option<Challenge>) -> Self { 
+        Self{ status_code: status, verifier: verifier.to_string(), challenge }
+    }
+}
+
+// Status code of a cancellation: 0 cancelled, while unknown or completed requests are reported as errors
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelResponse {
+    status_code: i32
+}
+
+impl CancelResponse {
+
+    pub fn new(status : i32) -> Self { 
+        Self{ status_code: status }
+    }
+}
+
+// Progress of a request as sent to the browser
+// The stage is "unknown" or "queued" before the assignment, the stage reported by the node while the job
+// runs and "completed" once the job is finalized, along with its status code
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProgressUpdate {
+    stage: String,
+    percentage: i32,
+    log_lines: Vec<String>,
+    completed: bool,
+    status_code: i32
+}
+
+impl ProgressUpdate {
+
+    pub fn new(stage : &str, percentage: i32, log_lines: Vec<String>, completed: bool, status_code: i32) -> Self { 
+        Self{ stage: stage.to_string(), percentage, log_lines, completed, status_code }
     }
 }
 
@@ -63,225 +128,229 @@ impl RequestResult {
 // Functions that constitute the business logic of the web application
 
 
-// Handle the job request
-#[instrument(level="debug")]
-pub async fn create_request(db_handler: &DatabaseHandler, data : &serde_json::Value) -> Result<RequestResponse>{
-    let mut new_request: NewRequest = NewRequest { reference: "", custom_list: "", 
-                                                    uncached: "", candidates_list_id: &-1, 
-                                                    hash_value: "", go_term: "",  meta: false, 
-                                                    segment_start: &-1, segment_end: &-1, comparison_mode: &0,
-                                                    alignment_level: &-1 };
-    // Parse the user input
-    let candidate_list_id = utils::retrieve_json_int(&data, "candidateList") as i32;
-    let custom_id_input = utils::get_substring(&utils::retrieve_json_str(&data, "customList")
-                                    .to_string().trim().to_uppercase(), 5000).replace("-MODEL_V", "-model_v");
-    let reference_input = utils::get_substring(&utils::retrieve_json_str(&data, "reference")
-                                    .to_string().trim().to_uppercase(), 40).replace("-MODEL_V", "-model_v");
-    let captcha_token = utils::retrieve_json_str(&data, "token");
-    let mut uncached_string = String::from("");
-    let mut custom_list_string = "";
-    let comparison_mode = utils::retrieve_json_int(&data, "comparisonMode");
-    let alignment_level = utils::retrieve_json_int(&data, "alignmentLevel");
-    let mut segment_start = utils::retrieve_json_int(&data, "segmentStart");
-    let mut segment_end = utils::retrieve_json_int(&data, "segmentEnd");
-    let meta_analysis = utils::retrieve_json_bool(&data, "meta");
-    let mut reference_id = String::from("");
-    let ref_id_parts : Vec<String> = utils::check_composite_id(&reference_input)?; 
-    let mut status = 0;
-    let mut list_name_found = false;
-    let mut structure_ids: Vec<String> = Vec::new();
-    let mut hash_string = String::from("");
-    let mut request_id = -1;
-
-
-    let captcha_sucess = utils::verify_captcha_token(captcha_token.to_string()).await?;
-
-    if captcha_sucess == false{ 
-        status = -8;
-    }
-
-    //Check comparison mode option
-    if status == 0 && (comparison_mode < 0 || comparison_mode > 2) {
-        status = -9;
+// Check that a request was made by a person, unless it was authenticated by an API key
+async fn verify_submission(verifier: &dyn HumanVerifier, input : &RequestInput, api_key: Option<&ApiKeyIdentity>) -> ApiResult<()>{
+    if api_key.is_none() {
+        let token = input.token.clone().unwrap_or_default();
+        if !verifier.verify(&token).await? { 
+            return Err(ApiError::CaptchaFailed(String::from("Your request was not verified. Please try again.")));
+        }
     }
+    Ok(())
+}
 
-    //Check alignment level option
-    if status == 0 && (comparison_mode == 2 && (alignment_level < 0 || alignment_level > 3)) {
-        status = -10;
+// Check the existence of the preset candidate list of a request
+async fn check_candidate_list(db_handler: &DatabaseHandler, input : &RequestInput) -> Option<FieldError>{
+    let list_id = input.candidate_list.unwrap_or(-1);
+    if list_id > -1 && list_id <= i32::MAX as i64 && input.comparison_mode != Some(ComparisonMode::Segment as i64) {
+        let list_found = match DatabaseHandler::verify_candidate_list(db_handler, &(list_id as i32)).await{
+            Ok(entry) => { debug!("List id: {}", entry); entry >= 0 },
+            Err(_e) => false
+        };    
+        if !list_found { 
+            return Some(FieldError::new("candidateList", "The selected candidate list identifier is not present in the system."));
+        } 
     }
+    None
+}
 
-    //Process reference id
-    if status == 0 {
-        if ref_id_parts.len() > 0 && status == 0  {
-            reference_id = ref_id_parts.join("_");
-        } else { 
-            status = -1;    
-        }
-    }
-    
-    //Check if the request is older than 10 minutes from the previous one
-    if status == 0 {
-        let early_request = match DatabaseHandler::check_early_request(&db_handler).await{
-            Ok(amount) => amount > 0,
-            Err(e) => { debug!("Early request : {}", e.to_string()); false }
-        };   
-
-        if early_request == true { 
-            status = -2;
-        }
+// Create the SHA-256 hash of the canonical form of the request's parameters, which is stable across releases
+fn calculate_request_hash(parameters: &RequestParameters) -> ApiResult<String>{
+    let candidates_missing = match &parameters.candidates {
+        Candidates::CustomList(structure_ids) => structure_ids.is_empty(),
+        Candidates::PresetList(list_id) => *list_id <= 0
+    };
+    if candidates_missing {
+        return Err(ApiError::invalid_input("candidateList", "The selected candidate list identifier is not present in the system."));
     }
+    Ok(utils::compute_chunk_hash(parameters.canonical_form().as_bytes()))
+}
 
-    //Check if id of candidate list exists
-    if status == 0 && candidate_list_id > -1 {
-        if comparison_mode == 2 { 
-            status = -11;
-        }
-        else { 
-            list_name_found = match DatabaseHandler::verify_candidate_list(&db_handler, &(candidate_list_id as i32)).await{
-                Ok(entry) => { debug!("List id: {}", entry); entry >= 0 },
-                Err(_e) => false
-            };    
-
-            if list_name_found == false { 
-                status = -3;
-            } 
-            else {
-                new_request.candidates_list_id = &candidate_list_id;
-                // Check if the provided reference protein has not cached features
-                let reference_slice = vec![ref_id_parts[0].clone()];
-                let uncached_structure_ids = match DatabaseHandler::get_uncached_structure_ids(&db_handler, 
-                                                          &reference_slice).await {
-                    Ok(entries) => entries,
-                    Err(e) => {debug!("{}", e); Vec::<String>::new()}
-                };    
-
-                if uncached_structure_ids.len() > 0 {
-                    uncached_string = uncached_structure_ids.join(",");
-                }
-                
-            }  
-        }
-    }
+// Prepare a new request with its hash, the hash of its comparison without the meta-analysis and a random access token
+async fn prepare_request(db_handler: &DatabaseHandler, parameters: &RequestParameters, hash_string: &str) -> NewRequest{
+    // Check if the provided reference protein or any protein in the custom candidate list has not cached features
+    let reference_slice = vec![parameters.reference_structure.clone()];
+    let (candidates_list_id, custom_list, lookup_ids) = match &parameters.candidates {
+        Candidates::PresetList(list_id) => (*list_id, String::from(""), reference_slice),
+        Candidates::CustomList(structure_ids) => (-1, structure_ids.join(","), [&structure_ids[..], &reference_slice].concat())
+    };
+    let uncached_structure_ids = match DatabaseHandler::get_uncached_structure_ids(db_handler, &lookup_ids).await {
+        Ok(entries) => entries,
+        Err(e) => {debug!("{}", e); Vec::<String>::new()}
+    };    
+    let uncached_string = uncached_structure_ids.join(",");
+
+    // The token grants access to the results of the request
+    let access_token = auth::generate_token();
+    let base_hash = utils::compute_chunk_hash(parameters.base_canonical_form().as_bytes());
+    let comparison_mode = parameters.comparison_mode as i16;
+    let stored_alignment_level = match parameters.alignment_level {
+        Some(level) if parameters.comparison_mode == ComparisonMode::Segment => level as i16,
+        _ => -1
+    };
+    NewRequest { reference: parameters.reference.clone(), custom_list, uncached: uncached_string, 
+                                   candidates_list_id, hash_value: hash_string.to_string(), go_term: parameters.go_term.clone(), 
+                                   meta: parameters.meta, segment_start: parameters.segment_start, segment_end: parameters.segment_end, 
+                                   comparison_mode, alignment_level: stored_alignment_level,
+                                   access_token, base_hash }
+}
 
-    // Process the custom provided candidate list 
-    let custom_ids:Vec<String> = custom_id_input.split(",").into_iter()
-                                .map(|s| s.to_string())
-                                .collect();
-    if status == 0 && list_name_found == false {
-        if custom_ids.len() > 0  {   
-            for id in &custom_ids {
-
-                if id.len() > 0 {
-                    if structure_ids.contains(id) == false {
-                        structure_ids.push(id.clone());
-                    }
-                }
-                else{
-                    status = -4; 
-                    break;     
-                }
-            } 
-
-            if structure_ids.len() > 0 && status == 0 { 
-                // Check if the provided reference protein or any protein in the candidate list has not cached features
-                let reference_slice = vec![ref_id_parts[0].clone()];
-                let uncached_structure_ids = match DatabaseHandler::get_uncached_structure_ids(&db_handler, 
-                                                          &[&structure_ids[..], &reference_slice].concat()).await {
-                    Ok(entries) => entries,
-                    Err(e) => {debug!("{}", e); Vec::<String>::new()}
-                };    
-
-                if uncached_structure_ids.len() > 0 {
-                    uncached_string = uncached_structure_ids.join(",");
-                }
+// Store new requests and return their ids along with their access tokens. The requests of an API key are stored along
+// with their record in its history, as long as they do not exceed its daily quota.
+async fn store_requests(db_handler: &DatabaseHandler, new_requests: Vec<NewRequest>, api_key: Option<&ApiKeyIdentity>) -> ApiResult<Vec<(i64, String)>>{
+    let access_tokens: Vec<String> = new_requests.iter().map(|new_request| new_request.access_token.clone()).collect();
+    let request_ids = match api_key {
+        Some(identity) => db_handler.insert_keyed_requests(&identity.id, &identity.daily_quota, new_requests).await?,
+        None => {
+            let mut request_ids = Vec::new();
+            for new_request in new_requests {
+                let access_token = new_request.access_token.clone();
+                DatabaseHandler::insert_request(db_handler, new_request).await?;
+                request_ids.push(DatabaseHandler::get_request_by_access_token(db_handler, &access_token).await?.unwrap_or(-1));
             }
-
-        } else {
-            status = -5;    
-        } 
-
-        if status == 0 && structure_ids.len() == 0 { 
-            status = -6;
+            request_ids
         }
-    }
+    };
+    Ok(request_ids.into_iter().zip(access_tokens).collect())
+}
 
-    // Check residue range for Segment comparison mode
-    if comparison_mode == 2{
-        if  segment_start > segment_end || 
-            segment_end > 10000 || 
-            segment_start > 10000 || 
-            segment_end < 1 ||
-            segment_start < 1 ||
-            segment_end - segment_start > 600  ||
-            segment_end - segment_start < 2
-        { 
-            status = -7;
-        }
-    }
-    else
-    {
-        segment_start = -1;
-        segment_end = -1;
+// Handle the job request, which is verified either by the human verifier or by an API key
+#[instrument(level="debug")]
+pub async fn create_request(db_handler: &DatabaseHandler, verifier: &dyn HumanVerifier, input : &RequestInput, 
+                            api_key: Option<&ApiKeyIdentity>) -> ApiResult<RequestResponse>{
+    verify_submission(verifier, input, api_key).await?;
+
+    // Check every field of the request, including the existence of the preset candidate list
+    let mut errors = Vec::new();
+    let parameters = match input.validate() {
+        Ok(parameters) => Some(parameters),
+        Err(field_errors) => { errors = field_errors; None }
+    };
+    errors.extend(check_candidate_list(db_handler, input).await);
+    let parameters = match parameters {
+        Some(parameters) if errors.is_empty() => parameters,
+        _ => return Err(ApiError::Validation(errors))
+    };
+
+    // The requests of an API key are also limited by its daily quota
+    let hash_string = calculate_request_hash(&parameters)?;
+    let new_request = prepare_request(db_handler, &parameters, &hash_string).await;
+    let (request_id, access_token) = store_requests(db_handler, vec![new_request], api_key).await?.remove(0);
+
+    Ok(RequestResponse{status_code : 0, hash: hash_string, access_token, request_id})
+}
+
+// Handle a job request of the unversioned endpoint, which reports only the first invalid field as its earlier clients expect
+#[instrument(level="debug")]
+pub async fn create_legacy_request(db_handler: &DatabaseHandler, verifier: &dyn HumanVerifier, data: &serde_json::Value,
+                                   api_key: Option<&ApiKeyIdentity>) -> ApiResult<RequestResponse>{
+    match create_request(db_handler, verifier, &RequestInput::from_legacy(data), api_key).await {
+        Err(ApiError::Validation(errors)) if !errors.is_empty() => Err(ApiError::invalid_input(errors[0].field(), errors[0].message())),
+        result => result
     }
+}
 
-    // Prepare to store the new request in database
-    let structure_ids_string = structure_ids.join(",");
-    if status == 0 { 
-        new_request.reference = reference_id.as_str();
+// Retrieve the human verifier and a challenge, if the verifier has its own challenges
+#[instrument(level="debug")]
+pub async fn get_challenge(verifier: &dyn HumanVerifier) -> ApiResult<ChallengeResponse>{
+    let challenge = verifier.issue_challenge().map_err(|e| {
+        debug!("Challenge: {}", e);
+        ApiError::Unavailable(String::from("No challenge can be issued at the moment. Please try again later."))
+    })?;
+    Ok(ChallengeResponse::new(0, verifier.name(), challenge))
+}
 
-        if uncached_string.len() > 0 { 
-            new_request.uncached = uncached_string.as_str();
-        }
-        if structure_ids.len() > 0 { 
-            custom_list_string = &structure_ids_string;
-            new_request.custom_list = custom_list_string;
-        }
+// Cancel a request that is queued or running, given the proof of its ownership
+#[instrument(level="debug")]
+pub async fn cancel_request(db_handler: &DatabaseHandler, proof: &str, request_id: &i64, protocol: &str) -> ApiResult<CancelResponse>{
+    let hash = verify_request(db_handler, proof, request_id).await?;
+
+    let state = DatabaseHandler::get_request_state(db_handler, request_id).await?;
+    match (state, DatabaseHandler::get_latest_job(db_handler, request_id).await?) {
+        // Queued request: a cancelled job records the outcome of the request
+        (Some(RequestState::Queued), _) => {
+            let retries = DatabaseHandler::count_requeued_jobs(db_handler, request_id).await? as i32;
+            let new_job = NewJob{ request_id: *request_id, node_id: -1, status_code: JOB_CANCELLED, 
+                                  completion_date: Some(Utc::now().naive_utc()), secure_hash: String::new(), retries };
+            DatabaseHandler::insert_job(db_handler, new_job, RequestState::Cancelled, "cancelled by the owner").await?;
+        },
+        // Running job: stop it on its node and free the node
+        (Some(state), Some(job)) if state.is_active() => {
+            if let Some(node) = DatabaseHandler::get_node(db_handler, &job.node_id).await? {
+                let client = GrpcClient::new([protocol, node.ip.as_str()].join(""), node.domain.as_str())?;
+                match client.cancel_job(*request_id, &hash).await {
+                    Ok(status) => debug!("Cancellation on node {}: {}", node.id, status),
+                    Err(e) => debug!("{}", e)
+                };
+            }
+            DatabaseHandler::finalize_job(db_handler, request_id, &job.id, "", &JOB_CANCELLED, RequestState::Cancelled,
+                                          "cancelled by the owner").await?;
+            DatabaseHandler::refresh_node_working_state(db_handler, &job.node_id).await?;
+        },
+        _ => return Err(ApiError::Conflict(String::from("The request is already completed.")))
+    }
 
-        // Create hash of the request by its object
-        let mut candidates = "";
-        let list_id = candidate_list_id.to_string();
-        if custom_list_string.len() > 0 { 
-            candidates = custom_list_string;  
-        }
-        else if candidate_list_id > 0 { 
-            candidates = &list_id; 
-        }
+    Ok(CancelResponse::new(0))
+}
 
-        if candidates.len() > 0 { 
-            hash_string = [reference_id.as_str(), candidates, &comparison_mode.to_string(), 
-                           &segment_start.to_string(), &segment_end.to_string(), &alignment_level.to_string()].join("\n");
-        }
+// Check the proof of ownership of a request and retrieve the hash of the request
+pub async fn verify_request(db_handler: &DatabaseHandler, proof: &str, request_id: &i64) -> ApiResult<String>{
+    match db_handler.verify_request_proof(request_id, proof).await? {
+        Some(hash) => Ok(hash),
+        None => Err(ApiError::NotFound(String::from("There is no such request.")))
+    }
+}
 
-        if hash_string.len() > 1 { 
-            hash_string = utils::calculate_hash(&hash_string);
-            new_request.hash_value = hash_string.as_str();
-        
-            let mode: i8 = comparison_mode as i8;
-            let residue_start: i32 = segment_start as i32;
-            let residue_end: i32 = segment_end as i32;
-            new_request.meta = meta_analysis;
-            new_request.segment_start = &residue_start;
-            new_request.segment_end = &residue_end;
-            new_request.comparison_mode = &mode;
-            
-            DatabaseHandler::insert_request(&db_handler, &new_request).await?;
-            request_id = match DatabaseHandler::get_request_by_hash(&db_handler, &hash_string).await?{
-                Some(req_id) => req_id,
-                None => -1
+// Follow the progress of a request until its job is finalized, given the proof of its ownership
+pub fn watch_request(db_handler: DatabaseHandler, proof: String, request_id: i64, protocol: String) -> impl Stream<Item = Result<ProgressUpdate>> {
+    async_stream::try_stream! {
+        let hash = match db_handler.verify_request_proof(&request_id, &proof).await? {
+            Some(hash) => hash,
+            None => {
+                yield ProgressUpdate::new("unknown", 0, Vec::new(), true, -1);
+                return;
             }
+        };
+        loop {
+            let state = db_handler.get_request_state(&request_id).await?.unwrap_or(RequestState::Queued);
+            match db_handler.get_latest_job(&request_id).await? {
+                _ if state == RequestState::Queued => { yield ProgressUpdate::new("queued", 0, Vec::new(), false, 0); },
+                Some(job) if state.is_active() => {
+                    // Relay the progress that the node reports and keep the last one on the job
+                    let mut progress = match db_handler.get_node(&job.node_id).await? {
+                        Some(node) => {
+                            let client = GrpcClient::new([protocol.as_str(), node.ip.as_str()].join(""), node.domain.as_str())?;
+                            client.watch_job(request_id, &hash).await.map_err(|e| debug!("{}", e)).ok()
+                        },
+                        None => None
+                    };
+                    match progress.as_mut() {
+                        Some(stream) => {
+                            while let Some(message) = stream.message().await? {
+                                let percentage = message.percentage.clamp(0, 100);
+                                db_handler.update_job_progress(&job.id, &message.stage, &(percentage as i16)).await?;
+                                yield ProgressUpdate::new(&message.stage, percentage, message.log_lines, false, 0);
+                            }
+                        },
+                        None => { yield ProgressUpdate::new(&job.stage, i32::from(job.progress), Vec::new(), false, 0); }
+                    }
+                },
+                job if state.is_final() => {
+                    let status_code = job.map_or(-1, |job| i32::from(job.status_code));
+                    yield ProgressUpdate::new("completed", 100, Vec::new(), true, status_code);
+                    return;
+                },
+                _ => {}
+            }
+            // Wait for the assignment or the finalization of the job
+            tokio::time::sleep(PROGRESS_INTERVAL).await;
         }
-        else
-        {
-            status = -11;
-        }
-    }  
-
-    Ok(RequestResponse{status_code : status, hash: hash_string, request_id: request_id})
+    }
 }
 
 // Retrieve information of the MachaonWeb network's current status
 #[instrument(level="debug")]
-pub async fn retrieve_info(db_handler: &DatabaseHandler) -> Result<Info>{
+pub async fn retrieve_info(db_handler: &DatabaseHandler) -> ApiResult<Info>{
  
     let nodes: i64 = match DatabaseHandler::get_active_node_count(&db_handler).await?{ 
         Some(count) => count,
@@ -302,33 +371,28 @@ pub async fn retrieve_info(db_handler: &DatabaseHandler) -> Result<Info>{
 
 // Retrieve candidate lists
 #[instrument(level="debug")]
-pub async fn get_candidate_lists(db_handler: &DatabaseHandler) -> Result<Vec<CandidateList>>{
-    let lists = match DatabaseHandler::get_candidate_lists(&db_handler).await {
-        Ok(entries) => entries,
-        Err(e) => {debug!("{}", e); Vec::<CandidateList>::new()}
-    };    
+pub async fn get_candidate_lists(db_handler: &DatabaseHandler) -> ApiResult<Vec<CandidateList>>{
+    let lists = DatabaseHandler::get_candidate_lists(db_handler).await?;
 
     Ok(lists)
 }
 
 // Retrieve the result of a request
 #[instrument(level="debug")]
-pub async fn get_request_result(db_handler: &DatabaseHandler, hash: &str, request_id: &i64, root_path: &str) -> Result<RequestResult>{
+pub async fn get_request_result(db_handler: &DatabaseHandler, proof: &str, request_id: &i64, root_path: &str) -> ApiResult<RequestResult>{
  
-    let request_row = match DatabaseHandler::get_request_with_proof(&db_handler, &request_id, &hash).await?{ 
+    // Requests without a finalized job are not available yet
+    let request_row = match DatabaseHandler::get_request_with_proof(db_handler, request_id, proof).await?{ 
         Some(row) => row,
-        None => FinalizedRequest::construct() 
+        None => return Err(ApiError::NotFound(String::from("This request has not been processed yet or it does not exist.")))
     };  
-    let mut output_files = HashMap::new();
-
-    if request_row.id > 0 {
-        // Get the html filenames for quickview
-        output_files = utils::get_html_filenames(&request_row.hash_value, &request_row.meta, 
-                                                                        &request_row.go_term,
-                                                                        root_path)?;
-        // Update the view count of the request                                                                        
-        DatabaseHandler::update_view_count(&db_handler, &request_id).await?;
-    }
+
+    // Get the html filenames for quickview
+    let output_files = utils::get_html_filenames(&request_row.hash_value, &request_row.meta, 
+                                                                    &request_row.go_term,
+                                                                    root_path)?;
+    // Update the view count of the request                                                                        
+    DatabaseHandler::update_view_count(db_handler, request_id).await?;
     Ok(RequestResult {request: request_row, files: output_files})
     
 }
\ No newline at end of file
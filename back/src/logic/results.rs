@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use crate::{db::dbhandler::DatabaseHandler, error::{ApiError, ApiResult}, utils};
+
+
+/*
+
+This module serves the outputs of the finished requests: the complete archive of Machaon's outputs, as it was extracted
+from the result of the job into OUTPUT_PATH/<hash>/<hash>.zip, and the individual CSV, HTML and PDB files in it.
+The versions of the files are identified by the SHA-256 hash of the job's result (secure_hash).
+
+*/
+
+// Files that can be downloaded individually and their content types
+const RESULT_FILE_TYPES: [(&str, &str); 3] = [("csv", "text/csv; charset=utf-8"),
+                                              ("html", "text/html; charset=utf-8"),
+                                              ("pdb", "chemical/x-pdb")];
+const ARCHIVE_CONTENT_TYPE: &str = "application/zip";
+
+// Content of a downloadable file: a file on disk or a file that was read from the archive
+#[derive(Debug)]
+pub enum ResultContent {
+    File(PathBuf),
+    Memory(Vec<u8>),
+}
+
+// Downloadable file of the results of a request
+#[derive(Debug)]
+pub struct ResultFile {
+    pub file_name: String,
+    pub content_type: &'static str,
+    pub etag: String,
+    pub length: u64,
+    pub content: ResultContent,
+}
+
+// File of the archive that can be downloaded individually
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResultEntry {
+    path: String,
+    size: u64,
+    content_type: String,
+}
+
+// Downloadable files of the results of a request (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResultFilesResponse {
+    status_code: i32,
+    archive_size: u64,
+    files: Vec<ResultEntry>,
+}
+
+// Content type of a file that can be downloaded individually, according to its extension
+fn result_file_type(file_path: &str) -> Option<&'static str> {
+    let extension = Path::new(file_path).extension()?.to_str()?.to_lowercase();
+    RESULT_FILE_TYPES.iter().find(|(file_type, _)| *file_type == extension).map(|(_, content_type)| *content_type)
+}
+
+// Locate the archive of the outputs of a finished request and the hash of its job's result, given the proof of its ownership
+pub async fn locate_archive(db_handler: &DatabaseHandler, proof: &str, request_id: &i64, output_path: &str) -> ApiResult<(PathBuf, String)> {
+    let not_available = || ApiError::NotFound(String::from("The results of this request are not available."));
+    let request = match db_handler.get_request_with_proof(request_id, proof).await? {
+        Some(request) if request.status_code == 0 && !request.secure_hash.is_empty() => request,
+        _ => return Err(not_available())
+    };
+    let archive_path = Path::new(output_path).join(&request.hash_value).join([&request.hash_value, ".zip"].join(""));
+    if !archive_path.is_file() {
+        return Err(not_available());
+    }
+    Ok((archive_path, request.secure_hash))
+}
+
+// Retrieve the complete archive of the outputs of a request
+#[instrument(level="debug")]
+pub async fn get_result_archive(db_handler: &DatabaseHandler, proof: &str, request_id: &i64, output_path: &str) -> ApiResult<ResultFile>{
+    let (archive_path, secure_hash) = locate_archive(db_handler, proof, request_id, output_path).await?;
+    let length = tokio::fs::metadata(&archive_path).await.map_err(anyhow::Error::from)?.len();
+    let file_name = archive_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    Ok(ResultFile { file_name, content_type: ARCHIVE_CONTENT_TYPE, etag: format!("\"{}\"", secure_hash), length,
+                    content: ResultContent::File(archive_path) })
+}
+
+// Retrieve a CSV, HTML or PDB file of the outputs of a request by its path in the archive
+#[instrument(level="debug")]
+pub async fn get_result_file(db_handler: &DatabaseHandler, proof: &str, request_id: &i64, file_path: &str, 
+                             output_path: &str) -> ApiResult<ResultFile>{
+    let not_found = || ApiError::NotFound(String::from("There is no such file in the results of this request."));
+    let content_type = result_file_type(file_path).ok_or_else(not_found)?;
+    let (archive_path, secure_hash) = locate_archive(db_handler, proof, request_id, output_path).await?;
+    let entry_path = file_path.to_string();
+    let content = tokio::task::spawn_blocking(move || utils::read_archive_file(&archive_path, &entry_path))
+                    .await.map_err(anyhow::Error::from)??
+                    .ok_or_else(not_found)?;
+    let file_name = Path::new(file_path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    // Each file of the same result has its own version
+    let etag = format!("\"{}-{}\"", secure_hash, &utils::compute_chunk_hash(file_path.as_bytes())[..16]);
+    Ok(ResultFile { file_name, content_type, etag, length: content.len() as u64, content: ResultContent::Memory(content) })
+}
+
+// List the files of the outputs of a request that can be downloaded individually
+#[instrument(level="debug")]
+pub async fn list_result_files(db_handler: &DatabaseHandler, proof: &str, request_id: &i64, output_path: &str) -> ApiResult<ResultFilesResponse>{
+    let (archive_path, _) = locate_archive(db_handler, proof, request_id, output_path).await?;
+    let archive_size = tokio::fs::metadata(&archive_path).await.map_err(anyhow::Error::from)?.len();
+    let entries = tokio::task::spawn_blocking(move || utils::list_archive_files(&archive_path))
+                    .await.map_err(anyhow::Error::from)??;
+    let files = entries.into_iter()
+                       .filter_map(|(path, size)| result_file_type(&path)
+                                   .map(|content_type| ResultEntry { path, size, content_type: content_type.to_string() }))
+                       .collect();
+    Ok(ResultFilesResponse { status_code: 0, archive_size, files })
+}
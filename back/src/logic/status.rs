@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use crate::{db::{dbhandler::DatabaseHandler, models::RequestState}, error::{ApiError, ApiResult}};
+
+
+/*
+
+This module reports the status of a request before and after its result exists: its position in the queue, the node
+that runs its job or the outcome of its job, along with an estimate of the remaining time. The estimates are derived
+from the durations of the latest completed jobs with the same comparison mode and candidate list, or with the same
+comparison mode when there are none.
+
+*/
+
+// Number of the latest completed jobs that the estimates are derived from
+const DURATION_SAMPLES: i64 = 50;
+
+// Status of a request (status code 0). The fields that do not apply to the state of the request are null.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RequestStatus {
+    status_code: i32,
+    request_id: i64,
+    state: String,
+    // Position in the queue, 1 for the next request to be assigned
+    queue_position: Option<i64>,
+    node_id: Option<i16>,
+    assignment_date: Option<chrono::NaiveDateTime>,
+    stage: Option<String>,
+    progress: Option<i16>,
+    completion_date: Option<chrono::NaiveDateTime>,
+    // Status code of the finalized job
+    job_status_code: Option<i32>,
+    // Estimated seconds until the request is completed
+    estimated_seconds: Option<i64>,
+}
+
+fn average(durations: &[i64]) -> Option<i64> {
+    match durations.len() {
+        0 => None,
+        count => Some(durations.iter().sum::<i64>() / count as i64)
+    }
+}
+
+// Typical duration of a job of a comparison mode and a candidate list
+async fn estimate_duration(db_handler: &DatabaseHandler, comparison_mode: i16, list_id: i32) -> ApiResult<Option<i64>> {
+    let durations = db_handler.get_job_durations(Some(comparison_mode), Some(list_id), DURATION_SAMPLES).await?;
+    if !durations.is_empty() {
+        return Ok(average(&durations));
+    }
+    Ok(average(&db_handler.get_job_durations(Some(comparison_mode), None, DURATION_SAMPLES).await?))
+}
+
+// Retrieve the status of a request, given the proof of its ownership. The queued requests wait for the jobs that are
+// running and the requests ahead of them, which are served by the job slots of the active nodes (CORES_PER_JOB cores each)
+// in rounds that last as long as an average job.
+#[instrument(level="debug")]
+pub async fn get_request_status(db_handler: &DatabaseHandler, proof: &str, request_id: &i64, cores_per_job: i64) -> ApiResult<RequestStatus>{
+    super::verify_request(db_handler, proof, request_id).await?;
+    let (state, comparison_mode, list_id) = match db_handler.get_request_settings(request_id).await? {
+        Some(settings) => settings,
+        None => return Err(ApiError::NotFound(String::from("There is no such request.")))
+    };
+    let mut status = RequestStatus { status_code: 0, request_id: *request_id, state: state.to_string(), ..Default::default() };
+
+    if state == RequestState::Queued {
+        let position = db_handler.get_queue_position(request_id).await?;
+        let slots: i64 = db_handler.get_active_nodes().await?.iter()
+                                   .map(|node| (i64::from(node.cores) / cores_per_job.max(1)).max(1))
+                                   .sum();
+        let running_jobs = db_handler.get_running_jobs_count().await?.unwrap_or(0);
+        let round_duration = average(&db_handler.get_job_durations(None, None, DURATION_SAMPLES).await?);
+        status.estimated_seconds = match (estimate_duration(db_handler, comparison_mode, list_id).await?, round_duration) {
+            (Some(duration), Some(round_duration)) if slots > 0 => Some((running_jobs + position - 1) / slots * round_duration + duration),
+            _ => None
+        };
+        status.queue_position = Some(position);
+        return Ok(status);
+    }
+
+    let job = match db_handler.get_latest_job(request_id).await? {
+        Some(job) => job,
+        None => return Ok(status)
+    };
+    if state.is_active() {
+        let elapsed_seconds = db_handler.get_job_elapsed_seconds(&job.id).await?.unwrap_or(0);
+        status.estimated_seconds = match state {
+            RequestState::Assigned | RequestState::Running => estimate_duration(db_handler, comparison_mode, list_id).await?
+                                                                  .map(|duration| (duration - elapsed_seconds).max(0)),
+            // The result is being retrieved from the node
+            _ => Some(0)
+        };
+        status.node_id = Some(job.node_id);
+        status.assignment_date = Some(job.assignment_date);
+        status.stage = Some(job.stage);
+        status.progress = Some(job.progress);
+    }
+    else {
+        status.node_id = Some(job.node_id).filter(|node_id| *node_id != -1);
+        status.completion_date = job.completion_date;
+        status.job_status_code = Some(i32::from(job.status_code));
+    }
+    Ok(status)
+}
@@ -0,0 +1,311 @@
+use std::{cmp::Ordering, path::Path};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::instrument;
+use crate::{db::dbhandler::DatabaseHandler, error::{ApiError, ApiResult}, utils};
+use super::results;
+
+
+/*
+
+This module exposes the tabular outputs of Machaon (the CSV files of the result archive, such as the ranked candidates
+with their scores and alignment metrics) as JSON. The rows of a table are paginated, sorted by any column and filtered
+by conditions on the columns, where the numeric values are compared as numbers.
+
+*/
+
+// Rows per page, by default and at most
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+// Tables of the reports that are presented in the result page, by the suffix of their names (as in get_html_filenames)
+const TABLE_LABELS: [(&str, &str); 4] = [("-merged-notenriched", "cluster"),
+                                         ("-merged-enriched_eval", "top"),
+                                         ("-merged-h-enriched", "topHuman"),
+                                         ("-pres", "goTerm")];
+
+// Table of the result archive
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableInfo {
+    path: String,
+    label: Option<String>,
+    columns: Vec<String>,
+    row_count: usize,
+}
+
+// Tables of the outputs of a request (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TablesResponse {
+    status_code: i32,
+    tables: Vec<TableInfo>,
+}
+
+// Page of the rows of a table (status code 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TablePage {
+    status_code: i32,
+    path: String,
+    columns: Vec<String>,
+    // Rows that match the filters
+    total_rows: usize,
+    page: usize,
+    per_page: usize,
+    total_pages: usize,
+    rows: Vec<Map<String, Value>>,
+}
+
+// Query of the rows of a table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableQuery {
+    // Page number, starting from 1
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    // Column to sort by, in ascending (asc) or descending (desc) order
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    // Comma-separated conditions of the form <column>:<operator>:<value>,
+    // where the operator is eq, ne, contains, gt, ge, lt or le
+    pub filter: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilterOperator {
+    Equal,
+    NotEqual,
+    Contains,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+// Condition on a column of a table
+#[derive(Debug, Clone)]
+struct Filter {
+    column: usize,
+    operator: FilterOperator,
+    value: String,
+}
+
+// Parsed table: the column names and the values of each row, which are numbers, strings or null (empty cells)
+#[derive(Debug, Default)]
+struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+// Label of a table that is presented in the result page
+fn table_label(path: &str) -> Option<String> {
+    let stem = Path::new(path).file_stem()?.to_str()?;
+    TABLE_LABELS.iter().find(|(suffix, _)| stem.ends_with(suffix)).map(|(_, label)| label.to_string())
+}
+
+// Convert a cell to a JSON number, if it is numeric, or to a string
+fn parse_cell(cell: &str) -> Value {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        return Value::Null;
+    }
+    if let Ok(number) = cell.parse::<i64>() {
+        return Value::from(number);
+    }
+    match cell.parse::<f64>() {
+        Ok(number) if number.is_finite() => Value::from(number),
+        _ => Value::from(cell)
+    }
+}
+
+// Parse a CSV or TSV table, whose delimiter is detected from its header
+fn parse_table(content: &[u8]) -> Result<Table> {
+    let header = content.split(|byte| *byte == b'\n').next().unwrap_or_default();
+    let tabs = header.iter().filter(|byte| **byte == b'\t').count();
+    let commas = header.iter().filter(|byte| **byte == b',').count();
+    let mut reader = csv::ReaderBuilder::new()
+                         .delimiter(if tabs > commas { b'\t' } else { b',' })
+                         .flexible(true)
+                         .from_reader(content);
+    let columns: Vec<String> = reader.headers()?.iter().map(|column| column.trim().to_string()).collect();
+    if columns.is_empty() {
+        bail!("The table has no columns");
+    }
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let mut row: Vec<Value> = record.iter().take(columns.len()).map(parse_cell).collect();
+        row.resize(columns.len(), Value::Null);
+        rows.push(row);
+    }
+    Ok(Table { columns, rows })
+}
+
+// Compare two cells: numbers as numbers and the rest as text, with the empty cells last
+fn compare_cells(first: &Value, second: &Value) -> Ordering {
+    match (first, second) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        (Value::Number(first), Value::Number(second)) => first.as_f64().partial_cmp(&second.as_f64()).unwrap_or(Ordering::Equal),
+        (Value::Number(_), _) => Ordering::Less,
+        (_, Value::Number(_)) => Ordering::Greater,
+        _ => first.as_str().cmp(&second.as_str())
+    }
+}
+
+// Text of a cell as it is compared by the filters
+fn cell_text(cell: &Value) -> String {
+    match cell {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        cell => cell.to_string()
+    }
+}
+
+impl Filter {
+
+    // Parse the comma-separated conditions of a query against the columns of a table
+    fn parse_all(filters: &str, columns: &[String]) -> ApiResult<Vec<Self>> {
+        let mut parsed = Vec::new();
+        for condition in filters.split(',').map(|condition| condition.trim()).filter(|condition| !condition.is_empty()) {
+            let invalid = || ApiError::invalid_input("filter", &format!("Invalid filter: {}", condition));
+            let mut parts = condition.splitn(3, ':');
+            let (column, operator, value) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(column), Some(operator), Some(value)) => (column, operator, value),
+                _ => return Err(invalid())
+            };
+            let column = columns.iter().position(|name| name == column)
+                                .ok_or_else(|| ApiError::invalid_input("filter", &format!("Unknown column: {}", column)))?;
+            let operator = match operator {
+                "eq" => FilterOperator::Equal,
+                "ne" => FilterOperator::NotEqual,
+                "contains" => FilterOperator::Contains,
+                "gt" => FilterOperator::Greater,
+                "ge" => FilterOperator::GreaterOrEqual,
+                "lt" => FilterOperator::Less,
+                "le" => FilterOperator::LessOrEqual,
+                _ => return Err(invalid())
+            };
+            parsed.push(Filter { column, operator, value: value.to_string() });
+        }
+        Ok(parsed)
+    }
+
+    // Check whether a row satisfies the condition. The order comparisons only match comparable cells.
+    fn matches(&self, row: &[Value]) -> bool {
+        let cell = &row[self.column];
+        let value = parse_cell(&self.value);
+        // Numbers are equal by value (e.g. 1 and 1.0), text regardless of case
+        let equal = || cell_text(cell).eq_ignore_ascii_case(&self.value) || (cell.is_number() && compare_cells(cell, &value) == Ordering::Equal);
+        match self.operator {
+            FilterOperator::Equal => equal(),
+            FilterOperator::NotEqual => !equal(),
+            FilterOperator::Contains => cell_text(cell).to_lowercase().contains(&self.value.to_lowercase()),
+            operator => {
+                if cell.is_null() || cell.is_number() != value.is_number() {
+                    return false;
+                }
+                let ordering = compare_cells(cell, &value);
+                match operator {
+                    FilterOperator::Greater => ordering == Ordering::Greater,
+                    FilterOperator::GreaterOrEqual => ordering != Ordering::Less,
+                    FilterOperator::Less => ordering == Ordering::Less,
+                    _ => ordering != Ordering::Greater
+                }
+            }
+        }
+    }
+}
+
+impl Table {
+
+    // Filter, sort and paginate the rows of the table
+    fn query(self, path: &str, query: &TableQuery) -> ApiResult<TablePage> {
+        let filters = Filter::parse_all(query.filter.as_deref().unwrap_or_default(), &self.columns)?;
+        let sort_column = match &query.sort {
+            Some(sort) => Some(self.columns.iter().position(|name| name == sort)
+                                   .ok_or_else(|| ApiError::invalid_input("sort", &format!("Unknown column: {}", sort)))?),
+            None => None
+        };
+        let descending = match query.order.as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(_) => return Err(ApiError::invalid_input("order", "The order must be asc or desc."))
+        };
+        let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&per_page) {
+            return Err(ApiError::invalid_input("perPage", &format!("The page size must be between 1 and {}.", MAX_PAGE_SIZE)));
+        }
+        let page = query.page.unwrap_or(1);
+        if page == 0 {
+            return Err(ApiError::invalid_input("page", "The pages start from 1."));
+        }
+
+        let mut rows: Vec<Vec<Value>> = self.rows.into_iter().filter(|row| filters.iter().all(|filter| filter.matches(row))).collect();
+        // The sorting is stable, so the rows keep the ranking of Machaon among equal values.
+        // Numbers come before text and empty cells last in both orders.
+        if let Some(column) = sort_column {
+            rows.sort_by(|first, second| {
+                let (first, second) = (&first[column], &second[column]);
+                let ordering = compare_cells(first, second);
+                let comparable = !first.is_null() && !second.is_null() && first.is_number() == second.is_number();
+                if descending && comparable { ordering.reverse() } else { ordering }
+            });
+        }
+        let total_rows = rows.len();
+        let total_pages = if total_rows == 0 { 0 } else { (total_rows - 1) / per_page + 1 };
+        let rows = rows.into_iter()
+                       .skip((page - 1).saturating_mul(per_page))
+                       .take(per_page)
+                       .map(|row| self.columns.iter().cloned().zip(row).collect::<Map<String, Value>>())
+                       .collect();
+        Ok(TablePage { status_code: 0, path: path.to_string(), columns: self.columns, total_rows, page, per_page,
+                       total_pages, rows })
+    }
+}
+
+// Read and parse a table of a result archive
+async fn read_table(archive_path: &Path, table_path: &str) -> ApiResult<Option<Table>> {
+    let (archive_path, table_path) = (archive_path.to_path_buf(), table_path.to_string());
+    let table = tokio::task::spawn_blocking(move || -> Result<Option<Table>> {
+        match utils::read_archive_file(&archive_path, &table_path)? {
+            Some(content) => Ok(Some(parse_table(&content)?)),
+            None => Ok(None)
+        }
+    }).await.map_err(anyhow::Error::from)??;
+    Ok(table)
+}
+
+// Check that a path of the archive is a table
+fn is_table(path: &str) -> bool {
+    matches!(Path::new(path).extension().and_then(|extension| extension.to_str()), Some(extension) if extension.eq_ignore_ascii_case("csv"))
+}
+
+// List the tables of the outputs of a request along with their columns
+#[instrument(level="debug")]
+pub async fn list_tables(db_handler: &DatabaseHandler, proof: &str, request_id: &i64, output_path: &str) -> ApiResult<TablesResponse>{
+    let (archive_path, _) = results::locate_archive(db_handler, proof, request_id, output_path).await?;
+    let listed_path = archive_path.clone();
+    let entries = tokio::task::spawn_blocking(move || utils::list_archive_files(&listed_path))
+                    .await.map_err(anyhow::Error::from)??;
+    let mut tables = Vec::new();
+    for (path, _) in entries.into_iter().filter(|(path, _)| is_table(path)) {
+        // Files that are not valid tables are left out
+        if let Ok(Some(table)) = read_table(&archive_path, &path).await {
+            tables.push(TableInfo { label: table_label(&path), path, columns: table.columns, row_count: table.rows.len() });
+        }
+    }
+    Ok(TablesResponse { status_code: 0, tables })
+}
+
+// Retrieve a page of the filtered and sorted rows of a table of the outputs of a request
+#[instrument(level="debug")]
+pub async fn query_table(db_handler: &DatabaseHandler, proof: &str, request_id: &i64, table_path: &str, 
+                         query: &TableQuery, output_path: &str) -> ApiResult<TablePage>{
+    let not_found = || ApiError::NotFound(String::from("There is no such table in the results of this request."));
+    if !is_table(table_path) {
+        return Err(not_found());
+    }
+    let (archive_path, _) = results::locate_archive(db_handler, proof, request_id, output_path).await?;
+    let table = read_table(&archive_path, table_path).await?.ok_or_else(not_found)?;
+    table.query(table_path, query)
+}
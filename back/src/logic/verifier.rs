@@ -0,0 +1,186 @@
+use std::{collections::HashMap, env, sync::Mutex, time::{Duration, Instant}};
+use anyhow::{anyhow, bail, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+use super::auth;
+
+/*
+
+Human verifiers check that a submitted request was made by a person and not by a bot. The verifier is selected in
+the configuration: a captcha service (reCAPTCHA, hCaptcha or Turnstile), the built-in proof-of-work challenge or,
+for offline and test environments, a verifier that always accepts or always refuses the requests.
+
+*/
+
+// Lifetime of a proof-of-work challenge
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(300);
+// Maximum number of unsolved proof-of-work challenges that are kept in memory
+const MAX_PENDING_CHALLENGES: usize = 10000;
+
+// Challenge that a client solves before submitting a request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Challenge {
+    pub challenge: String,
+    // Leading zero bits of the SHA-256 hash of "<challenge>:<nonce>"
+    pub difficulty: u32,
+    // Seconds until the challenge expires
+    pub expires_in: u64,
+}
+
+#[tonic::async_trait]
+pub trait HumanVerifier: Send + Sync + std::fmt::Debug {
+    // Name of the verifier, as used in the configuration
+    fn name(&self) -> &'static str;
+
+    // Issue a challenge for the verifiers that have their own, instead of a third-party widget
+    fn issue_challenge(&self) -> Result<Option<Challenge>> {
+        Ok(None)
+    }
+
+    // Check the token that was sent along with a request
+    async fn verify(&self, token: &str) -> Result<bool>;
+}
+
+// Create the verifier that is selected in the configuration
+pub fn create_verifier(kind: &str) -> Result<Box<dyn HumanVerifier>> {
+    let verifier: Box<dyn HumanVerifier> = match kind {
+        "recaptcha" => Box::new(SiteVerifier::new("recaptcha", "https://www.google.com/recaptcha/api/siteverify", env::var("CAPTCHA_SECRET")?)),
+        "hcaptcha" => Box::new(SiteVerifier::new("hcaptcha", "https://api.hcaptcha.com/siteverify", env::var("CAPTCHA_SECRET")?)),
+        "turnstile" => Box::new(SiteVerifier::new("turnstile", "https://challenges.cloudflare.com/turnstile/v0/siteverify", env::var("CAPTCHA_SECRET")?)),
+        "pow" => Box::new(ProofOfWorkVerifier::new(env::var("POW_DIFFICULTY")?.parse::<u32>()?)?),
+        "always_pass" => Box::new(StaticVerifier { outcome: true }),
+        "always_fail" => Box::new(StaticVerifier { outcome: false }),
+        _ => bail!("Unknown human verifier: {}", kind)
+    };
+    Ok(verifier)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SiteVerifyResponse {
+    pub success: bool,
+    #[serde(rename="error-codes")]
+    pub error_codes: Option<Vec<String>>
+}
+
+// Captcha service with a siteverify endpoint, which is common to reCAPTCHA, hCaptcha and Turnstile
+#[derive(Debug)]
+pub struct SiteVerifier {
+    name: &'static str,
+    url: &'static str,
+    secret: String,
+    client: Client,
+}
+
+impl SiteVerifier {
+
+    // Create a new instance
+    pub fn new(name: &'static str, url: &'static str, secret: String) -> Self {
+        Self { name, url, secret, client: Client::new() }
+    }
+}
+
+#[tonic::async_trait]
+impl HumanVerifier for SiteVerifier {
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn verify(&self, token: &str) -> Result<bool> {
+        let params = [("secret", self.secret.as_str()), ("response", token)];
+        let response = self.client.post(self.url)
+                .form(&params)
+                .send().await?.text().await?;
+        let verification: SiteVerifyResponse = serde_json::from_str(&response)?;
+        match verification.error_codes {
+            Some(codes) => debug!("{} error: {:?}", self.name, codes),
+            None => debug!("No error codes")
+        }
+        Ok(verification.success)
+    }
+}
+
+// Built-in challenge: the client finds a nonce for which the SHA-256 hash of "<challenge>:<nonce>" starts with
+// the configured number of zero bits. Each challenge is accepted once and only before it expires.
+#[derive(Debug)]
+pub struct ProofOfWorkVerifier {
+    difficulty: u32,
+    pending_challenges: Mutex<HashMap<String, Instant>>,
+}
+
+impl ProofOfWorkVerifier {
+
+    // Create a new instance
+    pub fn new(difficulty: u32) -> Result<Self> {
+        if !(1..=32).contains(&difficulty) {
+            bail!("The proof-of-work difficulty must be between 1 and 32 bits");
+        }
+        Ok(Self { difficulty, pending_challenges: Mutex::new(HashMap::new()) })
+    }
+
+    // Count the leading zero bits of a hash
+    fn leading_zero_bits(hash: &[u8]) -> u32 {
+        let mut bits = 0;
+        for byte in hash {
+            bits += byte.leading_zeros();
+            if *byte != 0 {
+                break;
+            }
+        }
+        bits
+    }
+}
+
+#[tonic::async_trait]
+impl HumanVerifier for ProofOfWorkVerifier {
+
+    fn name(&self) -> &'static str {
+        "pow"
+    }
+
+    fn issue_challenge(&self) -> Result<Option<Challenge>> {
+        let mut pending_challenges = self.pending_challenges.lock().map_err(|_| anyhow!("The challenges are not available"))?;
+        pending_challenges.retain(|_, expiration| *expiration > Instant::now());
+        if pending_challenges.len() >= MAX_PENDING_CHALLENGES {
+            bail!("Too many pending challenges");
+        }
+        let challenge = auth::generate_token();
+        pending_challenges.insert(challenge.clone(), Instant::now() + CHALLENGE_LIFETIME);
+        Ok(Some(Challenge { challenge, difficulty: self.difficulty, expires_in: CHALLENGE_LIFETIME.as_secs() }))
+    }
+
+    // The token is in the form <challenge>:<nonce>
+    async fn verify(&self, token: &str) -> Result<bool> {
+        let challenge = match token.split_once(':') {
+            Some((challenge, nonce)) if !nonce.is_empty() && nonce.len() <= 64 => challenge,
+            _ => return Ok(false)
+        };
+        let expiration = self.pending_challenges.lock().map_err(|_| anyhow!("The challenges are not available"))?
+                             .remove(challenge);
+        if !matches!(expiration, Some(expiration) if expiration > Instant::now()) {
+            debug!("Unknown or expired challenge");
+            return Ok(false);
+        }
+        Ok(Self::leading_zero_bits(&Sha256::digest(token.as_bytes())) >= self.difficulty)
+    }
+}
+
+// Verifier with a fixed outcome, for offline and test environments
+#[derive(Debug)]
+pub struct StaticVerifier {
+    outcome: bool,
+}
+
+#[tonic::async_trait]
+impl HumanVerifier for StaticVerifier {
+
+    fn name(&self) -> &'static str {
+        if self.outcome { "always_pass" } else { "always_fail" }
+    }
+
+    async fn verify(&self, _token: &str) -> Result<bool> {
+        Ok(self.outcome)
+    }
+}
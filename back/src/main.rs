@@ -1,7 +1,7 @@
-use std::env;
+use std::{env, sync::Arc};
 
-use crate::{rest::AppServer, monitor::Monitor};
-use anyhow::Result;
+use crate::{rest::AppServer, monitor::Monitor, db::{dbhandler::DatabaseHandler, migrations}, logic::auth, grpc::registry::RegistryServer, node::{NodeServer, executor::CommandExecutor}};
+use anyhow::{anyhow, Result};
 use tracing::debug; 
 
 pub mod db;
@@ -11,6 +11,8 @@ pub mod rest;
 pub mod grpc;
 pub mod logic;
 pub mod monitor;
+pub mod node;
+pub mod error;
 use dotenvy::dotenv;
 
 pub mod jobreceiver {
@@ -20,15 +22,61 @@ pub mod jobreceiver {
 #[tokio::main]
 async fn main() -> Result<()> { 
     dotenv().ok(); 
+    // Run as a computing node instead of the root node
+    let node_mode = env::args().any(|arg| arg == "--node");
 
     // Tracing
-    let file_appender = tracing_appender::rolling::daily(env::var("LOG_DIR")?, "axum.log");
+    let log_filename = if node_mode { "node.log" } else { "axum.log" };
+    let file_appender = tracing_appender::rolling::daily(env::var("LOG_DIR")?, log_filename);
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
     tracing_subscriber::fmt()
     .with_writer(non_blocking)
     .with_max_level(tracing::Level::DEBUG)
     .init();
 
+    // Computing node
+    if node_mode {
+        let cache_path = env::var("NODE_CACHE_PATH")?;
+        let executor = CommandExecutor::new(env::var("NODE_JOB_COMMAND")?, cache_path.clone());
+        println!("MachaonWeb computing node is starting."); 
+        // Self-registration with the root node (optional)
+        if let Ok(registry_url) = env::var("NODE_REGISTRY_URL") {
+            if !registry_url.is_empty() {
+                tokio::spawn(NodeServer::register(registry_url, env::var("NODE_REGISTRY_DOMAIN")?, env::var("NODE_CERT_NAME")?,
+                                                  env::var("NODE_ADVERTISED_ADDRESS")?, env::var("NODE_DOMAIN")?));
+            }
+        }
+        NodeServer::new(env::var("NODE_SERVER_IP")?, env::var("NODE_SERVER_PORT")?.parse::<u16>()?, 
+                        &cache_path, &env::var("NODE_WORK_PATH")?, env::var("NODE_MAX_JOBS")?.parse::<usize>()?,
+                        Arc::new(executor))?.start().await?;
+        return Ok(());
+    }
+
+    // Schema migrations: the pending ones are applied with --migrate, otherwise the root node only starts with an up-to-date schema
+    if env::args().any(|arg| arg == "--migrate") {
+        for version in migrations::apply_pending_migrations().await? {
+            println!("Applied the migration {}.", version);
+        }
+    } else {
+        let pending_migrations = migrations::get_pending_migrations().await?;
+        if !pending_migrations.is_empty() {
+            return Err(anyhow!("The database schema is outdated (pending migrations: {}). Start with --migrate to apply them.",
+                               pending_migrations.join(", ")));
+        }
+    }
+
+    // Administrator creation, or password replacement of an existing administrator, with the password read from the standard input
+    let mut arguments = env::args().skip_while(|arg| arg != "--create-admin");
+    if arguments.next().is_some() {
+        let username = arguments.next().ok_or_else(|| anyhow!("Usage: --create-admin <username>"))?;
+        println!("Password for {}:", username);
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        auth::create_admin(&DatabaseHandler::new()?, &username, password.trim_end_matches(['\r', '\n'])).await?;
+        println!("Administrator {} is saved.", username);
+        return Ok(());
+    }
+
     // Configurations
     let monitor_path: &'static str = Box::leak(env::var("MONITOR_PATH")?.to_string().into_boxed_str());
     let output_path: &'static str = Box::leak(env::var("OUTPUT_PATH")?.to_string().into_boxed_str());
@@ -36,6 +84,7 @@ async fn main() -> Result<()> {
     let request_monitoring_enabled = true;
     let job_monitoring_enabled = true;
     let node_synchronization_enabled = true;
+    let node_registry_enabled = env::var("NODE_REGISTRY_ENABLED").as_deref() == Ok("true");
 
     // Request monitoring loop start
     if request_monitoring_enabled == true {
@@ -85,9 +134,20 @@ async fn main() -> Result<()> {
         });
     }
 
+    // Node registry start
+    if node_registry_enabled {
+        let registry = RegistryServer::new(env::var("WEB_SERVER_IP")?, env::var("NODE_REGISTRY_PORT")?.parse::<u16>()?)?;
+        tokio::spawn( async move{
+            println!("Node registry started."); 
+            if let Err(e) = registry.start().await {
+                debug!("Node registry failed: {}", e); 
+            }
+        });
+    }
+
     // Server
     println!("MachaonWeb is starting."); 
-    AppServer::new(env::var("WEB_SERVER_IP")?, env::var("WEB_SERVER_PORT")?.parse::<u16>()?, output_path.to_string())?.start().await?;
+    AppServer::new(env::var("WEB_SERVER_IP")?, env::var("WEB_SERVER_PORT")?.parse::<u16>()?, output_path.to_string(), web_protocol.to_string())?.start().await?;
 
     Ok(())
 }
@@ -1,12 +1,14 @@
-use std::{ thread, time::Duration, i8, fs, path::{Path, self}, env };
+use std::{ sync::Mutex, time::{Duration, SystemTime}, fs, path::{Path, self}, env, collections::{HashMap, HashSet} };
+use futures::{stream, FutureExt, StreamExt};
 use dotenvy::dotenv;
-use rand::Rng;
-use uuid::Uuid;
-use crate::{ grpc::GrpcClient, db::{dbhandler::DatabaseHandler, models::{ NewJob, Node, QueriedRequest, Uncached }}, jobreceiver::{JobRequest, JobStatus, ResultRequest, JobDetails, ServerStatus}, utils };
+use crate::{ grpc::GrpcClient, db::{dbhandler::DatabaseHandler, models::{ NewJob, Node, JOB_TIMED_OUT, JOB_NODE_LOST, JOB_REQUEUED, JOB_CANCELLED, RequestState, QueriedRequest, QueriedJob, Uncached, NewSchedulingDecision, NewNodeFile }}, jobreceiver::{JobRequest, JobStatus, ResultRequest, JobDetails, CacheFile}, utils };
+use self::scheduler::{JobProfile, NodeProfile, Scheduler};
 use anyhow::Result;
 use tracing::{debug, instrument};
 use glob::{glob_with, MatchOptions};
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
+
+pub mod scheduler;
 
 /*  
 This module is dedicated to handling the events of MachaonWeb framework:
@@ -16,12 +18,35 @@ This module is dedicated to handling the events of MachaonWeb framework:
   that were produced from previous jobs in other nodes 
 */
 
+// Number of files in each manifest that is exchanged with a node
+const MANIFEST_BATCH_SIZE: usize = 1000;
+
+// A job that is ready to be assigned along with the ranking of the candidate nodes
+#[derive(Debug)]
+struct JobAssignment {
+    job_request: JobRequest,
+    job_profile: JobProfile,
+    ranking: Vec<NodeProfile>,
+    large_job: bool,
+}
+
 #[derive(Debug)]
 pub struct Monitor<'a>{ 
     request_monitoring_interval: Duration,
     job_monitoring_interval: Duration,
     sync_interval: Duration,
     db_handler: DatabaseHandler,
+    scheduler: Box<dyn Scheduler>,
+    large_job_threshold: usize,
+    large_job_min_cores: i16,
+    cores_per_job: i64,
+    max_concurrent_dispatches: usize,
+    // Maximum running time of a job in minutes, for each comparison mode (whole, domain, segment)
+    job_timeouts: [i64; 3],
+    node_max_failed_checks: i32,
+    max_job_retries: i32,
+    // Hash of each synchronized cache file along with its size and modification time when it was hashed
+    file_hashes: HashMap<String, (u64, SystemTime, String)>,
     root_dir_path: &'a str,
     output_dir_path: &'a str,
     protocol: &'a str
@@ -52,6 +77,17 @@ impl<'a> Monitor<'a> {
                 request_monitoring_interval: Duration::from_secs(env::var("REQUEST_MONITORING_INTERVAL")?.parse::<u64>()?),
                 job_monitoring_interval: Duration::from_secs(env::var("JOB_MONITORING_INTERVAL")?.parse::<u64>()?),
                 sync_interval: Duration::from_secs(env::var("NODE_SYNC_INTERVAL")?.parse::<u64>()?), 
+                scheduler: scheduler::create_scheduler(&env::var("JOB_SCHEDULER")?)?,
+                large_job_threshold: env::var("LARGE_JOB_THRESHOLD")?.parse::<usize>()?,
+                large_job_min_cores: env::var("LARGE_JOB_MIN_CORES")?.parse::<i16>()?,
+                cores_per_job: env::var("CORES_PER_JOB")?.parse::<i64>()?.max(1),
+                max_concurrent_dispatches: env::var("MAX_CONCURRENT_DISPATCHES")?.parse::<usize>()?.max(1),
+                job_timeouts: [env::var("JOB_TIMEOUT_WHOLE")?.parse::<i64>()?,
+                               env::var("JOB_TIMEOUT_DOMAIN")?.parse::<i64>()?,
+                               env::var("JOB_TIMEOUT_SEGMENT")?.parse::<i64>()?],
+                node_max_failed_checks: env::var("NODE_MAX_FAILED_CHECKS")?.parse::<i32>()?.max(1),
+                max_job_retries: env::var("MAX_JOB_RETRIES")?.parse::<i32>()?,
+                file_hashes: HashMap::new(),
                 root_dir_path: working_directory_full_path,
                 output_dir_path: output_full_path,
                 protocol: web_protocol })
@@ -60,22 +96,22 @@ impl<'a> Monitor<'a> {
     // Loop for monitoring new requests
     pub async fn monitor_requests(&mut self) {
         loop{
-            match self.fulfill_request().await{
+            match self.fulfill_requests().await{
                 Ok(result) => result,
                 Err(e) => {debug!("{}", e); false}
             };
-            thread::sleep(self.request_monitoring_interval);
+            tokio::time::sleep(self.request_monitoring_interval).await;
         }
     }
 
     // Loop for checking running jobs
     pub async fn monitor_jobs(&mut self) {
         loop{
-            match self.check_job().await{
+            match self.check_jobs().await{
                 Ok(result) => result,
                 Err(e) => {debug!("{}", e); false}
             };
-            thread::sleep(self.job_monitoring_interval);
+            tokio::time::sleep(self.job_monitoring_interval).await;
         }
     }
 
@@ -86,361 +122,547 @@ impl<'a> Monitor<'a> {
                 Ok(result) => result,
                 Err(e) => {debug!("{}", e); false}
             };
-            thread::sleep(self.sync_interval);
+            tokio::time::sleep(self.sync_interval).await;
         }
     }
 
-    // Handling a new request by assigning a new job in an available computational node
+    // Handling the new requests by assigning new jobs in the available computational nodes
     #[instrument(level="debug")]
-    pub async fn fulfill_request(&mut self) -> Result<bool> {
-        let db_handler = &mut self.db_handler;
-        let mut result = false;
-        // Check if there any nodes available
-        let nodes_count = match db_handler.get_available_node_count().await?{
-            Some(count) => count,
-            None => 0
-        };
-        if nodes_count > 0
-        {
-            // Check if there are any requests that have not been handled yet
-            let entries = db_handler.get_early_pending_request().await?;
-            if entries.len() > 0 {
-                let initialized = QueriedRequest::construct()?;
-                let request = match entries.get(0){
-                    Some(entry) => entry,
-                    None => &initialized
-                }; 
-                if request.id > 0
-                { 
-                    // Double-check if the request has not been already fullfilled 
-                    let secure_hash: String = match db_handler.get_fullfilled_request(&request.hash_value, 
-                                                                                      &request.meta, &request.go_term).await?{ 
-                            Some(sha_hash) => sha_hash,
-                            None => String::from("") 
-                    };
-                    if secure_hash.len() > 0 {
-                        // The request has already been processed
-                        let new_job = NewJob{ request_id: &request.id, node_id: &-1, status_code: &0, completion_date:
-                                                       Some(Utc::now().naive_utc()), secure_hash: &secure_hash};
-                        DatabaseHandler::insert_job(db_handler, &new_job).await?;
-                    }
-                    else {
-                        // Parse the candidate selections
-                        let list_name: Vec<String> = match &request.list_name {
-                            Some(entry) => entry.split(" ")
-                            .into_iter()
-                            .map(|s| s.to_string())
-                            .collect(),
-                            None => Vec::<String>::new()
-                        };
-                        
-                        // Create a job instance
-                        let job_request = JobRequest { 
-                            reference_id: request.reference.clone(),
-                            request_id: request.id,
-                            listname: match list_name.get(0){
-                                Some(entry) => entry.to_string(),
-                                None => String::from("")
-                            },
-                            structure_ids: request.custom_list.split(",")
-                            .into_iter()
-                            .map(|s| s.to_string())
-                            .collect(),
-                            meta_analysis: request.meta,
-                            go_term: request.go_term.clone(),
-                            hash: request.hash_value.clone(),
-                            comparison_mode: i32::from(request.comparison_mode),
-                            segment_start: i32::from(request.segment_start),
-                            segment_end: i32::from(request.segment_end),
-                            alignment_level: i32::from(request.alignment_level)
-                        };
-
-                        // Assign a job to a computational node
-                        result = self.assign_job(&job_request).await?;
-                    }
+    pub async fn fulfill_requests(&mut self) -> Result<bool> {
+        // Determine how many more jobs each active node can accept
+        let running_counts: HashMap<i16, i64> = self.db_handler.get_running_job_counts().await?.into_iter().collect();
+        let mut free_slots: HashMap<i16, i64> = HashMap::new();
+        let mut available_nodes = Vec::<Node>::new();
+        for node in self.db_handler.get_active_nodes().await? {
+            let slots = self.job_slots(&node) - running_counts.get(&node.id).copied().unwrap_or(0);
+            if slots > 0 {
+                free_slots.insert(node.id, slots);
+                available_nodes.push(node);
+            }
+        }
+        if free_slots.is_empty() {
+            return Ok(false);
+        }
+        let max_cores = self.db_handler.get_max_active_cores().await?.unwrap_or(0);
+        // The slots are reserved on the node that accepts each job when the jobs are dispatched, since a job may fall back
+        // to a node other than its preferred one
+        let node_slots = Mutex::new(free_slots.clone());
+        // Structures that were produced in other nodes by the jobs that completed since the last sync of each available node
+        let mut produced_elsewhere: HashMap<i16, HashSet<String>> = HashMap::new();
+        for node in &available_nodes {
+            let structure_ids = self.db_handler.get_uncached_by_date(node.id, node.sync_date).await?.iter()
+                                    .flat_map(|entry| entry.uncached.split(','))
+                                    .filter(|structure_id| !structure_id.is_empty())
+                                    .map(String::from)
+                                    .collect();
+            produced_elsewhere.insert(node.id, structure_ids);
+        }
+
+        // Requests that share their hash with a running job wait for its result, and the requests with meta-analysis
+        // that share their comparison (base hash) with a running job wait to reuse its outputs
+        let running_jobs = self.db_handler.get_running_jobs().await?;
+        let mut planned_hashes: HashSet<String> = running_jobs.iter().map(|job| job.hash_value.clone()).collect();
+        let mut planned_bases: HashSet<String> = running_jobs.into_iter().filter_map(|job| job.base_hash).collect();
+        let mut assignments = Vec::<JobAssignment>::new();
+        // Check if there are any requests that have not been handled yet
+        for request in self.db_handler.get_early_pending_requests().await? {
+            if free_slots.values().all(|slots| *slots <= 0) {
+                break;
+            }
+            if planned_hashes.contains(&request.hash_value) {
+                continue;
+            }
+            if request.meta && matches!(&request.base_hash, Some(base_hash) if planned_bases.contains(base_hash)) {
+                continue;
+            }
+            // Double-check if the request has not been already fullfilled 
+            let secure_hash: String = self.db_handler.get_fullfilled_request(&request.hash_value, &request.meta, &request.go_term)
+                                          .await?.unwrap_or_default();
+            if !secure_hash.is_empty() {
+                // The request has already been processed
+                let new_job = NewJob{ request_id: request.id, node_id: -1, status_code: 0, completion_date:
+                                               Some(Utc::now().naive_utc()), secure_hash, retries: 0};
+                if let Err(e) = self.db_handler.insert_job(new_job, RequestState::Completed, "served by an identical request").await {
+                    debug!("{}", e);
+                }
+                continue;
+            }
+
+            // A request with meta-analysis reuses a finished comparison on the node that ran it, so that only 
+            // the meta-analysis and the GO term stage run there
+            let base_job = match &request.base_hash {
+                Some(base_hash) if request.meta => self.db_handler.get_fullfilled_base(base_hash).await?,
+                _ => None
+            };
+
+            // Rank the nodes that still have free slots in this cycle
+            let job_profile = JobProfile::from_request(&request);
+            let candidates: Vec<Node> = available_nodes.iter()
+                                                       .filter(|node| free_slots.get(&node.id).copied().unwrap_or(0) > 0)
+                                                       .cloned()
+                                                       .collect();
+            let (mut ranking, large_job) = self.rank_nodes(candidates, &job_profile, max_cores, &produced_elsewhere).await?;
+            let mut job_request = Self::create_job_request(&request);
+            if let Some((base_job_hash, base_node_id)) = base_job {
+                if let Some(position) = ranking.iter().position(|profile| profile.node.id == base_node_id) {
+                    let base_node = ranking.remove(position);
+                    ranking.insert(0, base_node);
+                    job_request.base_job_hash = base_job_hash;
                 }
             }
+            match ranking.first() {
+                Some(preferred) => {
+                    if let Some(slots) = free_slots.get_mut(&preferred.node.id) {
+                        *slots -= 1;
+                    }
+                },
+                None => continue
+            }
+            planned_hashes.insert(request.hash_value.clone());
+            if let Some(base_hash) = &request.base_hash {
+                planned_bases.insert(base_hash.clone());
+            }
+            assignments.push(JobAssignment { job_request, job_profile, ranking, large_job });
+        }
+
+        // Assign the jobs to the computational nodes concurrently
+        let dispatches: Vec<_> = assignments.iter().map(|assignment| self.assign_job(assignment, &node_slots)
+                                                                     .map(move |outcome| (assignment, outcome)))
+                                                   .collect();
+        let outcomes: Vec<(&JobAssignment, Result<Option<i16>>)> = stream::iter(dispatches)
+                                                                     .buffer_unordered(self.max_concurrent_dispatches)
+                                                                     .collect().await;
+        let mut result = false;
+        for (assignment, outcome) in outcomes {
+            match outcome {
+                Ok(Some(node_id)) => {
+                    // Only the node that accepted the job counts as selected by the scheduler
+                    self.scheduler.record_assignment(&assignment.ranking, node_id);
+                    result = true;
+                },
+                Ok(None) => {},
+                Err(e) => debug!("{}", e)
+            }
         }
         Ok(result)
     } 
+
+    // Create a job instance from a request
+    fn create_job_request(request: &QueriedRequest) -> JobRequest {
+        // Parse the candidate selections
+        let list_name: Vec<String> = match &request.list_name {
+            Some(entry) => entry.split(' ')
+            .map(|s| s.to_string())
+            .collect(),
+            None => Vec::<String>::new()
+        };
+
+        JobRequest { 
+            reference_id: request.reference.clone(),
+            request_id: request.id,
+            listname: match list_name.first(){
+                Some(entry) => entry.to_string(),
+                None => String::from("")
+            },
+            structure_ids: request.custom_list.split(',')
+            .map(|s| s.to_string())
+            .collect(),
+            meta_analysis: request.meta,
+            go_term: request.go_term.clone(),
+            hash: request.hash_value.clone(),
+            comparison_mode: i32::from(request.comparison_mode),
+            segment_start: request.segment_start,
+            segment_end: request.segment_end,
+            alignment_level: i32::from(request.alignment_level),
+            base_job_hash: String::from("")
+        }
+    }
+
+    // Number of concurrent jobs that a node can execute
+    fn job_slots(&self, node: &Node) -> i64 {
+        (i64::from(node.cores) / self.cores_per_job).max(1)
+    }
  
     // Synchronize a node in the MachaonWeb network
+    // Only the files that the node lacks are transferred: the files that were recorded for the node are skipped, the node
+    // reports the hashes of the rest and the missing or outdated files are uploaded in resumable chunks
     pub async fn sync_node(&mut self) -> Result<bool> {
-        let db_handler = &mut self.db_handler;
-        let mut result = false;
+        let db_handler = &self.db_handler;
+        // Look for an outdated computing node
+        let outdated_node = match DatabaseHandler::get_outdated_node(db_handler, self.cores_per_job).await?{
+            Some(node) => node,
+            None => return Ok(false)
+        };  
+        // Find which structures were processed in jobs that completed since the node's last update. The new sync date is the
+        // date before the lookup, so that the jobs that complete during the synchronization are included in the next one.
+        let sync_date = DatabaseHandler::get_database_date(db_handler).await?;
+        let custom_lists:Vec<Uncached> = DatabaseHandler::get_uncached_by_date(db_handler, 
+                                                                outdated_node.id, outdated_node.sync_date).await?;
+        let structure_ids: HashSet<&str> = custom_lists.iter()
+                                                       .flat_map(|custom_list| custom_list.uncached.split(','))
+                                                       .filter(|structure_id| !structure_id.is_empty())
+                                                       .collect();
+
+        // Hash the cached data of these structures, skipping the files that the node is known to hold. The files are only
+        // hashed again when their size or modification time changed since they were last hashed.
+        let mut local_paths = HashMap::new();
+        let mut cache_files = Vec::new();
+        for (cache_path, local_path) in self.collect_cache_files(&structure_ids)? {
+            let metadata = fs::metadata(&local_path)?;
+            let (size, modified) = (metadata.len(), metadata.modified()?);
+            let hash = match self.file_hashes.get(&local_path) {
+                Some((hashed_size, hashed_modified, hash)) if *hashed_size == size && *hashed_modified == modified => hash.clone(),
+                _ => {
+                    let hash = utils::compute_file_hash(&local_path)?;
+                    self.file_hashes.insert(local_path.clone(), (size, modified, hash.clone()));
+                    hash
+                }
+            };
+            cache_files.push(CacheFile { hash, size: size as i64, path: cache_path.clone(), received: 0 });
+            local_paths.insert(cache_path, local_path);
+        }
+        let cache_paths: Vec<String> = cache_files.iter().map(|cache_file| cache_file.path.clone()).collect();
+        let synced_files: HashMap<String, String> = DatabaseHandler::get_synced_files(db_handler, &outdated_node.id, &cache_paths)
+                                                                            .await?.into_iter().collect();
+        cache_files.retain(|cache_file| synced_files.get(&cache_file.path) != Some(&cache_file.hash));
+
+        // Exchange manifests with the node and upload the files that it lacks via mTLS gRPC
+        let client = GrpcClient::new([self.protocol, outdated_node.ip.as_str()].join(""), outdated_node.domain.as_str())?;
+        let mut complete = true;
+        for batch in cache_files.chunks(MANIFEST_BATCH_SIZE) {
+            let node_files: HashMap<String, CacheFile> = client.get_manifest(batch.to_vec()).await?.into_iter()
+                                                               .map(|node_file| (node_file.path.clone(), node_file))
+                                                               .collect();
+            for cache_file in batch {
+                let node_file = node_files.get(&cache_file.path);
+                if node_file.map(|node_file| &node_file.hash) != Some(&cache_file.hash) {
+                    // Resume an interrupted upload of the same file
+                    let received = node_file.map(|node_file| node_file.received)
+                                            .filter(|received| *received <= cache_file.size)
+                                            .unwrap_or(0);
+                    let upload = CacheFile { received, ..cache_file.clone() };
+                    let status = match client.upload_file(&local_paths[&cache_file.path], &upload).await {
+                        Ok(code) => code,
+                        Err(e) => {debug!("{}", e); -1}
+                    };
+                    if status != 0 {
+                        complete = false;
+                        continue;
+                    }
+                }
+                let node_file = NewNodeFile { node_id: outdated_node.id, file_path: cache_file.path.clone(), file_hash: cache_file.hash.clone() };
+                DatabaseHandler::record_synced_file(db_handler, node_file).await?;
+            }
+        }
+
+        // Update the database with the new update timestamp
+        if complete {
+            DatabaseHandler::update_node_sync_date(db_handler, &outdated_node.id, sync_date).await?;
+        }
+        Ok(complete)
+    } 
+
+    // Find the cached data of the given structures: structure data files and extracted features
+    // Each file is returned with its path relative to the cache directory and its local path
+    fn collect_cache_files(&self, structure_ids: &HashSet<&str>) -> Result<Vec<(String, String)>> {
         // Pattern matching options for feature filenames
         let options = MatchOptions {
             case_sensitive: true,
             require_literal_separator: false,
             require_literal_leading_dot: false
         };
-        // Look for an outdated computing node
-        let outdated_node = match DatabaseHandler::get_outdated_node(db_handler).await?{
-            Some(node) => node,
-            None => Node::construct()
-        };  
-        // Handle outdated node
-        if outdated_node.id > 0 { 
-            // Find which structures were processed in previous jobs since the node's last update
-            let custom_lists:Vec<Uncached> = DatabaseHandler::get_uncached_by_date(db_handler, 
-                                                                    outdated_node.id, outdated_node.sync_date).await?;
-            if custom_lists.len() > 0{
-                // Setting the paths for the cached data and creating a temporary folder for the update
-                let uuid = Uuid::new_v4(); 
-                let temp_folder_name = uuid.to_string(); 
-                let temp_path = [self.root_dir_path, &path::MAIN_SEPARATOR.to_string(), &temp_folder_name].join("");
-                let pdb_path = [&temp_path, &path::MAIN_SEPARATOR.to_string(), "PDBs_new"].join("");
-                let data_path = [&temp_path, &path::MAIN_SEPARATOR.to_string(), "DATA_PDBs_new_whole"].join("");
-                let domain_data_path = [&temp_path, &path::MAIN_SEPARATOR.to_string(), "DATA_PDBs_new_domain"].join("");
-                let root_pdb_path = [self.root_dir_path, &path::MAIN_SEPARATOR.to_string(), "PDBs_new"].join("");
-                let root_data_path = [self.root_dir_path, &path::MAIN_SEPARATOR.to_string(), "DATA_PDBs_new_whole"].join("");
-                let root_domain_data_path = [self.root_dir_path, &path::MAIN_SEPARATOR.to_string(), "DATA_PDBs_new_domain"].join("");
-                let output_path = [&temp_path, ".zip"].join("");
-                fs::create_dir_all(&temp_path)?;
-                fs::create_dir_all(&pdb_path)?;
-                fs::create_dir_all(&data_path)?;
-                fs::create_dir_all(&domain_data_path)?;
-
-                // For each list of a newer request than the current node's last update, 
-                // copy the associated uncached data to a temporary folder
-                for custom_list in custom_lists {
-                    let ids = custom_list.uncached.split(",");
-                    for structure_id in ids { 
-                        // structure data files
-                        if Path::new(&data_path).exists() {
-                            fs::copy([&root_pdb_path, &path::MAIN_SEPARATOR.to_string(), structure_id, ".pdb"].join(""),
-                                       [&pdb_path, &path::MAIN_SEPARATOR.to_string(), structure_id, ".pdb"].join(""))?; 
-                        }
-
-                        // whole structure extracted features
-                        if Path::new(&root_data_path).exists() {
-                            for entry in glob_with(&[&root_data_path, 
-                                                                                &path::MAIN_SEPARATOR.to_string(), 
-                                                                                structure_id, "*"].join(""), options)? {
-                                if let Ok(path) = entry {
-                                    let filename = match path.file_name() {
-                                        Some(os_string) => match os_string.to_str() {
-                                            Some(base_name) => base_name,
-                                            None => ""
-                                        },
-                                        None => ""
-                                    };
-
-                                    match path.as_os_str().to_str() {
-                                        Some(path_string) => {
-                                            fs::copy(path_string, 
-                                            &[&data_path, &path::MAIN_SEPARATOR.to_string(), filename].join(""))?;
-                                        },
-                                        None => {}
-                                    }  
-                                }
-                            }
-                        }
-
-                        // domain-level extracted features
-                        if Path::new(&root_domain_data_path).exists() {
-                            for entry in glob_with(&[&root_domain_data_path, 
-                                                                               &path::MAIN_SEPARATOR.to_string(), 
-                                                                               structure_id, "*"].join(""), options)? {
-                                if let Ok(path) = entry {
-                                    let filename = match path.file_name() {
-                                        Some(os_string) => match os_string.to_str() {
-                                            Some(base_name) => base_name,
-                                            None => ""
-                                        },
-                                        None => ""
-                                    };
-
-                                    match path.as_os_str().to_str() {
-                                        Some(path_string) => {
-                                            fs::copy(path_string, 
-                                            &[&data_path, &path::MAIN_SEPARATOR.to_string(), filename].join(""))?;
-                                        },
-                                        None => {}
-                                    }  
-                                }
-                            }
-                        }
+        let mut cache_files = Vec::new();
+        for structure_id in structure_ids {
+            let patterns = [("PDBs_new", [structure_id, ".pdb"].join("")),
+                            ("DATA_PDBs_new_whole", [structure_id, "*"].join("")),
+                            ("DATA_PDBs_new_domain", [structure_id, "*"].join(""))];
+            for (directory, pattern) in patterns {
+                let pattern_path = Path::new(self.root_dir_path).join(directory).join(&pattern);
+                for entry in glob_with(&pattern_path.to_string_lossy(), options)?.flatten() {
+                    if let (true, Some(filename)) = (entry.is_file(), entry.file_name().and_then(|name| name.to_str())) {
+                        cache_files.push(([directory, "/", filename].join(""), entry.to_string_lossy().to_string()));
                     }
                 }
-                // create a compressed archive that contains the new data
-                if Path::new(&output_path).exists() {
-                    fs::remove_file(&output_path)?;
-                }
-                let zip_ready = utils::compress_directory(&temp_path, &output_path)?;
-                if zip_ready == true {
-                    // Create a file hash for data integrity verification
-                    let secure_hash = utils::compute_file_hash(&output_path)?;
-                    // Upload the archive via mTLS gRPC
-                    let client = GrpcClient::new([self.protocol, outdated_node.ip.as_str()].join(""), outdated_node.domain.as_str())?;
-                    let server_status = match client.synchronize_node(&output_path, secure_hash.to_owned()).await{
-                        Ok(status) => status,
-                        Err(e) => {debug!("{}", e); ServerStatus { status_code: -1} }
-                    }; 
-                    // Update the database with the new update timestamp
-                    if server_status.status_code == 0 {
-                        DatabaseHandler::update_node_sync_date(db_handler, &outdated_node.id).await?;
-                        result = true;
-                    }
-                    // Clean up
-                    if Path::new(&temp_path).exists() {
-                        fs::remove_dir_all(&temp_path)?;
-                    }
-                    if Path::new(&output_path).exists() {
-                        fs::remove_file(&output_path)?;
-                    }
-                } 
             }
         }
-        Ok(result)
-    } 
+        Ok(cache_files)
+    }
+
+    // Handle the running jobs
+    #[instrument(level="debug")]
+    pub async fn check_jobs(&mut self) -> Result<bool> {
+        // Look for running jobs
+        let running_jobs = self.db_handler.get_running_jobs().await?;
+        let checks: Vec<_> = running_jobs.iter().map(|running_job| self.check_job(running_job)).collect();
+        let outcomes: Vec<Result<bool>> = stream::iter(checks)
+                                            .buffer_unordered(self.max_concurrent_dispatches)
+                                            .collect().await;
+        for outcome in outcomes {
+            if let Err(e) = outcome {
+                debug!("{}", e);
+            }
+        }
+        Ok(!running_jobs.is_empty())
+    }
 
-    // Handle finished jobs
+    // Check a running job and handle its result once it has finished
     #[instrument(level="debug")]
-    pub async fn check_job(&mut self) -> Result<bool> {
+    pub async fn check_job(&self, running_job: &QueriedJob) -> Result<bool> {
         // Database handler
-        let db_handler = &mut self.db_handler;
-        // Look for a running job
-        let active_job = db_handler.get_running_job().await?;
-        let result  = match active_job {
-            Some(running_job) => {
-                // Set the path of the folder that contains the results, using the hash of the request
-                let file_path = [self.root_dir_path, &path::MAIN_SEPARATOR.to_string(),
-                                         &running_job.hash_value, ".zip"].join("");
-                // Query the status of the node that has the running
-                let client = GrpcClient::new([self.protocol, running_job.node_ip.as_str()].join(""), running_job.node_domain.as_str())?;
-                let status = match client.get_server_status().await {
-                    Ok(code) => code,
-                    Err(e) => {debug!("{}", e); -1}
-                };
-                if status == 1
-                { 
-                    // Download the result if the job has finished
-                    let job_check = ResultRequest{ hash: running_job.hash_value.clone(), request_id: running_job.request_id };
-                    let job_details = match client.download_result(&job_check, &file_path).await{
-                        Ok(result) => result,
-                        Err(e) => {debug!("{}", e); JobDetails{request_id: -1, hash: String::from(""), 
-                                                                    secure_hash: String::from(""), status_code: -1}}
-                    }; 
-                    let mut job_finished = false;
-                    let mut file_hash= String::from("");
-                    // If the download was successful
-                    if job_details.status_code == 0{
-                        if Path::new(&file_path).exists(){
-                            // Compute the hash of the file
-                            file_hash = utils::compute_file_hash(&file_path)?;
-                            if file_hash == job_details.secure_hash {
-                                job_finished = true;
-                                // Create an output directory
-                                let output_directory: &str = &[self.output_dir_path, &path::MAIN_SEPARATOR.to_string(), 
-                                                                &running_job.hash_value].join("");
-                                if Path::new(&output_directory).exists() == false {
-                                    fs::create_dir_all(output_directory)?;
-                                }
-                                // Selectively extract the files from the downloaded archive:
-                                // - html outputs for quick view
-                                // - uncached data: structure data files & extracted features
-                                utils::extract_result_files(&file_path, &output_directory, "zip")?; 
-                                let compressed_filepath = &[output_directory, &path::MAIN_SEPARATOR.to_string(), &running_job.hash_value, 
-                                                                ".zip"].join("");
-                                utils::extract_result_files(&compressed_filepath, &output_directory, "html")?;
-                                let output_directory = &[self.root_dir_path, &path::MAIN_SEPARATOR.to_string(), "PDBs_new"].join("");
-                                utils::extract_result_files(&file_path, &output_directory, "pdb")?; 
-                                let output_directory = &[self.root_dir_path, &path::MAIN_SEPARATOR.to_string(), "DATA_PDBs_new_"].join("");
-                                if running_job.comparison_mode == 0 || running_job.comparison_mode == 1 {
-                                    let mut data_directory = "whole";
-                                    if running_job.comparison_mode == 1 {
-                                        data_directory = "domain";
-                                    }    
-                                    fs::create_dir_all(&[&output_directory, data_directory].join(""))?;
-                                    utils::extract_result_files(&file_path, &[&output_directory, data_directory].join(""), "proto")?; 
-                                }
-                                // Clean up
-                                fs::remove_file(&file_path)?;
-                            }
-                        }
-                    }
-                    if job_details.status_code == -2 || job_details.status_code == -3 || job_finished == true {
-                        // Handle failed jobs
-                        DatabaseHandler::finalize_job(db_handler, &running_job.id, &file_hash, &(job_details.status_code as i8)).await?;
-                        DatabaseHandler::update_node_working_state(db_handler, &running_job.node_id, false).await?;
-                    } 
-                }
-                else 
-                { 
-                    // Store the timestamp of the job check
-                    DatabaseHandler::update_job_check(db_handler, &running_job.id).await?;
-                } 
-                true
-            },
-            None =>  false
+        let db_handler = &self.db_handler;
+        // Set the path of the folder that contains the results, using the hash of the request
+        let file_path = [self.root_dir_path, path::MAIN_SEPARATOR_STR, &running_job.hash_value, ".zip"].join("");
+        // Jobs of deactivated nodes and jobs that exceeded their deadline are abandoned
+        if !running_job.node_active {
+            self.abandon_job(running_job, JOB_NODE_LOST).await?;
+            return Ok(false);
+        }
+        let timeout = usize::try_from(running_job.comparison_mode).ok()
+                                 .and_then(|mode| self.job_timeouts.get(mode).copied())
+                                 .unwrap_or(0);
+        if timeout > 0 && running_job.elapsed_minutes > timeout {
+            self.abandon_job(running_job, JOB_TIMED_OUT).await?;
+            return Ok(false);
+        }
+        // Query the status of the job on its node
+        let client = GrpcClient::new([self.protocol, running_job.node_ip.as_str()].join(""), running_job.node_domain.as_str())?;
+        let status = match client.get_job_status(running_job.request_id, &running_job.hash_value).await {
+            Ok(code) => code,
+            Err(e) => {
+                debug!("{}", e); 
+                self.record_failed_check(&running_job.node_id).await?;
+                DatabaseHandler::update_job_check(db_handler, &running_job.id).await?;
+                return Ok(false);
+            }
         };
-        
+        DatabaseHandler::record_node_check(db_handler, &running_job.node_id, true).await?;
+        let mut state = running_job.state.parse::<RequestState>()?;
+        if state == RequestState::Assigned && (status == 0 || status == 1) {
+            // The node confirmed that it runs the job
+            DatabaseHandler::transition_request(db_handler, &running_job.request_id, Some(running_job.id), 
+                                                RequestState::Running, "confirmed by the node").await?;
+            state = RequestState::Running;
+        }
+        if status == -1 {
+            // The node does not know the job, e.g. after a restart
+            self.abandon_job(running_job, JOB_NODE_LOST).await?;
+            return Ok(false);
+        }
+        if status == 2 {
+            // The job was cancelled on the node
+            DatabaseHandler::finalize_job(db_handler, &running_job.request_id, &running_job.id, "", &JOB_CANCELLED, 
+                                          RequestState::Cancelled, "cancelled on the node").await?;
+            DatabaseHandler::refresh_node_working_state(db_handler, &running_job.node_id).await?;
+            return Ok(false);
+        }
+        if status == 1
+        { 
+            // Download the result if the job has finished. The download is retried in the next checks until it succeeds.
+            if state != RequestState::Downloading {
+                let reason = if state == RequestState::Extracting { "extraction failed" } else { "finished on the node" };
+                DatabaseHandler::transition_request(db_handler, &running_job.request_id, Some(running_job.id), 
+                                                    RequestState::Downloading, reason).await?;
+            }
+            let job_check = ResultRequest{ hash: running_job.hash_value.clone(), request_id: running_job.request_id, offset: 0 };
+            let job_details = match client.download_result(&job_check, &file_path).await{
+                Ok(result) => result,
+                Err(e) => {debug!("{}", e); JobDetails{request_id: -1, hash: String::from(""), 
+                                                            secure_hash: String::from(""), status_code: -1, file_size: 0}}
+            }; 
+            let mut job_finished = false;
+            let mut file_hash= String::from("");
+            // If the download was successful
+            if job_details.status_code == 0 && Path::new(&file_path).exists(){
+                // The hash of the file is verified during the download
+                file_hash = job_details.secure_hash.clone();
+                job_finished = true;
+                DatabaseHandler::transition_request(db_handler, &running_job.request_id, Some(running_job.id), 
+                                                    RequestState::Extracting, "downloaded").await?;
+                // Create an output directory
+                let output_directory: &str = &[self.output_dir_path, path::MAIN_SEPARATOR_STR, &running_job.hash_value].join("");
+                if !Path::new(output_directory).exists() {
+                    fs::create_dir_all(output_directory)?;
+                }
+                // Selectively extract the files from the downloaded archive:
+                // - html outputs for quick view
+                // - uncached data: structure data files & extracted features
+                utils::extract_result_files(&file_path, output_directory, "zip")?; 
+                let compressed_filepath = &[output_directory, path::MAIN_SEPARATOR_STR, &running_job.hash_value, ".zip"].join("");
+                utils::extract_result_files(compressed_filepath, output_directory, "html")?;
+                let output_directory = &[self.root_dir_path, path::MAIN_SEPARATOR_STR, "PDBs_new"].join("");
+                utils::extract_result_files(&file_path, output_directory, "pdb")?; 
+                let output_directory = &[self.root_dir_path, path::MAIN_SEPARATOR_STR, "DATA_PDBs_new_"].join("");
+                if running_job.comparison_mode == 0 || running_job.comparison_mode == 1 {
+                    let mut data_directory = "whole";
+                    if running_job.comparison_mode == 1 {
+                        data_directory = "domain";
+                    }    
+                    fs::create_dir_all([output_directory, data_directory].join(""))?;
+                    utils::extract_result_files(&file_path, &[output_directory, data_directory].join(""), "proto")?; 
+                }
+                // Clean up
+                fs::remove_file(&file_path)?;
+            }
+            if job_details.status_code == -2 || job_details.status_code == -3 || job_finished {
+                // Finalize the completed and the failed jobs
+                let (next, reason) = match job_finished {
+                    true => (RequestState::Completed, String::from("extracted")),
+                    false => (RequestState::Failed, format!("failed on the node ({})", job_details.status_code))
+                };
+                DatabaseHandler::finalize_job(db_handler, &running_job.request_id, &running_job.id, &file_hash, 
+                                              &(job_details.status_code as i16), next, &reason).await?;
+                DatabaseHandler::refresh_node_working_state(db_handler, &running_job.node_id).await?;
+            } 
+        }
+        else 
+        { 
+            // Store the timestamp of the job check
+            DatabaseHandler::update_job_check(db_handler, &running_job.id).await?;
+        } 
 
-        Ok(result)
+        Ok(true)
     } 
 
-    // Assign a job to a computing node
+    // Finalize a job that will not be completed and queue its request again, as long as retries remain.
+    // Otherwise the request expires when its job timed out, or it fails when its node lost the job.
+    async fn abandon_job(&self, running_job: &QueriedJob, reason: i16) -> Result<()> {
+        let (status, next) = match (running_job.retries < self.max_job_retries, reason) {
+            (true, _) => (JOB_REQUEUED, RequestState::Queued),
+            (false, JOB_TIMED_OUT) => (reason, RequestState::Expired),
+            (false, _) => (reason, RequestState::Failed)
+        };
+        let description = if reason == JOB_TIMED_OUT { "the job timed out" } else { "the node lost the job" };
+        debug!("Abandoning job {} of request {} ({})", running_job.id, running_job.request_id, reason);
+        self.db_handler.finalize_job(&running_job.request_id, &running_job.id, "", &status, next, description).await?;
+        self.db_handler.refresh_node_working_state(&running_job.node_id).await?;
+        Ok(())
+    }
+
+    // Count a failed status query of a node and deactivate the node after too many consecutive failures
+    async fn record_failed_check(&self, node_id: &i16) -> Result<()> {
+        let failed_checks = self.db_handler.record_node_check(node_id, false).await?;
+        if failed_checks >= self.node_max_failed_checks {
+            debug!("Deactivating node {} after {} failed checks", node_id, failed_checks);
+            self.db_handler.update_node_active_state(node_id, false).await?;
+        }
+        Ok(())
+    }
+
+    // Gather the information that the scheduler needs for each candidate node
+    async fn profile_nodes(db_handler: &DatabaseHandler, nodes: Vec<Node>, job_profile: &JobProfile, min_cores: i16,
+                           produced_elsewhere: &HashMap<i16, HashSet<String>>) -> Result<Vec<NodeProfile>> {
+        let last_assignments: HashMap<i16, Option<NaiveDateTime>> = db_handler.get_last_assignment_dates().await?
+                                                                             .into_iter().collect();
+        let mut profiles = Vec::new();
+        for node in nodes.into_iter().filter(|node| node.cores >= min_cores) {
+            // Structures of the job that were produced in other nodes since the last sync of this node
+            let missing_structures = match produced_elsewhere.get(&node.id) {
+                Some(structure_ids) => job_profile.structure_ids.iter().filter(|id| structure_ids.contains(id.as_str())).count(),
+                None => 0
+            };
+            profiles.push(NodeProfile { last_assignment: last_assignments.get(&node.id).copied().flatten(),
+                                        missing_structures, node });
+        }
+        Ok(profiles)
+    }
+
+    // Order the available nodes by preference for a job, according to the configured strategy
+    async fn rank_nodes(&mut self, nodes: Vec<Node>, job_profile: &JobProfile, max_cores: i16,
+                        produced_elsewhere: &HashMap<i16, HashSet<String>>) -> Result<(Vec<NodeProfile>, bool)> {
+        // Large candidate sets are reserved for the nodes with enough cores, if there are any in the network
+        let large_job = job_profile.candidate_count >= self.large_job_threshold;
+        let min_cores = if large_job && max_cores >= self.large_job_min_cores { self.large_job_min_cores } else { 0 };
+        let candidates = Self::profile_nodes(&self.db_handler, nodes, job_profile, min_cores, produced_elsewhere).await?;
+        Ok((self.scheduler.rank_nodes(job_profile, candidates), large_job))
+    }
+
+    // Take a free slot of a node, if it has any left in this cycle
+    fn reserve_slot(node_slots: &Mutex<HashMap<i16, i64>>, node_id: &i16) -> bool {
+        let mut node_slots = node_slots.lock().unwrap_or_else(|e| e.into_inner());
+        match node_slots.get_mut(node_id) {
+            Some(slots) if *slots > 0 => { *slots -= 1; true },
+            _ => false
+        }
+    }
+
+    // Give back a slot of a node that did not accept a job
+    fn release_slot(node_slots: &Mutex<HashMap<i16, i64>>, node_id: &i16) {
+        let mut node_slots = node_slots.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(slots) = node_slots.get_mut(node_id) {
+            *slots += 1;
+        }
+    }
+
+    // Assign a job to a computing node that still has a free slot in this cycle and return the node that accepted it
     #[instrument(level="debug")]
-    pub async fn assign_job(&mut self, job_request : &JobRequest) -> Result<bool>{
-        let db_handler = &mut self.db_handler;
-        // Get idle nodes
-        let nodes = match DatabaseHandler::get_available_nodes(db_handler).await {
-            Ok(node_set) => node_set,
-            Err(e) => {println!("{}", e); Vec::<Node>::new()}
-        }; 
-        // Maximum retries for assignment
-        let mut retries = 3; 
-        if nodes.len() > 0{
-            loop { 
-                // Choose randomly one node of the currently available ones
-                let mut random_index: usize = 0;
-                if nodes.len() > 1
-                {
-                    random_index = rand::thread_rng().gen_range(0..nodes.len());
-                }
-                let random_node = &nodes[random_index];
-                // Query the status of the selected node
-                let client = GrpcClient::new([self.protocol, random_node.ip.as_str()].join(""), random_node.domain.as_str())?;
-                let status = match client.get_server_status().await{
-                    Ok(code) => code,
-                    Err(e) => {debug!("{}", e); -1}
+    async fn assign_job(&self, assignment: &JobAssignment, node_slots: &Mutex<HashMap<i16, i64>>) -> Result<Option<i16>>{
+        let db_handler = &self.db_handler;
+        let job_request = &assignment.job_request;
+        let ranking_string = assignment.ranking.iter().map(|profile| profile.node.id.to_string()).collect::<Vec<String>>().join(",");
+        debug!("Scheduling of request {} ({}): {}", job_request.request_id, self.scheduler.name(), ranking_string);
+
+        // Previous attempts of the same request that were abandoned
+        let retries = DatabaseHandler::count_requeued_jobs(db_handler, &job_request.request_id).await? as i32;
+        // Try the nodes in order of preference until the maximum number of attempts is reached
+        let max_attempts = 3;
+        for profile in assignment.ranking.iter().take(max_attempts) {
+            let selected_node = &profile.node;
+            // Skip nodes that were drained or deactivated after the ranking
+            match DatabaseHandler::get_node(db_handler, &selected_node.id).await? {
+                Some(node) if node.active && !node.drained => {},
+                _ => continue
+            }
+            // Query the status of the selected node
+            let client = GrpcClient::new([self.protocol, selected_node.ip.as_str()].join(""), selected_node.domain.as_str())?;
+            let status = match client.get_server_status().await{
+                Ok(code) => { DatabaseHandler::record_node_check(db_handler, &selected_node.id, true).await?; code },
+                Err(e) => {debug!("{}", e); self.record_failed_check(&selected_node.id).await?; -1}
+            };
+            if status != 1 || !Self::reserve_slot(node_slots, &selected_node.id) {
+                continue;
+            }
+            // Assign the job to the selected node
+            let job_status = match client.start_job(job_request).await{
+                Ok(response) => response,
+                Err(e) => {debug!("{}", e); JobStatus{ request_id: -1, status_code: -1}}
+            };
+            let status = job_status.status_code;
+            if status != 0 {
+                Self::release_slot(node_slots, &selected_node.id);
+            }
+            if status != 1 && status != 2 {
+                // Update the database for this assignment: the request is assigned to the node, or it fails if the node rejected it
+                let new_job = NewJob{ request_id: job_request.request_id, node_id: selected_node.id, 
+                                            completion_date: if status == 0 { None } else { Some(Utc::now().naive_utc()) }, 
+                                            status_code: status as i16, secure_hash: String::new(), retries};
+                let (next, reason) = match status {
+                    0 => (RequestState::Assigned, format!("accepted by node {}", selected_node.id)),
+                    _ => (RequestState::Failed, format!("rejected by node {} ({})", selected_node.id, status))
                 };
-                retries -= 1;
-                if status == 1 { 
-                    // Assign the job to the selected node
-                    let job_status = match client.start_job(job_request).await{
-                        Ok(response) => response,
-                        Err(e) => {debug!("{}", e); JobStatus{ request_id: -1, status_code: -1}}
-                    };
-                    let status = job_status.status_code;
-                    if status != 1 && status != 2 {
-                        // Update the database for this assignment
-                        let new_job = NewJob{ request_id: &job_request.request_id, node_id: &random_node.id, 
-                                                    completion_date: if status == 0 { None } else { Some(Utc::now().naive_utc()) }, 
-                                                    status_code: &(status as i8), secure_hash: &String::from("")};
-                        DatabaseHandler::insert_job(db_handler, &new_job).await?;
-                        if status == 0 {
-                            DatabaseHandler::update_node_working_state(db_handler, &random_node.id, true).await?;
-                        }
-                        else {
-                            DatabaseHandler::finalize_job(db_handler, &job_request.request_id, "", &(status as i8)).await?;
-                            DatabaseHandler::update_node_working_state(db_handler, &random_node.id, false).await?;
-                        } 
+                if let Err(e) = DatabaseHandler::insert_job(db_handler, new_job, next, &reason).await {
+                    // The request was cancelled in the meantime, so the job is stopped on the node
+                    if status == 0 {
+                        client.cancel_job(job_request.request_id, &job_request.hash).await.map_err(|e| debug!("{}", e)).ok();
                     }
-                    retries = 0
-                }
-                else {
-                    // Delay the execution of the loop
-                    thread::sleep(Duration::from_secs(15));
+                    return Err(e);
                 }
-                // Halt the assignment attempts once the maximum number of retries have been reached
-                if retries == 0 {
-                    break;
+                if status == 0 {
+                    DatabaseHandler::update_node_working_state(db_handler, &selected_node.id, true).await?;
                 }
+                else {
+                    DatabaseHandler::refresh_node_working_state(db_handler, &selected_node.id).await?;
+                } 
+                // Record the decision of the scheduler
+                let decision = NewSchedulingDecision{ request_id: job_request.request_id, node_id: selected_node.id,
+                                                      strategy: self.scheduler.name().to_string(), ranking: ranking_string,
+                                                      candidate_count: assignment.job_profile.candidate_count as i32,
+                                                      large_job: assignment.large_job };
+                DatabaseHandler::insert_scheduling_decision(db_handler, decision).await?;
+                return Ok(if status == 0 { Some(selected_node.id) } else { None });
             }
+            break;
         }
 
-        Ok(true)
+        Ok(None)
     }
-}
\ No newline at end of file
+}
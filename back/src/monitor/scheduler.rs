@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use anyhow::{bail, Result};
+use rand::seq::SliceRandom;
+use crate::db::models::{Node, QueriedRequest};
+use crate::utils;
+
+/*
+
+Schedulers decide which of the available computing nodes should receive a job. Each strategy ranks the
+candidate nodes by preference and the request monitor tries them in that order.
+
+*/
+
+// Properties of a request that are relevant to its scheduling
+#[derive(Debug, Clone)]
+pub struct JobProfile {
+    // The reference structure and the custom candidates of the request
+    pub structure_ids: Vec<String>,
+    // Estimated number of candidate structures
+    pub candidate_count: usize,
+}
+
+impl JobProfile {
+    pub fn from_request(request: &QueriedRequest) -> Self {
+        let mut structure_ids: Vec<String> = request.custom_list.split(',')
+                                                   .filter(|id| !id.is_empty())
+                                                   .map(|id| id.to_string())
+                                                   .collect();
+        let candidate_count = match &request.list_name {
+            Some(list_name) if structure_ids.is_empty() => utils::parse_list_size(list_name),
+            _ => structure_ids.len()
+        };
+        if let Some(structure_id) = request.reference.split('_').next() {
+            structure_ids.push(structure_id.to_string());
+        }
+        Self { structure_ids, candidate_count }
+    }
+}
+
+// A candidate node along with the information that the strategies use
+#[derive(Debug)]
+pub struct NodeProfile {
+    pub node: Node,
+    pub last_assignment: Option<chrono::NaiveDateTime>,
+    // How many structures of the job were processed in other nodes after the last sync of this node
+    pub missing_structures: usize,
+}
+
+pub trait Scheduler: Send + Sync + std::fmt::Debug {
+    // Name of the strategy, as used in the configuration
+    fn name(&self) -> &'static str;
+
+    // Order the candidate nodes by preference for a job
+    fn rank_nodes(&mut self, job: &JobProfile, nodes: Vec<NodeProfile>) -> Vec<NodeProfile>;
+
+    // Take note of the node of a ranking that accepted the job
+    fn record_assignment(&mut self, _ranking: &[NodeProfile], _node_id: i16) {}
+}
+
+// Create the scheduler that is selected in the configuration
+pub fn create_scheduler(strategy: &str) -> Result<Box<dyn Scheduler>> {
+    let scheduler: Box<dyn Scheduler> = match strategy {
+        "random" => Box::new(RandomScheduler),
+        "lru" => Box::new(LeastRecentlyUsedScheduler),
+        "cores" => Box::new(MostCoresScheduler),
+        "affinity" => Box::new(CacheAffinityScheduler),
+        "round_robin" => Box::new(WeightedRoundRobinScheduler::default()),
+        _ => bail!("Unknown job scheduler: {}", strategy)
+    };
+    Ok(scheduler)
+}
+
+// Any available node
+#[derive(Debug)]
+pub struct RandomScheduler;
+
+impl Scheduler for RandomScheduler {
+    fn name(&self) -> &'static str { "random" }
+
+    fn rank_nodes(&mut self, _job: &JobProfile, mut nodes: Vec<NodeProfile>) -> Vec<NodeProfile> {
+        nodes.shuffle(&mut rand::thread_rng());
+        nodes
+    }
+}
+
+// The node that has been idle for the longest time
+#[derive(Debug)]
+pub struct LeastRecentlyUsedScheduler;
+
+impl Scheduler for LeastRecentlyUsedScheduler {
+    fn name(&self) -> &'static str { "lru" }
+
+    fn rank_nodes(&mut self, _job: &JobProfile, mut nodes: Vec<NodeProfile>) -> Vec<NodeProfile> {
+        // Nodes that never received a job come first
+        nodes.sort_by_key(|profile| profile.last_assignment);
+        nodes
+    }
+}
+
+// The most powerful node, preferring the freshest cache among equals
+#[derive(Debug)]
+pub struct MostCoresScheduler;
+
+impl Scheduler for MostCoresScheduler {
+    fn name(&self) -> &'static str { "cores" }
+
+    fn rank_nodes(&mut self, _job: &JobProfile, mut nodes: Vec<NodeProfile>) -> Vec<NodeProfile> {
+        nodes.sort_by(|a, b| b.node.cores.cmp(&a.node.cores)
+                              .then(b.node.sync_date.cmp(&a.node.sync_date)));
+        nodes
+    }
+}
+
+// The node that already holds most of the structures of the job
+#[derive(Debug)]
+pub struct CacheAffinityScheduler;
+
+impl Scheduler for CacheAffinityScheduler {
+    fn name(&self) -> &'static str { "affinity" }
+
+    fn rank_nodes(&mut self, _job: &JobProfile, mut nodes: Vec<NodeProfile>) -> Vec<NodeProfile> {
+        nodes.sort_by(|a, b| a.missing_structures.cmp(&b.missing_structures)
+                              .then(b.node.sync_date.cmp(&a.node.sync_date))
+                              .then(b.node.cores.cmp(&a.node.cores)));
+        nodes
+    }
+}
+
+// Smooth weighted round-robin, using the cores of each node as its weight
+// The weights of a round are only updated when a node of its ranking accepts the job, so that the rounds without an
+// assignment do not shift the distribution
+#[derive(Debug, Default)]
+pub struct WeightedRoundRobinScheduler {
+    current_weights: HashMap<i16, i64>,
+}
+
+impl Scheduler for WeightedRoundRobinScheduler {
+    fn name(&self) -> &'static str { "round_robin" }
+
+    fn rank_nodes(&mut self, _job: &JobProfile, mut nodes: Vec<NodeProfile>) -> Vec<NodeProfile> {
+        nodes.sort_by_key(|profile| std::cmp::Reverse(self.round_weight(profile)));
+        nodes
+    }
+
+    fn record_assignment(&mut self, ranking: &[NodeProfile], node_id: i16) {
+        for profile in ranking {
+            *self.current_weights.entry(profile.node.id).or_insert(0) += Self::weight(profile);
+        }
+        // The node that received the job gives up the total weight of the round
+        let total_weight: i64 = ranking.iter().map(Self::weight).sum();
+        *self.current_weights.entry(node_id).or_insert(0) -= total_weight;
+    }
+}
+
+impl WeightedRoundRobinScheduler {
+    fn weight(profile: &NodeProfile) -> i64 {
+        i64::from(profile.node.cores.max(1))
+    }
+
+    // Current weight of a node after the increase of the round
+    fn round_weight(&self, profile: &NodeProfile) -> i64 {
+        self.current_weights.get(&profile.node.id).copied().unwrap_or(0) + Self::weight(profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn date(day: u32) -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    fn profile(id: i16, cores: i16, sync_day: u32, last_assignment: Option<u32>, missing_structures: usize) -> NodeProfile {
+        let node = Node { id, cores, sync_date: date(sync_day), active: true, ..Node::construct() };
+        NodeProfile { node, last_assignment: last_assignment.map(date), missing_structures }
+    }
+
+    fn job() -> JobProfile {
+        JobProfile { structure_ids: vec![String::from("1abc")], candidate_count: 1 }
+    }
+
+    fn ids(ranking: &[NodeProfile]) -> Vec<i16> {
+        ranking.iter().map(|profile| profile.node.id).collect()
+    }
+
+    #[test]
+    fn create_known_schedulers() {
+        for strategy in ["random", "lru", "cores", "affinity", "round_robin"] {
+            assert_eq!(create_scheduler(strategy).unwrap().name(), strategy);
+        }
+        assert!(create_scheduler("fastest").is_err());
+    }
+
+    #[test]
+    fn random_keeps_every_node() {
+        let nodes = vec![profile(1, 4, 1, None, 0), profile(2, 8, 1, None, 0), profile(3, 2, 1, None, 0)];
+        let mut ranking = ids(&RandomScheduler.rank_nodes(&job(), nodes));
+        ranking.sort();
+        assert_eq!(ranking, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn lru_prefers_the_nodes_that_never_received_a_job() {
+        let nodes = vec![profile(1, 4, 1, Some(5), 0), profile(2, 4, 1, None, 0), profile(3, 4, 1, Some(3), 0)];
+        assert_eq!(ids(&LeastRecentlyUsedScheduler.rank_nodes(&job(), nodes)), vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn cores_prefers_more_cores_then_the_latest_sync() {
+        let nodes = vec![profile(1, 4, 5, None, 0), profile(2, 8, 1, None, 0), profile(3, 4, 7, None, 0)];
+        assert_eq!(ids(&MostCoresScheduler.rank_nodes(&job(), nodes)), vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn affinity_prefers_fewer_missing_structures_then_the_latest_sync_then_more_cores() {
+        let nodes = vec![profile(1, 16, 1, None, 2), profile(2, 4, 3, None, 0), profile(3, 8, 3, None, 0),
+                         profile(4, 2, 5, None, 0)];
+        assert_eq!(ids(&CacheAffinityScheduler.rank_nodes(&job(), nodes)), vec![4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn round_robin_distributes_by_weight() {
+        let mut scheduler = WeightedRoundRobinScheduler::default();
+        let mut selected = Vec::new();
+        for _ in 0..7 {
+            let nodes = vec![profile(1, 5, 1, None, 0), profile(2, 1, 1, None, 0), profile(3, 1, 1, None, 0)];
+            let ranking = scheduler.rank_nodes(&job(), nodes);
+            scheduler.record_assignment(&ranking, ranking[0].node.id);
+            selected.push(ranking[0].node.id);
+        }
+        assert_eq!(selected, vec![1, 1, 2, 1, 3, 1, 1]);
+    }
+
+    #[test]
+    fn round_robin_charges_only_the_node_that_accepted() {
+        let mut scheduler = WeightedRoundRobinScheduler::default();
+        let nodes = || vec![profile(1, 2, 1, None, 0), profile(2, 1, 1, None, 0)];
+        // A round without an assignment leaves the weights unchanged
+        let ranking = scheduler.rank_nodes(&job(), nodes());
+        assert_eq!(ids(&ranking), vec![1, 2]);
+        assert_eq!(ids(&scheduler.rank_nodes(&job(), nodes())), vec![1, 2]);
+        // The preferred node refused the job, so the fallback node pays for the round
+        scheduler.record_assignment(&ranking, 2);
+        assert_eq!(ids(&scheduler.rank_nodes(&job(), nodes())), vec![1, 2]);
+        assert_eq!(scheduler.current_weights, HashMap::from([(1, 2), (2, -2)]));
+    }
+}
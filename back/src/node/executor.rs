@@ -0,0 +1,95 @@
+use std::{fs::File, path::Path, process::Stdio};
+use anyhow::Result;
+use tokio::process::Command;
+use tracing::debug;
+use crate::jobreceiver::JobRequest;
+
+/*
+
+Executors run the actual computation of a job on a computing node. The node server only knows the
+JobExecutor trait, so a Machaon installation, a container runtime or a mock can be plugged in.
+
+*/
+
+// A job finished without errors
+pub const EXECUTION_SUCCESS: i32 = 0;
+// The executed command failed
+pub const EXECUTION_FAILURE: i32 = -2;
+
+#[tonic::async_trait]
+pub trait JobExecutor: Send + Sync + std::fmt::Debug {
+    // Run a job inside its working directory and return its status code.
+    // The results must be left in the working directory, including the <hash>.zip archive of Machaon's outputs.
+    // A cancelled job is stopped by dropping the returned future.
+    async fn execute(&self, job_request: &JobRequest, job_directory: &Path, log_path: &Path) -> Result<i32>;
+}
+
+// Executor that runs a configurable command line for each job
+#[derive(Debug)]
+pub struct CommandExecutor {
+    command_template: String,
+    cache_path: String,
+}
+
+impl CommandExecutor {
+
+    // Create a new instance
+    // The template is split on whitespace and the following placeholders are replaced in each argument:
+    // {reference}, {request_id}, {list}, {structures}, {meta}, {go_term}, {hash}, {mode},
+    // {segment_start}, {segment_end}, {alignment}, {output}, {cache}, {meta_only}
+    // {meta_only} is true when the outputs of the comparison are already in the working directory, so that only
+    // the meta-analysis and the GO term stage have to run
+    pub fn new(command_template: String, cache_path: String) -> Self {
+        Self { command_template, cache_path }
+    }
+
+    // Fill the placeholders of an argument with the values of a job
+    fn fill_placeholders(&self, argument: &str, job_request: &JobRequest, job_directory: &Path) -> String {
+        let structure_ids: Vec<&str> = job_request.structure_ids.iter()
+                                                  .map(|s| s.as_str())
+                                                  .filter(|s| !s.is_empty())
+                                                  .collect();
+        argument.replace("{reference}", &job_request.reference_id)
+                .replace("{request_id}", &job_request.request_id.to_string())
+                .replace("{list}", &job_request.listname)
+                .replace("{structures}", &structure_ids.join(","))
+                .replace("{meta}", &job_request.meta_analysis.to_string())
+                .replace("{go_term}", &job_request.go_term)
+                .replace("{hash}", &job_request.hash)
+                .replace("{mode}", &job_request.comparison_mode.to_string())
+                .replace("{segment_start}", &job_request.segment_start.to_string())
+                .replace("{segment_end}", &job_request.segment_end.to_string())
+                .replace("{alignment}", &job_request.alignment_level.to_string())
+                .replace("{output}", &job_directory.to_string_lossy())
+                .replace("{cache}", &self.cache_path)
+                .replace("{meta_only}", &(!job_request.base_job_hash.is_empty()).to_string())
+    }
+}
+
+#[tonic::async_trait]
+impl JobExecutor for CommandExecutor {
+
+    async fn execute(&self, job_request: &JobRequest, job_directory: &Path, log_path: &Path) -> Result<i32> {
+        let arguments: Vec<String> = self.command_template.split_whitespace()
+                                         .map(|argument| self.fill_placeholders(argument, job_request, job_directory))
+                                         .collect();
+        let (program, arguments) = match arguments.split_first() {
+            Some(parts) => parts,
+            None => { debug!("Empty job command"); return Ok(EXECUTION_FAILURE); }
+        };
+
+        // The output of the command is kept next to the working directory for inspection
+        let log_file = File::create(log_path)?;
+        let status = Command::new(program)
+                            .args(arguments)
+                            .current_dir(job_directory)
+                            .kill_on_drop(true)
+                            .stdout(Stdio::from(log_file.try_clone()?))
+                            .stderr(Stdio::from(log_file))
+                            .status()
+                            .await?;
+        debug!("Job {} exited with {}", job_request.request_id, status);
+
+        Ok(if status.success() { EXECUTION_SUCCESS } else { EXECUTION_FAILURE })
+    }
+}
@@ -0,0 +1,593 @@
+use std::{collections::HashMap, env, fs, io::{Read, Seek, SeekFrom}, net::SocketAddr, path::{Component, Path, PathBuf}, pin::Pin, sync::Arc, time::Duration};
+use anyhow::{bail, Result};
+use futures::Stream;
+use prost::bytes::BytesMut;
+use sha2::{Digest, Sha256};
+use tokio::{io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt}, sync::Mutex, task::JoinHandle};
+use tonic::{transport::{Certificate, Identity, Server, ServerTlsConfig}, Request, Response, Status, Streaming};
+use tracing::{debug, instrument};
+use uuid::Uuid;
+use crate::{jobreceiver::{job_receiver_server::{JobReceiver, JobReceiverServer}, job_result::JobData,
+                          cache_upload::UploadData, uncached_data::SyncData, CacheFile, CacheManifest, CacheUpload, CancelRequest,
+                          FileChunk, JobDetails, JobProgress, JobRequest, JobResult, JobStatus, ResultRequest,
+                          ServerStatus, StatusRequest, UncachedData}, grpc::GrpcClient, utils};
+use self::executor::{JobExecutor, EXECUTION_FAILURE, EXECUTION_SUCCESS};
+
+pub mod executor;
+
+/*
+
+This module is a reference implementation of a computing node in the MachaonWeb network. It serves the
+JobReceiver gRPC service over mutual TLS, runs a bounded number of concurrent jobs through a pluggable executor, streams the
+compressed results back to the root node and receives the cache updates of the node synchronization.
+
+*/
+
+// Server status codes, as interpreted by the monitors of the root node
+pub const NODE_BUSY: i32 = 0;
+pub const NODE_IDLE: i32 = 1;
+
+// Status codes of a job (GetStatus with a hash)
+pub const JOB_RUNNING: i32 = 0;
+pub const JOB_FINISHED: i32 = 1;
+pub const JOB_CANCELLED: i32 = 2;
+pub const JOB_UNKNOWN: i32 = -1;
+
+// Status codes of a job assignment (StartJob)
+pub const JOB_STARTED: i32 = 0;
+pub const JOB_REJECTED_BUSY: i32 = 1;
+pub const JOB_REJECTED_SYNCING: i32 = 2;
+pub const JOB_INVALID_REFERENCE: i32 = 3;
+pub const JOB_MALFORMED: i32 = 4;
+pub const JOB_INVALID_LIST: i32 = 5;
+
+// Status codes of a result download (DownloadResult), alongside the executor's codes
+pub const RESULT_UNKNOWN: i32 = -1;
+pub const RESULT_MISSING: i32 = -3;
+pub const RESULT_CANCELLED: i32 = -4;
+
+// Status codes of a cancellation (CancelJob)
+pub const CANCEL_SUCCESS: i32 = 0;
+pub const CANCEL_UNKNOWN: i32 = -1;
+
+// Status codes of a synchronization
+pub const SYNC_SUCCESS: i32 = 0;
+pub const SYNC_FAILURE: i32 = -1;
+
+// Cache directories that the node synchronization fills
+const CACHE_DIRECTORIES: [&str; 3] = ["PDBs_new", "DATA_PDBs_new_whole", "DATA_PDBs_new_domain"];
+// Directory of the interrupted uploads inside the cache, named after the hash of each file
+const UPLOAD_DIRECTORY: &str = ".uploads";
+
+// Size of the chunks of a streamed result archive
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Interval between the progress messages of a watched job
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
+// Maximum number of log lines in a progress message
+const MAX_LOG_LINES: usize = 20;
+
+// Interval between the attempts to register the node with the root node
+const REGISTRATION_INTERVAL: Duration = Duration::from_secs(30);
+
+// Outcome of a job that is no longer running
+#[derive(Debug, Clone)]
+struct FinishedJob {
+    request_id: i64,
+    status_code: i32,
+    archive_path: PathBuf,
+    secure_hash: String,
+}
+
+#[derive(Debug, Default)]
+struct NodeState {
+    // Request ids of the running jobs by their hash
+    running_jobs: HashMap<String, i64>,
+    // Tasks of the running jobs by their hash, kept for cancellation
+    job_tasks: HashMap<String, JoinHandle<()>>,
+    synchronizing: bool,
+    finished_jobs: HashMap<String, FinishedJob>,
+}
+
+#[derive(Debug)]
+pub struct NodeServer {
+    ip: [u8; 4],
+    port: u16,
+    cache_path: PathBuf,
+    work_path: PathBuf,
+    max_jobs: usize,
+    executor: Arc<dyn JobExecutor>,
+    state: Arc<Mutex<NodeState>>,
+}
+
+impl NodeServer {
+
+    // Create a new instance
+    pub fn new(ip_string: String, port: u16, cache_path: &str, work_path: &str, max_jobs: usize, 
+               executor: Arc<dyn JobExecutor>) -> Result<Self> {
+        let collected_ip = ip_string.split('.')
+                            .filter_map(|s| s.parse::<u8>().ok())
+                            .collect::<Vec<u8>>();
+        let mut ip: [u8; 4] = [0; 4];
+        ip.copy_from_slice(&collected_ip[..4]);
+
+        // Creating the cache directories that the node synchronization fills
+        for directory in CACHE_DIRECTORIES.iter().chain([UPLOAD_DIRECTORY].iter()) {
+            fs::create_dir_all(Path::new(cache_path).join(directory))?;
+        }
+        fs::create_dir_all(work_path)?;
+
+        Ok(Self { ip, port, cache_path: PathBuf::from(cache_path), work_path: PathBuf::from(work_path),
+                  max_jobs: max_jobs.max(1), executor, state: Arc::new(Mutex::new(NodeState::default())) })
+    }
+
+    pub async fn start(self) -> Result<bool> {
+        // Mutual TLS configuration: the node presents its own certificate and accepts only clients signed by the local CA
+        let data_dir = PathBuf::from(env::var("MTLS_CERTS_PATH")?);
+        let node_name = env::var("NODE_CERT_NAME")?;
+        let client_ca_cert = Certificate::from_pem(fs::read_to_string(data_dir.join("machaonlocalca.cert"))?);
+        let server_cert = fs::read_to_string(data_dir.join([&node_name, ".cert"].join("")))?;
+        let server_key = fs::read_to_string(data_dir.join([&node_name, ".key"].join("")))?;
+        let tls = ServerTlsConfig::new()
+                    .identity(Identity::from_pem(server_cert, server_key))
+                    .client_ca_root(client_ca_cert);
+
+        let node_socket = SocketAddr::from((self.ip, self.port));
+        // Remove the TLS configuration for unencrypted gRPC (debugging)
+        Server::builder()
+            .tls_config(tls)?
+            .add_service(JobReceiverServer::new(self))
+            .serve(node_socket)
+            .await?;
+        Ok(true)
+    }
+
+    // Announce the node to the registry of the root node, retrying until the root node accepts or rejects it
+    // The node presents its own certificate and announces the address and the domain that the root node should use
+    pub async fn register(registry_url: String, registry_domain: String, cert_name: String, address: String, domain: String) {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        let cores = i32::from(i16::try_from(cores).unwrap_or(i16::MAX));
+        loop {
+            let status = match GrpcClient::with_identity(registry_url.clone(), &registry_domain, &cert_name) {
+                Ok(client) => client.register_node(&address, &domain, cores).await,
+                Err(e) => Err(e)
+            };
+            match status {
+                Ok(0) => { println!("Node registered as {}.", address); break; },
+                Ok(-2) => { println!("Node registration rejected: {}", address); break; },
+                Ok(code) => debug!("Node registration failed: {}", code),
+                Err(e) => debug!("Node registration failed: {}", e)
+            }
+            tokio::time::sleep(REGISTRATION_INTERVAL).await;
+        }
+    }
+
+    // Check the parameters of a job before its execution
+    fn validate_job(job_request: &JobRequest) -> i32 {
+        let has_structures = job_request.structure_ids.iter().any(|id| !id.is_empty());
+        // The hash names the files of the job, so it must not contain any path components
+        let valid_hash = !job_request.hash.is_empty() && job_request.hash.chars().all(char::is_alphanumeric)
+                         && job_request.base_job_hash.chars().all(char::is_alphanumeric);
+        if !job_request.reference_id.contains('_') {
+            JOB_INVALID_REFERENCE
+        }
+        else if !valid_hash || !(0..=2).contains(&job_request.comparison_mode) {
+            JOB_MALFORMED
+        }
+        else if job_request.listname.is_empty() && !has_structures {
+            JOB_INVALID_LIST
+        }
+        else {
+            JOB_STARTED
+        }
+    }
+
+    // Execute a job and compress its results for the download by the root node
+    async fn run_job(executor: Arc<dyn JobExecutor>, state: Arc<Mutex<NodeState>>, mut job_request: JobRequest, work_path: PathBuf) {
+        let job_directory = work_path.join(&job_request.hash);
+        let archive_path = work_path.join([&job_request.hash, ".zip"].join(""));
+        let log_path = work_path.join([&job_request.hash, ".log"].join(""));
+        let mut secure_hash = String::from("");
+
+        // Start from the outputs of the finished job with the same comparison, if the node still has its archive.
+        // Otherwise the job runs completely.
+        if !job_request.base_job_hash.is_empty() && !Self::restore_base_outputs(&job_request.base_job_hash, &job_directory, &work_path) {
+            job_request.base_job_hash.clear();
+        }
+
+        let mut status_code = match executor.execute(&job_request, &job_directory, &log_path).await {
+            Ok(code) => code,
+            Err(e) => { debug!("Job {} failed: {}", job_request.request_id, e); EXECUTION_FAILURE }
+        };
+        if status_code == EXECUTION_SUCCESS {
+            status_code = match Self::archive_results(&job_directory, &archive_path) {
+                Ok(hash) => { secure_hash = hash; EXECUTION_SUCCESS },
+                Err(e) => { debug!("Archiving job {} failed: {}", job_request.request_id, e); RESULT_MISSING }
+            };
+        }
+
+        let mut node_state = state.lock().await;
+        node_state.finished_jobs.insert(job_request.hash.clone(), FinishedJob { request_id: job_request.request_id,
+                                        status_code, archive_path, secure_hash });
+        node_state.running_jobs.remove(&job_request.hash);
+        node_state.job_tasks.remove(&job_request.hash);
+    }
+
+    // Extract the archive of a finished job into the working directory of a new job
+    fn restore_base_outputs(base_job_hash: &str, job_directory: &Path, work_path: &Path) -> bool {
+        let base_archive = work_path.join([base_job_hash, ".zip"].join(""));
+        if !base_archive.exists() {
+            debug!("The outputs of job {} are not available", base_job_hash);
+            return false;
+        }
+        match utils::extract_archive(&base_archive.to_string_lossy(), &job_directory.to_string_lossy()) {
+            Ok(_) => true,
+            Err(e) => {
+                debug!("Restoring the outputs of job {} failed: {}", base_job_hash, e);
+                // Discard the partially extracted files
+                if let Err(e) = fs::remove_dir_all(job_directory).and_then(|_| fs::create_dir_all(job_directory)) {
+                    debug!("{}", e);
+                }
+                false
+            }
+        }
+    }
+
+    // Status of a job as reported by GetStatus and WatchJob
+    fn job_status(node_state: &NodeState, hash: &str, request_id: i64) -> i32 {
+        if node_state.running_jobs.get(hash) == Some(&request_id) {
+            JOB_RUNNING
+        }
+        else if let Some(job) = node_state.finished_jobs.get(hash).filter(|job| job.request_id == request_id) {
+            if job.status_code == RESULT_CANCELLED { JOB_CANCELLED } else { JOB_FINISHED }
+        }
+        else {
+            JOB_UNKNOWN
+        }
+    }
+
+    // Read the complete lines that were appended to a job log after an offset and return the new offset
+    fn read_log_lines(log_path: &Path, offset: u64) -> Result<(Vec<String>, u64)> {
+        if !log_path.exists() {
+            return Ok((Vec::new(), offset));
+        }
+        let mut log_file = fs::File::open(log_path)?;
+        log_file.seek(SeekFrom::Start(offset))?;
+        let mut buffer = Vec::new();
+        log_file.read_to_end(&mut buffer)?;
+        // An incomplete last line is read again in the next message
+        let complete_length = match buffer.iter().rposition(|byte| *byte == b'\n') {
+            Some(position) => position + 1,
+            None => 0
+        };
+        let lines = String::from_utf8_lossy(&buffer[..complete_length]).lines()
+                          .map(|line| line.to_string())
+                          .collect();
+        Ok((lines, offset + complete_length as u64))
+    }
+
+    // Job commands report their progress with lines of the form "PROGRESS <stage> <percentage>"
+    fn parse_progress(line: &str) -> Option<(String, i32)> {
+        let mut parts = line.strip_prefix("PROGRESS ")?.split_whitespace();
+        let stage = parts.next()?.to_string();
+        let percentage = parts.next()?.trim_end_matches('%').parse::<i32>().ok()?;
+        Some((stage, percentage.clamp(0, 100)))
+    }
+
+    // Compress the working directory of a job and compute the SHA-256 hash of the archive
+    fn archive_results(job_directory: &Path, archive_path: &Path) -> Result<String> {
+        let archive_ready = utils::compress_directory(&job_directory.to_string_lossy(), &archive_path.to_string_lossy())?;
+        if !archive_ready || !archive_path.exists() {
+            bail!("No results were produced in {}", job_directory.display());
+        }
+        utils::compute_file_hash(&archive_path.to_string_lossy())
+    }
+
+    // Resolve the path of a cache file, which must be a file inside one of the cache directories
+    fn resolve_cache_path(&self, relative_path: &str) -> Option<PathBuf> {
+        let path = Path::new(relative_path);
+        let mut components = path.components();
+        let in_cache_directory = match components.next() {
+            Some(Component::Normal(directory)) => CACHE_DIRECTORIES.iter().any(|name| directory == *name),
+            _ => false
+        };
+        let normal_file = components.clone().count() > 0 && components.all(|component| matches!(component, Component::Normal(_)));
+        if in_cache_directory && normal_file { Some(self.cache_path.join(path)) } else { None }
+    }
+
+    // Path of the partial upload of a file with the given hash
+    fn upload_path(&self, hash: &str) -> Option<PathBuf> {
+        let valid_hash = hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit());
+        if valid_hash { Some(self.cache_path.join(UPLOAD_DIRECTORY).join([hash, ".part"].join(""))) } else { None }
+    }
+
+    // Store an uploaded file in the cache after verifying its chunks and its hash
+    // An interrupted upload is kept and resumed when the next upload of the same file starts from its end
+    async fn receive_cache_file(&self, mut stream: Streaming<CacheUpload>) -> Result<i32> {
+        let file_info = match stream.message().await? {
+            Some(CacheUpload { upload_data: Some(UploadData::FileInfo(info)) }) => info,
+            _ => bail!("Upload without file information")
+        };
+        let (target_path, upload_path) = match (self.resolve_cache_path(&file_info.path), self.upload_path(&file_info.hash)) {
+            (Some(target_path), Some(upload_path)) => (target_path, upload_path),
+            _ => bail!("Invalid cache file: {}", file_info.path)
+        };
+
+        // Hash the part that was received previously, so that the whole file is verified at the end
+        let mut hasher = Sha256::new();
+        let mut offset: i64 = 0;
+        if file_info.received > 0 {
+            if !upload_path.exists() || fs::metadata(&upload_path)?.len() as i64 != file_info.received {
+                bail!("Upload of {} cannot be resumed from {}", file_info.path, file_info.received);
+            }
+            offset = std::io::copy(&mut fs::File::open(&upload_path)?, &mut hasher)? as i64;
+        }
+        let mut upload_file = tokio::fs::OpenOptions::new()
+                                    .create(true)
+                                    .write(true)
+                                    .append(offset > 0)
+                                    .truncate(offset == 0)
+                                    .open(&upload_path)
+                                    .await?;
+
+        while let Some(message) = stream.message().await? {
+            if let Some(UploadData::Chunk(chunk)) = message.upload_data {
+                if chunk.offset != offset || utils::compute_chunk_hash(&chunk.data) != chunk.checksum {
+                    bail!("Corrupt chunk at offset {} of {}", chunk.offset, file_info.path);
+                }
+                upload_file.write_all(&chunk.data).await?;
+                hasher.update(&chunk.data);
+                offset += chunk.data.len() as i64;
+            }
+        }
+        upload_file.flush().await?;
+
+        if offset < file_info.size {
+            debug!("Incomplete upload of {}: {} of {} bytes", file_info.path, offset, file_info.size);
+            return Ok(SYNC_FAILURE);
+        }
+        if format!("{:x}", hasher.finalize()) != file_info.hash {
+            debug!("Uploaded file {} does not match its hash", file_info.path);
+            fs::remove_file(&upload_path)?;
+            return Ok(SYNC_FAILURE);
+        }
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // The upload directory is inside the cache, so the file appears at once
+        fs::rename(&upload_path, &target_path)?;
+        Ok(SYNC_SUCCESS)
+    }
+
+    // Store an uploaded archive, verify its hash and unpack it into the cache directories
+    async fn receive_cache_update(&self, mut stream: Streaming<UncachedData>) -> Result<i32> {
+        let archive_path = self.work_path.join([Uuid::new_v4().to_string().as_str(), ".zip"].join(""));
+        let mut archive = tokio::fs::File::create(&archive_path).await?;
+        let mut hasher = Sha256::new();
+        let mut expected_hash = String::from("");
+
+        while let Some(message) = stream.message().await? {
+            match message.sync_data {
+                Some(SyncData::SecureHash(hash)) => { expected_hash = hash; },
+                Some(SyncData::ChunkData(chunk)) => {
+                    hasher.update(&chunk);
+                    archive.write_all(&chunk).await?;
+                },
+                None => {}
+            }
+        }
+        archive.flush().await?;
+
+        let mut status = SYNC_FAILURE;
+        if format!("{:x}", hasher.finalize()) == expected_hash {
+            utils::extract_archive(&archive_path.to_string_lossy(), &self.cache_path.to_string_lossy())?;
+            status = SYNC_SUCCESS;
+        }
+        else {
+            debug!("Synchronization archive does not match its hash");
+        }
+        fs::remove_file(&archive_path)?;
+        Ok(status)
+    }
+}
+
+#[tonic::async_trait]
+impl JobReceiver for NodeServer {
+
+    type DownloadResultStream = Pin<Box<dyn Stream<Item = Result<JobResult, Status>> + Send>>;
+    type WatchJobStream = Pin<Box<dyn Stream<Item = Result<JobProgress, Status>> + Send>>;
+
+    // Accept a job if the node is idle and start its execution in the background
+    #[instrument(level="debug", skip(self))]
+    async fn start_job(&self, request: Request<JobRequest>) -> Result<Response<JobStatus>, Status> {
+        let job_request = request.into_inner();
+        let mut node_state = self.state.lock().await;
+        let mut status_code = Self::validate_job(&job_request);
+        if node_state.running_jobs.len() >= self.max_jobs || node_state.running_jobs.contains_key(&job_request.hash) {
+            status_code = JOB_REJECTED_BUSY;
+        }
+        else if node_state.synchronizing {
+            status_code = JOB_REJECTED_SYNCING;
+        }
+
+        if status_code == JOB_STARTED {
+            // Start from a clean working directory
+            let job_directory = self.work_path.join(&job_request.hash);
+            if job_directory.exists() {
+                fs::remove_dir_all(&job_directory)?;
+            }
+            fs::create_dir_all(&job_directory)?;
+
+            node_state.running_jobs.insert(job_request.hash.clone(), job_request.request_id);
+            node_state.finished_jobs.remove(&job_request.hash);
+            let task = tokio::spawn(Self::run_job(self.executor.clone(), self.state.clone(), job_request.clone(), self.work_path.clone()));
+            node_state.job_tasks.insert(job_request.hash.clone(), task);
+        }
+
+        Ok(Response::new(JobStatus { request_id: job_request.request_id, status_code }))
+    }
+
+    // Report whether the node can accept a new job or whether a specific job has finished
+    async fn get_status(&self, request: Request<StatusRequest>) -> Result<Response<ServerStatus>, Status> {
+        let status_request = request.into_inner();
+        let node_state = self.state.lock().await;
+        let status_code = if status_request.hash.is_empty() {
+            if node_state.running_jobs.len() < self.max_jobs && !node_state.synchronizing { NODE_IDLE } else { NODE_BUSY }
+        }
+        else {
+            Self::job_status(&node_state, &status_request.hash, status_request.request_id)
+        };
+        Ok(Response::new(ServerStatus { status_code }))
+    }
+
+    // Stream the stage, the percentage and the new log lines of a job until it is no longer running
+    #[instrument(level="debug", skip(self))]
+    async fn watch_job(&self, request: Request<StatusRequest>) -> Result<Response<Self::WatchJobStream>, Status> {
+        let status_request = request.into_inner();
+        let state = self.state.clone();
+        let log_path = self.work_path.join([&status_request.hash, ".log"].join(""));
+
+        let stream = async_stream::try_stream! {
+            let mut log_offset = 0;
+            let mut stage = String::from("running");
+            let mut percentage = 0;
+            loop {
+                let status_code = Self::job_status(&*state.lock().await, &status_request.hash, status_request.request_id);
+                let (lines, offset) = Self::read_log_lines(&log_path, log_offset).map_err(|e| Status::internal(e.to_string()))?;
+                log_offset = offset;
+                let mut log_lines = Vec::new();
+                for line in lines {
+                    match Self::parse_progress(&line) {
+                        Some((line_stage, line_percentage)) => { stage = line_stage; percentage = line_percentage; },
+                        None => log_lines.push(line)
+                    }
+                }
+                if log_lines.len() > MAX_LOG_LINES {
+                    log_lines.drain(..log_lines.len() - MAX_LOG_LINES);
+                }
+                match status_code {
+                    JOB_FINISHED => { stage = String::from("finished"); percentage = 100; },
+                    JOB_CANCELLED => { stage = String::from("cancelled"); },
+                    JOB_UNKNOWN => { stage = String::from("unknown"); },
+                    _ => {}
+                }
+                yield JobProgress { request_id: status_request.request_id, status_code, stage: stage.clone(), percentage, log_lines };
+                if status_code != JOB_RUNNING {
+                    break;
+                }
+                tokio::time::sleep(PROGRESS_INTERVAL).await;
+            }
+        };
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    // Stream the details of a finished job followed by the chunks of its result archive
+    #[instrument(level="debug", skip(self))]
+    async fn download_result(&self, request: Request<ResultRequest>) -> Result<Response<Self::DownloadResultStream>, Status> {
+        let result_request = request.into_inner();
+        let finished_job = self.state.lock().await.finished_jobs.get(&result_request.hash)
+                               .filter(|job| job.request_id == result_request.request_id)
+                               .cloned();
+        let mut job_details = JobDetails { request_id: result_request.request_id, hash: result_request.hash,
+                                           secure_hash: String::from(""), status_code: RESULT_UNKNOWN, file_size: 0 };
+        let mut archive_path = None;
+        if let Some(job) = finished_job {
+            job_details.status_code = job.status_code;
+            job_details.secure_hash = job.secure_hash;
+            if job.status_code == EXECUTION_SUCCESS {
+                job_details.file_size = fs::metadata(&job.archive_path)?.len() as i64;
+                archive_path = Some(job.archive_path);
+            }
+        }
+        // Resuming past the end of the archive sends no chunks
+        let mut offset = result_request.offset.max(0);
+
+        let stream = async_stream::try_stream! {
+            yield JobResult { job_data: Some(JobData::FileInfo(job_details)) };
+            if let Some(path) = archive_path {
+                let mut archive = tokio::fs::File::open(path).await.map_err(Status::from)?;
+                archive.seek(SeekFrom::Start(offset as u64)).await.map_err(Status::from)?;
+                loop {
+                    let mut buf = BytesMut::with_capacity(CHUNK_SIZE);
+                    let bytes_read = archive.read_buf(&mut buf).await.map_err(Status::from)?;
+                    if bytes_read == 0 {
+                        break;
+                    }
+                    let chunk = FileChunk { offset, checksum: utils::compute_chunk_hash(&buf), data: buf.to_vec() };
+                    offset += bytes_read as i64;
+                    yield JobResult { job_data: Some(JobData::Chunk(chunk)) };
+                }
+            }
+        };
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    // Stop a running job and discard its working directory
+    #[instrument(level="debug", skip(self))]
+    async fn cancel_job(&self, request: Request<CancelRequest>) -> Result<Response<JobStatus>, Status> {
+        let cancel_request = request.into_inner();
+        let mut node_state = self.state.lock().await;
+        let mut status_code = CANCEL_UNKNOWN;
+        if node_state.running_jobs.get(&cancel_request.hash) == Some(&cancel_request.request_id) {
+            // Aborting the task drops the executor, which stops the job's process
+            if let Some(task) = node_state.job_tasks.remove(&cancel_request.hash) {
+                task.abort();
+            }
+            node_state.running_jobs.remove(&cancel_request.hash);
+            node_state.finished_jobs.insert(cancel_request.hash.clone(), FinishedJob { request_id: cancel_request.request_id,
+                                            status_code: RESULT_CANCELLED, archive_path: PathBuf::new(), secure_hash: String::from("") });
+            let job_directory = self.work_path.join(&cancel_request.hash);
+            if job_directory.exists() {
+                fs::remove_dir_all(&job_directory)?;
+            }
+            status_code = CANCEL_SUCCESS;
+        }
+        Ok(Response::new(JobStatus { request_id: cancel_request.request_id, status_code }))
+    }
+
+    // Report which of the given cache files the node holds, along with their hashes and any interrupted uploads
+    #[instrument(level="debug", skip(self, request))]
+    async fn get_manifest(&self, request: Request<CacheManifest>) -> Result<Response<CacheManifest>, Status> {
+        let mut files = Vec::new();
+        for requested_file in request.into_inner().files {
+            let mut cache_file = CacheFile { path: requested_file.path, hash: String::from(""), size: 0, received: 0 };
+            if let Some(path) = self.resolve_cache_path(&cache_file.path).filter(|path| path.is_file()) {
+                cache_file.hash = utils::compute_file_hash(&path.to_string_lossy()).map_err(|e| Status::internal(e.to_string()))?;
+                cache_file.size = fs::metadata(&path)?.len() as i64;
+            }
+            if let Some(upload_path) = self.upload_path(&requested_file.hash).filter(|path| path.is_file()) {
+                cache_file.received = fs::metadata(&upload_path)?.len() as i64;
+            }
+            files.push(cache_file);
+        }
+        Ok(Response::new(CacheManifest { files }))
+    }
+
+    // Receive a file of the cache in chunks
+    async fn upload_file(&self, request: Request<Streaming<CacheUpload>>) -> Result<Response<ServerStatus>, Status> {
+        let status_code = match self.receive_cache_file(request.into_inner()).await {
+            Ok(code) => code,
+            Err(e) => { debug!("Upload failed: {}", e); SYNC_FAILURE }
+        };
+        Ok(Response::new(ServerStatus { status_code }))
+    }
+
+    // Receive the uncached data that other nodes produced
+    async fn synchronize(&self, request: Request<Streaming<UncachedData>>) -> Result<Response<ServerStatus>, Status> {
+        {
+            let mut node_state = self.state.lock().await;
+            if !node_state.running_jobs.is_empty() || node_state.synchronizing {
+                return Ok(Response::new(ServerStatus { status_code: SYNC_FAILURE }));
+            }
+            node_state.synchronizing = true;
+        }
+
+        let status_code = match self.receive_cache_update(request.into_inner()).await {
+            Ok(code) => code,
+            Err(e) => { debug!("Synchronization failed: {}", e); SYNC_FAILURE }
+        };
+        self.state.lock().await.synchronizing = false;
+
+        Ok(Response::new(ServerStatus { status_code }))
+    }
+}
@@ -1,8 +1,9 @@
-use axum::{http::{HeaderValue, Method}, response::IntoResponse, routing::{get, post}, Router, extract::State, http::{StatusCode, self}};
-use std::{net::SocketAddr, sync::Arc,  path::PathBuf, collections::HashMap, env};
+use axum::{body::{Body, StreamBody}, http::{HeaderMap, HeaderValue, Method, Request}, response::{IntoResponse, Response, sse::{Event, KeepAlive, Sse}}, routing::{get, post, put}, Router, Extension, extract::{ConnectInfo, State, rejection::{JsonRejection, PathRejection, QueryRejection}}, http::{StatusCode, self}, middleware::{self, Next}};
+use std::{net::SocketAddr, sync::Arc,  path::PathBuf, env};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use crate::{db::{dbhandler::DatabaseHandler, models::{CandidateList, FinalizedRequest}}, logic::{self, RequestResponse, Info, RequestResult}};
+use crate::{db::dbhandler::DatabaseHandler, error::{ApiError, ApiResult}, logic::{self, admin, apikeys, auth::{self, AdminIdentity}, batch, input::{BatchInput, RequestInput}, results, status, tables::{self, TableQuery}, verifier::{self, HumanVerifier}}};
+use futures::{Stream, StreamExt};
 use tracing::{debug, Instrument};
 use axum_server::tls_rustls::RustlsConfig; 
 use tower_http::{
@@ -11,6 +12,11 @@ use tower_http::{
     cors::CorsLayer
 };
 use dotenvy::dotenv;
+use tokio_util::io::ReaderStream;
+use self::ratelimit::RateLimiter;
+
+pub mod ranges;
+pub mod ratelimit;
 
 /*
 
@@ -18,6 +24,11 @@ This module coordinates the endpoints of MachaonWeb's REST API.
 
 */
 
+// Maximum size in bytes of the body of an administrative request
+const ADMIN_BODY_LIMIT: usize = 1024 * 1024;
+// Maximum size in bytes of the payload that is recorded in the audit log
+const AUDIT_PAYLOAD_LIMIT: usize = 4096;
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Config {
     http_port: u16,
@@ -29,6 +40,12 @@ pub struct AppServer{
     ip: [u8; 4],
     https_port: u16,
     output_path: String,
+    protocol: String,
+    admin_session_minutes: i64,
+    // Cores of a job slot of the nodes, for the estimates of the queued requests
+    cores_per_job: i64,
+    verifier: Box<dyn HumanVerifier>,
+    rate_limiter: RateLimiter,
     db_handler:  DatabaseHandler,
 }
 
@@ -36,14 +53,18 @@ pub struct AppServer{
 impl AppServer{
  
     // Create a new instance
-    pub fn new(ip_string: String, https_port : u16, output_path: String) -> Result<Self> {
+    pub fn new(ip_string: String, https_port : u16, output_path: String, protocol: String) -> Result<Self> {
         let db_handler = DatabaseHandler::new()?;
         let collected_ip = ip_string.split(".")
                             .filter_map(|s| s.parse::<u8>().ok())
                             .collect::<Vec<u8>>();
         let mut ip: [u8; 4] = [0; 4];
         ip.copy_from_slice(&collected_ip[..4]);
-        Ok(Self { ip, https_port, output_path, db_handler })
+        let admin_session_minutes = env::var("ADMIN_SESSION_MINUTES")?.parse::<i64>()?;
+        let cores_per_job = env::var("CORES_PER_JOB")?.parse::<i64>()?.max(1);
+        let verifier = verifier::create_verifier(&env::var("HUMAN_VERIFIER")?)?;
+        let rate_limiter = RateLimiter::new(&env::var("RATE_LIMITS")?, &env::var("RATE_LIMIT_STORE")?, db_handler.clone())?;
+        Ok(Self { ip, https_port, output_path, protocol, admin_session_minutes, cores_per_job, verifier, rate_limiter, db_handler })
     }
 
     pub async fn start(self) -> Result<bool> {
@@ -61,15 +82,61 @@ impl AppServer{
                     .not_found_service(ServeFile::new(&frontend_path.join("index.html")));
 
         let app_socket = SocketAddr::from((self.ip, self.https_port));
+        let state = Arc::new(self);
+        // Administrative endpoints for the computing nodes
+        let admin_router = Router::new()
+            .route("/nodes", get(Self::list_nodes).post(Self::add_node))
+            .route("/nodes/:node_id", put(Self::update_node).delete(Self::remove_node))
+            .route("/nodes/:node_id/drain", post(Self::drain_node))
+            .route("/nodes/:node_id/deactivate", post(Self::deactivate_node))
+            .route("/nodes/:node_id/activate", post(Self::activate_node))
+            .route("/api-keys", get(Self::list_api_keys).post(Self::issue_api_key))
+            .route("/api-keys/:key_id", put(Self::update_api_key).delete(Self::revoke_api_key))
+            .route("/api-keys/:key_id/requests", get(Self::get_api_key_history))
+            .route("/database", get(Self::get_database_metrics))
+            .route("/logout", post(Self::logout))
+            .route_layer(middleware::from_fn_with_state(state.clone(), Self::authorize_admin))
+            .route_layer(middleware::from_fn_with_state((state.clone(), "admin"), Self::limit_rate))
+            // The login is the only administrative endpoint without a session
+            .route("/login", post(Self::login).route_layer(middleware::from_fn_with_state((state.clone(), "login"), Self::limit_rate)));
+        // Version 1 of the public endpoints
+        let api_router = Router::new()
+            .route("/requests/:hash/:req_id", get(Self::fetch_result))
+            .route("/requests/:hash/:req_id/cancel", post(Self::cancel_request))
+            .route("/requests/:hash/:req_id/progress", get(Self::watch_progress))
+            .route("/requests/:hash/:req_id/status", get(Self::get_request_status))
+            .route("/requests/:hash/:req_id/download", get(Self::download_result))
+            .route("/requests/:hash/:req_id/files", get(Self::list_result_files))
+            .route("/requests/:hash/:req_id/files/*file_path", get(Self::download_result_file))
+            .route("/requests/:hash/:req_id/tables", get(Self::list_result_tables))
+            .route("/requests/:hash/:req_id/tables/*table_path", get(Self::query_result_table))
+            .route("/challenge", get(Self::get_challenge))
+            .route("/key/requests", get(Self::get_key_history))
+            .route("/info", get(Self::get_info))
+            .route("/lists", get(Self::get_candidate_lists))
+            .route("/batches/:batch_id", get(Self::get_batch_status))
+            .route("/batches/:batch_id/download", get(Self::download_batch))
+            .route_layer(middleware::from_fn_with_state((state.clone(), "api"), Self::limit_rate))
+            .route("/requests", post(Self::receive_request).route_layer(middleware::from_fn_with_state((state.clone(), "submit"), Self::limit_rate)))
+            .route("/batches", post(Self::receive_batch).route_layer(middleware::from_fn_with_state((state.clone(), "batch"), Self::limit_rate)));
+        // Unversioned endpoints of earlier clients
+        let legacy_router = Router::new()
+            .route("/resultdata/:hash/:req_id",get(Self::fetch_result))
+            .route("/cancel/:hash/:req_id", post(Self::cancel_request))
+            .route("/progress/:hash/:req_id", get(Self::watch_progress))
+            .route("/status/:hash/:req_id", get(Self::get_request_status))
+            .route("/info", get(Self::get_info))
+            .route("/lists", get(Self::get_candidate_lists))
+            .route_layer(middleware::from_fn_with_state((state.clone(), "api"), Self::limit_rate))
+            .route("/request", post(Self::receive_legacy_request).route_layer(middleware::from_fn_with_state((state.clone(), "submit"), Self::limit_rate)));
         let router = Router::new()
             //static file serving
             .nest_service("/", serve_dir.clone())
             .fallback_service(serve_dir)
             // endpoint routing 
-            .route("/resultdata/:hash/:req_id",get(Self::fetch_result))
-            .route("/request", post(Self::receive_request))
-            .route("/info", get(Self::get_info))
-            .route("/lists", get(Self::get_candidate_lists))
+            .nest("/api/v1", api_router)
+            .merge(legacy_router)
+            .nest("/admin", admin_router)
             // Cross-Origin access configuration (debugging)
             .layer(CorsLayer::new()
                     .allow_origin(env::var("CORS_URL1")?.parse::<HeaderValue>().unwrap())
@@ -82,83 +149,544 @@ impl AppServer{
             .allow_headers([http::header::CONTENT_TYPE]),
     )       
             .layer(TraceLayer::new_for_http())
-            .with_state(Arc::new(self));
+            .with_state(state);
 
         // HTTPS only, use axum::Server::bind(&app_socket) for unencrypted HTTP
         axum_server::bind_rustls(app_socket, ssl_config)
-        .serve(router.into_make_service())
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
         .await?;  
         Ok(true)
     }
 
     // Endpoint for retrieving the results of a request
     async fn fetch_result(state: State<Arc<AppServer>>,
-        axum::extract::Path((hash, request_id)):
-            axum::extract::Path<(String, i64)>
-    ) -> impl IntoResponse {
-        let app_server = state.0; 
-        let response = match logic::get_request_result(&app_server.db_handler, &hash, &request_id, &app_server.output_path)
-        .instrument(tracing::debug_span!("fetch_result")).await
-        {
-            Ok(result) => result,
-            Err(e) => { debug!("Error: {}", e.to_string()); RequestResult{request: FinalizedRequest::construct(), files: HashMap::new()}}
-        }; 
-        (
+        path: std::result::Result<axum::extract::Path<(String, i64)>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path((hash, request_id)) = path?;
+        let response = logic::get_request_result(&app_server.db_handler, &hash, &request_id, &app_server.output_path)
+        .instrument(tracing::debug_span!("fetch_result")).await?;
+        Ok((
             StatusCode::OK, 
             axum::Json(response)
-        )
+        ))
     }
 
-    // Endpoint for receiving the request by a user
-    async fn receive_request(state: State<Arc<AppServer>>,
-        axum::extract::Json(data): axum::extract::Json<serde_json::Value>
-    ) -> impl IntoResponse {
-        let app_server = state.0; 
-        let response = match logic::create_request(&app_server.db_handler, &data)
-        .instrument(tracing::debug_span!("receive_request")).await
-        {
-            Ok(result) => result,
-            Err(e) => { debug!("Error: {}", e.to_string()); RequestResponse::new(1, String::from(""), -1)}
+    // Endpoint for the status of a request, before and after its result exists
+    async fn get_request_status(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<(String, i64)>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path((hash, request_id)) = path?;
+        let response = status::get_request_status(&app_server.db_handler, &hash, &request_id, app_server.cores_per_job)
+        .instrument(tracing::debug_span!("get_request_status")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for downloading the complete archive of the outputs of a request
+    async fn download_result(state: State<Arc<AppServer>>, headers: HeaderMap,
+        path: std::result::Result<axum::extract::Path<(String, i64)>, PathRejection>
+    ) -> ApiResult<Response> {
+        let app_server = state.0;
+        let axum::extract::Path((hash, request_id)) = path?;
+        let file = results::get_result_archive(&app_server.db_handler, &hash, &request_id, &app_server.output_path)
+        .instrument(tracing::debug_span!("download_result")).await?;
+        ranges::serve_file(&headers, file).await
+    }
+
+    // Endpoint for listing the output files of a request that can be downloaded individually
+    async fn list_result_files(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<(String, i64)>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path((hash, request_id)) = path?;
+        let response = results::list_result_files(&app_server.db_handler, &hash, &request_id, &app_server.output_path)
+        .instrument(tracing::debug_span!("list_result_files")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for downloading a CSV, HTML or PDB output file of a request
+    async fn download_result_file(state: State<Arc<AppServer>>, headers: HeaderMap,
+        path: std::result::Result<axum::extract::Path<(String, i64, String)>, PathRejection>
+    ) -> ApiResult<Response> {
+        let app_server = state.0;
+        let axum::extract::Path((hash, request_id, file_path)) = path?;
+        let file = results::get_result_file(&app_server.db_handler, &hash, &request_id, file_path.trim_start_matches('/'), 
+                                            &app_server.output_path)
+        .instrument(tracing::debug_span!("download_result_file")).await?;
+        ranges::serve_file(&headers, file).await
+    }
+
+    // Endpoint for listing the tabular outputs of a request
+    async fn list_result_tables(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<(String, i64)>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path((hash, request_id)) = path?;
+        let response = tables::list_tables(&app_server.db_handler, &hash, &request_id, &app_server.output_path)
+        .instrument(tracing::debug_span!("list_result_tables")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for a page of the filtered and sorted rows of a tabular output of a request
+    async fn query_result_table(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<(String, i64, String)>, PathRejection>,
+        query: std::result::Result<axum::extract::Query<TableQuery>, QueryRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path((hash, request_id, table_path)) = path?;
+        let axum::extract::Query(query) = query?;
+        let response = tables::query_table(&app_server.db_handler, &hash, &request_id, table_path.trim_start_matches('/'), 
+                                           &query, &app_server.output_path)
+        .instrument(tracing::debug_span!("query_result_table")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for receiving the request by a user, or by a script with an API key
+    async fn receive_request(state: State<Arc<AppServer>>, headers: HeaderMap,
+        payload: std::result::Result<axum::extract::Json<RequestInput>, JsonRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Json(data) = payload?;
+        let api_key = match Self::bearer_token(&headers) {
+            Some(key) => Some(apikeys::authenticate(&app_server.db_handler, key).await?),
+            None => None
         };
-        (
+        let response = logic::create_request(&app_server.db_handler, app_server.verifier.as_ref(), &data, api_key.as_ref())
+        .instrument(tracing::debug_span!("receive_request")).await?;
+        Ok((
             StatusCode::OK, 
             axum::Json(response)
-        )
+        ))
     }
 
-    // Endpoint for MachaonWeb's status
-    async fn get_info(state: State<Arc<AppServer>>) -> impl IntoResponse {
+    // Endpoint for receiving the request by a client of the unversioned API, with the untyped input of those clients
+    async fn receive_legacy_request(state: State<Arc<AppServer>>, headers: HeaderMap,
+        payload: std::result::Result<axum::extract::Json<serde_json::Value>, JsonRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Json(data) = payload?;
+        let api_key = match Self::bearer_token(&headers) {
+            Some(key) => Some(apikeys::authenticate(&app_server.db_handler, key).await?),
+            None => None
+        };
+        let response = logic::create_legacy_request(&app_server.db_handler, app_server.verifier.as_ref(), &data, api_key.as_ref())
+        .instrument(tracing::debug_span!("receive_legacy_request")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
 
+    // Endpoint for receiving a batch of requests for several references
+    async fn receive_batch(state: State<Arc<AppServer>>, headers: HeaderMap,
+        payload: std::result::Result<axum::extract::Json<BatchInput>, JsonRejection>
+    ) -> ApiResult<impl IntoResponse> {
         let app_server = state.0;
-        let response = match logic::retrieve_info(&app_server.db_handler)
-        .instrument(tracing::debug_span!("get_info")).await
-        {
-            Ok(result) => result,
-            Err(e) => { debug!("Error: {}", e.to_string()); Info::new(0, 0, 0)}
+        let axum::extract::Json(data) = payload?;
+        let api_key = match Self::bearer_token(&headers) {
+            Some(key) => Some(apikeys::authenticate(&app_server.db_handler, key).await?),
+            None => None
         };
+        let response = batch::create_batch(&app_server.db_handler, app_server.verifier.as_ref(), &data, api_key.as_ref())
+        .instrument(tracing::debug_span!("receive_batch")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
 
-        (
+    // Endpoint for the aggregated status of a batch
+    async fn get_batch_status(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<String>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(batch_id) = path?;
+        let response = batch::get_batch_status(&app_server.db_handler, &batch_id)
+        .instrument(tracing::debug_span!("get_batch_status")).await?;
+        Ok((
             StatusCode::OK, 
             axum::Json(response)
-        )
+        ))
+    }
+
+    // Endpoint for downloading the results of the completed requests of a batch in one archive
+    async fn download_batch(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<String>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(batch_id) = path?;
+        let bundle_path = batch::bundle_batch_results(&app_server.db_handler, &batch_id, &app_server.output_path)
+        .instrument(tracing::debug_span!("download_batch")).await?;
+        let bundle = tokio::fs::File::open(&bundle_path).await.map_err(anyhow::Error::from)?;
+        let headers = [(http::header::CONTENT_TYPE, String::from("application/zip")),
+                       (http::header::CONTENT_DISPOSITION, format!("attachment; filename=\"batch-{}.zip\"", batch_id))];
+        Ok((
+            StatusCode::OK, 
+            headers,
+            StreamBody::new(ReaderStream::new(bundle))
+        ))
+    }
+
+    // Endpoint for cancelling a queued or running request
+    async fn cancel_request(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<(String, i64)>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path((hash, request_id)) = path?;
+        let response = logic::cancel_request(&app_server.db_handler, &hash, &request_id, &app_server.protocol)
+        .instrument(tracing::debug_span!("cancel_request")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for following the progress of a request (Server-Sent Events)
+    async fn watch_progress(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<(String, i64)>, PathRejection>
+    ) -> ApiResult<Sse<impl Stream<Item = Result<Event>>>> {
+        let app_server = state.0; 
+        let axum::extract::Path((hash, request_id)) = path?;
+        logic::verify_request(&app_server.db_handler, &hash, &request_id).await?;
+        let updates = logic::watch_request(app_server.db_handler.clone(), hash, request_id, app_server.protocol.clone())
+                        .map(|update| Ok(Event::default().json_data(update?)?));
+        Ok(Sse::new(updates).keep_alive(KeepAlive::default()))
+    }
+
+    // Endpoint for the human verifier of the requests and its challenge
+    async fn get_challenge(state: State<Arc<AppServer>>) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let response = logic::get_challenge(app_server.verifier.as_ref())
+        .instrument(tracing::debug_span!("get_challenge")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for the usage and the request history of an API key
+    async fn get_key_history(state: State<Arc<AppServer>>, headers: HeaderMap) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let key = Self::bearer_token(&headers).unwrap_or_default();
+        let identity = apikeys::authenticate(&app_server.db_handler, key).await?;
+        let response = apikeys::get_key_history(&app_server.db_handler, &identity)
+        .instrument(tracing::debug_span!("get_key_history")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for MachaonWeb's status
+    async fn get_info(state: State<Arc<AppServer>>) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let response = logic::retrieve_info(&app_server.db_handler)
+        .instrument(tracing::debug_span!("get_info")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
     }
 
     // Endpoint for retrieving the available preset candidate lists
-    async fn get_candidate_lists(state: State<Arc<AppServer>>) -> impl IntoResponse {
+    async fn get_candidate_lists(state: State<Arc<AppServer>>) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let response = logic::get_candidate_lists(&app_server.db_handler)
+        .instrument(tracing::debug_span!("get_candidate_lists")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
 
+    // Retrieve the token of an Authorization: Bearer <token> header
+    fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+        headers.get(http::header::AUTHORIZATION)
+               .and_then(|value| value.to_str().ok())
+               .and_then(|value| value.strip_prefix("Bearer "))
+               .map(|value| value.trim())
+    }
+
+    // Identify the client of a request by its API key, its administrator session or its IP address.
+    // Only the tokens with the format of a key or a session token are looked up.
+    async fn identify_client(&self, headers: &HeaderMap, address: Option<SocketAddr>) -> String {
+        match Self::bearer_token(headers) {
+            Some(token) if apikeys::is_key_format(token) => {
+                if let Ok(identity) = apikeys::authenticate(&self.db_handler, token).await {
+                    return format!("key:{}", identity.id);
+                }
+            },
+            Some(token) if auth::is_token_format(token) => {
+                if let Ok(Some(identity)) = auth::authenticate(&self.db_handler, token).await {
+                    return format!("account:{}", identity.id);
+                }
+            },
+            _ => {}
+        }
+        address.map_or(String::from("ip:unknown"), |address| format!("ip:{}", address.ip()))
+    }
+
+    // Refuse the requests of a client that exceeded the rate limit of the route
+    async fn limit_rate(State((app_server, route)): State<(Arc<AppServer>, &'static str)>, request: Request<Body>, next: Next<Body>) -> Response {
+        let address = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|connection| connection.0);
+        let client_key = app_server.identify_client(request.headers(), address).await;
+        match app_server.rate_limiter.check(route, &client_key).await {
+            Ok(Some(retry_after)) => return ApiError::RateLimited { retry_after }.into_response(),
+            Ok(None) => {},
+            // The requests are not refused when the buckets cannot be reached
+            Err(e) => debug!("Rate limiter error: {}", e)
+        }
+        next.run(request).await
+    }
+
+    // Allow the administrative requests with a valid session token and record them in the audit log
+    async fn authorize_admin(state: State<Arc<AppServer>>, request: Request<Body>, next: Next<Body>) -> Response {
         let app_server = state.0;
-        let response = match logic::get_candidate_lists(&app_server.db_handler)
-        .instrument(tracing::debug_span!("get_candidate_lists")).await
-        {
-            Ok(result) => result,
-            Err(e) => { debug!("Error: {}", e.to_string()); Vec::<CandidateList>::new()}
+        let identity = match Self::bearer_token(request.headers()) {
+            Some(token) => match auth::authenticate(&app_server.db_handler, token).await {
+                Ok(identity) => identity,
+                Err(e) => return ApiError::from(e).into_response()
+            },
+            None => None
+        };
+        let identity = match identity {
+            Some(identity) => identity,
+            None => return ApiError::Unauthorized(String::from("A valid administrator session is required.")).into_response()
         };
 
-        (
+        // The payload is buffered, up to its size limit, for the audit log and handed over to the endpoint
+        let (parts, body) = request.into_parts();
+        let payload = match hyper::body::to_bytes(http_body::Limited::new(body, ADMIN_BODY_LIMIT)).await {
+            Ok(payload) => payload,
+            Err(_) => return ApiError::invalid_input("body", &format!("The body of the request could not be read or exceeds {} bytes.",
+                                                                      ADMIN_BODY_LIMIT)).into_response()
+        };
+        let action = [parts.method.as_str(), parts.uri.path()].join(" ");
+        let mut request = Request::from_parts(parts, Body::from(payload.clone()));
+        request.extensions_mut().insert(identity.clone());
+
+        let response = next.run(request).await;
+        if let Err(e) = auth::record_action(&app_server.db_handler, identity.id, &identity.username, &action,
+                                            &String::from_utf8_lossy(&payload[..payload.len().min(AUDIT_PAYLOAD_LIMIT)]),
+                                            i32::from(response.status().as_u16())).await {
+            debug!("Error: {}", e.to_string());
+        }
+        response
+    }
+
+    // Endpoint for the login of an administrator
+    async fn login(state: State<Arc<AppServer>>,
+        payload: std::result::Result<axum::extract::Json<serde_json::Value>, JsonRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Json(data) = payload?;
+        let response = auth::login(&app_server.db_handler, &data, app_server.admin_session_minutes)
+        .instrument(tracing::debug_span!("login")).await?;
+        Ok((
             StatusCode::OK, 
             axum::Json(response)
-        )
+        ))
     }
 
-}
+    // Endpoint for the logout of an administrator
+    async fn logout(state: State<Arc<AppServer>>, headers: HeaderMap) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let token = Self::bearer_token(&headers).unwrap_or_default();
+        let response = auth::logout(&app_server.db_handler, token)
+        .instrument(tracing::debug_span!("logout")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for listing the computing nodes
+    async fn list_nodes(state: State<Arc<AppServer>>) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let response = admin::list_nodes(&app_server.db_handler)
+        .instrument(tracing::debug_span!("list_nodes")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for the metrics of the database connections
+    async fn get_database_metrics(state: State<Arc<AppServer>>) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let response = admin::get_database_metrics(&app_server.db_handler);
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for adding a computing node
+    async fn add_node(state: State<Arc<AppServer>>,
+        payload: std::result::Result<axum::extract::Json<serde_json::Value>, JsonRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Json(data) = payload?;
+        let response = admin::add_node(&app_server.db_handler, &data)
+        .instrument(tracing::debug_span!("add_node")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for updating the address, the domain or the cores of a computing node
+    async fn update_node(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<i16>, PathRejection>,
+        payload: std::result::Result<axum::extract::Json<serde_json::Value>, JsonRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(node_id) = path?;
+        let axum::extract::Json(data) = payload?;
+        let response = admin::update_node(&app_server.db_handler, &node_id, &data)
+        .instrument(tracing::debug_span!("update_node")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for draining a computing node
+    async fn drain_node(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<i16>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(node_id) = path?;
+        let response = admin::drain_node(&app_server.db_handler, &node_id)
+        .instrument(tracing::debug_span!("drain_node")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
 
+    // Endpoint for deactivating a computing node
+    async fn deactivate_node(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<i16>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(node_id) = path?;
+        let response = admin::deactivate_node(&app_server.db_handler, &node_id)
+        .instrument(tracing::debug_span!("deactivate_node")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for returning a drained or deactivated computing node to service
+    async fn activate_node(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<i16>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(node_id) = path?;
+        let response = admin::activate_node(&app_server.db_handler, &node_id)
+        .instrument(tracing::debug_span!("activate_node")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for removing a computing node
+    async fn remove_node(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<i16>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(node_id) = path?;
+        let response = admin::remove_node(&app_server.db_handler, &node_id)
+        .instrument(tracing::debug_span!("remove_node")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for listing the API keys
+    async fn list_api_keys(state: State<Arc<AppServer>>) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let response = apikeys::list_api_keys(&app_server.db_handler)
+        .instrument(tracing::debug_span!("list_api_keys")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for issuing an API key
+    async fn issue_api_key(state: State<Arc<AppServer>>, Extension(admin): Extension<AdminIdentity>,
+        payload: std::result::Result<axum::extract::Json<serde_json::Value>, JsonRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Json(data) = payload?;
+        let response = apikeys::issue_api_key(&app_server.db_handler, &admin, &data)
+        .instrument(tracing::debug_span!("issue_api_key")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for updating the owner, the quota or the state of an API key
+    async fn update_api_key(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<i32>, PathRejection>,
+        payload: std::result::Result<axum::extract::Json<serde_json::Value>, JsonRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(key_id) = path?;
+        let axum::extract::Json(data) = payload?;
+        let response = apikeys::update_api_key(&app_server.db_handler, &key_id, &data)
+        .instrument(tracing::debug_span!("update_api_key")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for revoking an API key
+    async fn revoke_api_key(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<i32>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(key_id) = path?;
+        let response = apikeys::revoke_api_key(&app_server.db_handler, &key_id)
+        .instrument(tracing::debug_span!("revoke_api_key")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+    // Endpoint for the usage and the request history of any API key
+    async fn get_api_key_history(state: State<Arc<AppServer>>,
+        path: std::result::Result<axum::extract::Path<i32>, PathRejection>
+    ) -> ApiResult<impl IntoResponse> {
+        let app_server = state.0;
+        let axum::extract::Path(key_id) = path?;
+        let response = apikeys::get_api_key_history(&app_server.db_handler, &key_id)
+        .instrument(tracing::debug_span!("get_api_key_history")).await?;
+        Ok((
+            StatusCode::OK, 
+            axum::Json(response)
+        ))
+    }
+
+}
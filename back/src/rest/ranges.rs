@@ -0,0 +1,183 @@
+use std::io::SeekFrom;
+use axum::{body::{Full, StreamBody}, http::{header, HeaderMap, HeaderValue, StatusCode}, response::{IntoResponse, Response}};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use crate::{error::{ApiError, ApiResult}, logic::results::{ResultContent, ResultFile}};
+
+/*
+
+The downloads of the results support conditional and partial requests: a file is not sent again when the ETag of the
+client's copy (If-None-Match) is current, and a single byte range (Range) of it is sent, e.g. to resume an interrupted
+download. Requests for several ranges are answered with the whole file.
+
+*/
+
+// Inclusive byte range of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+// Parse the Range header of a request for a file of the given length.
+// Headers that are malformed or have several ranges are ignored, while ranges that start after the end of the file are refused.
+pub fn parse_range(range: &str, length: u64) -> ApiResult<Option<ByteRange>> {
+    let range = match range.trim().strip_prefix("bytes=") {
+        Some(range) if !range.contains(',') => range.trim(),
+        _ => return Ok(None)
+    };
+    let (start, end) = match range.split_once('-') {
+        Some(bounds) => bounds,
+        None => return Ok(None)
+    };
+    // A suffix range (-<length>) requests the last bytes of the file
+    if start.is_empty() {
+        return match end.parse::<u64>() {
+            Ok(suffix) if suffix > 0 && length > 0 => Ok(Some(ByteRange { start: length.saturating_sub(suffix), end: length - 1 })),
+            Ok(_) => Err(ApiError::RangeNotSatisfiable { length }),
+            Err(_) => Ok(None)
+        };
+    }
+    let start = match start.parse::<u64>() {
+        Ok(start) => start,
+        Err(_) => return Ok(None)
+    };
+    let end = match end {
+        "" => u64::MAX,
+        end => match end.parse::<u64>() {
+            Ok(end) if end >= start => end,
+            _ => return Ok(None)
+        }
+    };
+    if start >= length {
+        return Err(ApiError::RangeNotSatisfiable { length });
+    }
+    Ok(Some(ByteRange { start, end: end.min(length - 1) }))
+}
+
+// Check whether an If-None-Match or If-Range header refers to the current version of a file
+fn matches_etag(value: Option<&HeaderValue>, etag: &str) -> bool {
+    match value.and_then(|value| value.to_str().ok()) {
+        Some(value) => value.split(',').map(|tag| tag.trim()).any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag),
+        None => false
+    }
+}
+
+// Send a file of the results of a request, or the requested range of it
+pub async fn serve_file(request_headers: &HeaderMap, file: ResultFile) -> ApiResult<Response> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, HeaderValue::from_str(&file.etag).map_err(anyhow::Error::from)?);
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if matches_etag(request_headers.get(header::IF_NONE_MATCH), &file.etag) {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(file.content_type));
+    // HTML reports are shown in the browser, the rest of the files are saved
+    let disposition = if file.content_type.starts_with("text/html") { "inline" } else { "attachment" };
+    let file_name = file.file_name.replace('"', "");
+    headers.insert(header::CONTENT_DISPOSITION, HeaderValue::from_str(&format!("{}; filename=\"{}\"", disposition, file_name))
+                                                            .map_err(anyhow::Error::from)?);
+
+    // A range of an older version of the file (If-Range) is answered with the whole file
+    let range = match request_headers.get(header::RANGE).and_then(|value| value.to_str().ok()) {
+        Some(_) if request_headers.contains_key(header::IF_RANGE) 
+                   && !matches_etag(request_headers.get(header::IF_RANGE), &file.etag) => None,
+        Some(range) => parse_range(range, file.length)?,
+        None => None
+    };
+    let (status, ByteRange { start, end }) = match range {
+        Some(range) => {
+            headers.insert(header::CONTENT_RANGE, HeaderValue::from_str(&format!("bytes {}-{}/{}", range.start, range.end, file.length))
+                                                                .map_err(anyhow::Error::from)?);
+            (StatusCode::PARTIAL_CONTENT, range)
+        },
+        None => (StatusCode::OK, ByteRange { start: 0, end: file.length.saturating_sub(1) })
+    };
+    let content_length = if file.length == 0 { 0 } else { end - start + 1 };
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+
+    let response = match file.content {
+        ResultContent::File(path) => {
+            let mut source = tokio::fs::File::open(&path).await.map_err(anyhow::Error::from)?;
+            source.seek(SeekFrom::Start(start)).await.map_err(anyhow::Error::from)?;
+            (status, headers, StreamBody::new(ReaderStream::new(source.take(content_length)))).into_response()
+        },
+        ResultContent::Memory(content) => {
+            let content = if content_length == 0 { Vec::new() } else { content[start as usize..=end as usize].to_vec() };
+            (status, headers, Full::from(content)).into_response()
+        }
+    };
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> Option<ByteRange> {
+        Some(ByteRange { start, end })
+    }
+
+    fn is_not_satisfiable(result: ApiResult<Option<ByteRange>>, expected_length: u64) -> bool {
+        matches!(result, Err(ApiError::RangeNotSatisfiable { length }) if length == expected_length)
+    }
+
+    #[test]
+    fn closed_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000).ok(), Some(range(0, 99)));
+        assert_eq!(parse_range(" bytes=10-10 ", 1000).ok(), Some(range(10, 10)));
+        // The end is clamped to the last byte of the file
+        assert_eq!(parse_range("bytes=900-5000", 1000).ok(), Some(range(900, 999)));
+    }
+
+    #[test]
+    fn open_ranges() {
+        assert_eq!(parse_range("bytes=0-", 1000).ok(), Some(range(0, 999)));
+        assert_eq!(parse_range("bytes=999-", 1000).ok(), Some(range(999, 999)));
+    }
+
+    #[test]
+    fn suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000).ok(), Some(range(900, 999)));
+        // A suffix longer than the file covers the whole file
+        assert_eq!(parse_range("bytes=-5000", 1000).ok(), Some(range(0, 999)));
+        assert!(is_not_satisfiable(parse_range("bytes=-0", 1000), 1000));
+    }
+
+    #[test]
+    fn ranges_after_the_end_are_refused() {
+        assert!(is_not_satisfiable(parse_range("bytes=1000-", 1000), 1000));
+        assert!(is_not_satisfiable(parse_range("bytes=1000-1100", 1000), 1000));
+        assert!(is_not_satisfiable(parse_range("bytes=5000-", 1000), 1000));
+    }
+
+    #[test]
+    fn zero_length_files() {
+        assert!(is_not_satisfiable(parse_range("bytes=0-", 0), 0));
+        assert!(is_not_satisfiable(parse_range("bytes=0-10", 0), 0));
+        assert!(is_not_satisfiable(parse_range("bytes=-10", 0), 0));
+    }
+
+    #[test]
+    fn several_or_malformed_ranges_are_ignored() {
+        // The whole file is sent instead
+        assert_eq!(parse_range("bytes=0-10,20-30", 1000).ok(), Some(None));
+        assert_eq!(parse_range("bytes=0-10, 5000-", 1000).ok(), Some(None));
+        assert_eq!(parse_range("items=0-10", 1000).ok(), Some(None));
+        assert_eq!(parse_range("bytes=10", 1000).ok(), Some(None));
+        assert_eq!(parse_range("bytes=20-10", 1000).ok(), Some(None));
+        assert_eq!(parse_range("bytes=a-10", 1000).ok(), Some(None));
+        assert_eq!(parse_range("bytes=-a", 1000).ok(), Some(None));
+    }
+
+    #[test]
+    fn etag_matching() {
+        let etag = "\"abc\"";
+        assert!(matches_etag(Some(&HeaderValue::from_static("\"abc\"")), etag));
+        assert!(matches_etag(Some(&HeaderValue::from_static("W/\"abc\"")), etag));
+        assert!(matches_etag(Some(&HeaderValue::from_static("\"xyz\", \"abc\"")), etag));
+        assert!(matches_etag(Some(&HeaderValue::from_static("*")), etag));
+        assert!(!matches_etag(Some(&HeaderValue::from_static("\"xyz\"")), etag));
+        assert!(!matches_etag(None, etag));
+    }
+}
@@ -0,0 +1,148 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicU64, Ordering}, Mutex}};
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use crate::db::{dbhandler::DatabaseHandler, models::RateLimitBucket};
+
+/*
+
+The rate limiter keeps a token bucket for each client of a route. A bucket holds up to the capacity of the route's
+policy and is refilled at the capacity per period, so that a client can send short bursts but not more than the
+capacity in each period on average. The buckets are kept in memory or, when several servers share the database, in
+the rate_limit_buckets table.
+
+*/
+
+// Number of bucket updates between the removals of the idle buckets
+const PRUNE_INTERVAL: u64 = 1000;
+// Number of buckets in memory above which the full buckets are removed
+const MAX_MEMORY_BUCKETS: usize = 10000;
+
+// Capacity of the buckets of a route and the period in which they are refilled
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitPolicy {
+    pub capacity: u32,
+    pub period_seconds: u32,
+}
+
+impl RateLimitPolicy {
+
+    // Tokens that are added to a bucket per millisecond
+    fn refill_rate(&self) -> f64 {
+        f64::from(self.capacity) / (f64::from(self.period_seconds) * 1000.0)
+    }
+}
+
+// Parse the policies of the routes in the form <route>=<capacity>/<seconds>, separated by commas
+pub fn parse_policies(limits: &str) -> Result<HashMap<String, RateLimitPolicy>> {
+    let mut policies = HashMap::new();
+    for limit in limits.split(',').map(|limit| limit.trim()).filter(|limit| !limit.is_empty()) {
+        let (route, policy) = limit.split_once('=').ok_or_else(|| anyhow!("Invalid rate limit: {}", limit))?;
+        let (capacity, period_seconds) = policy.split_once('/').ok_or_else(|| anyhow!("Invalid rate limit: {}", limit))?;
+        let policy = RateLimitPolicy { capacity: capacity.trim().parse::<u32>()?, period_seconds: period_seconds.trim().parse::<u32>()? };
+        if policy.capacity == 0 || policy.period_seconds == 0 {
+            bail!("Invalid rate limit: {}", limit);
+        }
+        policies.insert(route.trim().to_string(), policy);
+    }
+    Ok(policies)
+}
+
+// Refill a bucket up to the current time and take a token from it.
+// The outcome is the number of seconds until a token is available, if the bucket is empty.
+fn take_token(bucket: Option<RateLimitBucket>, bucket_key: &str, policy: &RateLimitPolicy, current_time: i64) -> (RateLimitBucket, Option<u64>) {
+    let capacity = f64::from(policy.capacity);
+    let mut bucket = bucket.unwrap_or(RateLimitBucket { bucket_key: bucket_key.to_string(), tokens: capacity, refill_time: current_time });
+    let elapsed = (current_time - bucket.refill_time).max(0) as f64;
+    bucket.tokens = (bucket.tokens + elapsed * policy.refill_rate()).min(capacity);
+    bucket.refill_time = current_time;
+    if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        return (bucket, None);
+    }
+    let retry_after = ((1.0 - bucket.tokens) / policy.refill_rate() / 1000.0).ceil() as u64;
+    (bucket, Some(retry_after.max(1)))
+}
+
+#[tonic::async_trait]
+pub trait RateLimitStore: Send + Sync + std::fmt::Debug {
+    // Take a token from the bucket of a key, returning the seconds to wait if it is empty
+    async fn take(&self, bucket_key: &str, policy: &RateLimitPolicy, current_time: i64) -> Result<Option<u64>>;
+}
+
+// Create the store that is selected in the configuration
+pub fn create_store(kind: &str, db_handler: DatabaseHandler) -> Result<Box<dyn RateLimitStore>> {
+    let store: Box<dyn RateLimitStore> = match kind {
+        "memory" => Box::new(MemoryStore::default()),
+        "database" => Box::new(DatabaseStore { db_handler, updates: AtomicU64::new(0) }),
+        _ => bail!("Unknown rate limit store: {}", kind)
+    };
+    Ok(store)
+}
+
+// Buckets of a single server, along with the policies of their routes
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, (RateLimitBucket, RateLimitPolicy)>>,
+}
+
+#[tonic::async_trait]
+impl RateLimitStore for MemoryStore {
+
+    async fn take(&self, bucket_key: &str, policy: &RateLimitPolicy, current_time: i64) -> Result<Option<u64>> {
+        let mut buckets = self.buckets.lock().map_err(|_| anyhow!("The rate limit buckets are not available"))?;
+        // Buckets that would be refilled completely are equivalent to missing ones
+        if buckets.len() > MAX_MEMORY_BUCKETS {
+            buckets.retain(|_, (bucket, policy)| {
+                let elapsed = (current_time - bucket.refill_time).max(0) as f64;
+                bucket.tokens + elapsed * policy.refill_rate() < f64::from(policy.capacity)
+            });
+        }
+        let (bucket, retry_after) = take_token(buckets.remove(bucket_key).map(|(bucket, _)| bucket), bucket_key, policy, current_time);
+        buckets.insert(bucket_key.to_string(), (bucket, *policy));
+        Ok(retry_after)
+    }
+}
+
+// Buckets that are shared by the servers of the same database
+#[derive(Debug)]
+pub struct DatabaseStore {
+    db_handler: DatabaseHandler,
+    updates: AtomicU64,
+}
+
+#[tonic::async_trait]
+impl RateLimitStore for DatabaseStore {
+
+    async fn take(&self, bucket_key: &str, policy: &RateLimitPolicy, current_time: i64) -> Result<Option<u64>> {
+        if self.updates.fetch_add(1, Ordering::Relaxed) >= PRUNE_INTERVAL {
+            self.updates.store(0, Ordering::Relaxed);
+            self.db_handler.delete_idle_rate_limit_buckets(current_time - 24 * 3600 * 1000).await?;
+        }
+        let (key, policy) = (bucket_key.to_string(), *policy);
+        self.db_handler.update_rate_limit_bucket(bucket_key, move |bucket| take_token(bucket, &key, &policy, current_time)).await
+    }
+}
+
+// Rate limits of the routes along with the store of their buckets
+#[derive(Debug)]
+pub struct RateLimiter {
+    policies: HashMap<String, RateLimitPolicy>,
+    store: Box<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+
+    // Create a new instance
+    pub fn new(limits: &str, store_kind: &str, db_handler: DatabaseHandler) -> Result<Self> {
+        Ok(Self { policies: parse_policies(limits)?, store: create_store(store_kind, db_handler)? })
+    }
+
+    // Take a token for a request of a client to a route, returning the seconds to wait if the client exceeded the limit.
+    // The routes without a policy are not limited.
+    pub async fn check(&self, route: &str, client_key: &str) -> Result<Option<u64>> {
+        match self.policies.get(route) {
+            Some(policy) => self.store.take(&[route, client_key].join(":"), policy, Utc::now().timestamp_millis()).await,
+            None => Ok(None)
+        }
+    }
+}
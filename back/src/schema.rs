@@ -18,24 +18,29 @@ diesel::table! {
     jobs (id) {
         id -> Bigint,
         request_id -> Bigint,
-        node_id -> Tinyint,
-        assignment_date -> Datetime,
-        completion_date -> Nullable<Datetime>,
-        last_checked -> Nullable<Datetime>,
-        status_code -> Tinyint,
+        node_id -> SmallInt,
+        assignment_date -> Timestamp,
+        completion_date -> Nullable<Timestamp>,
+        last_checked -> Nullable<Timestamp>,
+        status_code -> SmallInt,
         secure_hash -> Char,
+        retries -> Integer,
+        stage -> Varchar,
+        progress -> SmallInt,
     }
 }
 
 diesel::table! {
     nodes (id) {
-        id -> Tinyint,
+        id -> SmallInt,
         ip -> Char,
         domain -> Char,
         active -> Bool,
         working -> Bool,
-        sync_date -> Datetime,
-        cores -> Tinyint, 
+        sync_date -> Timestamp,
+        cores -> SmallInt,
+        failed_checks -> Integer,
+        drained -> Bool,
     }
 }
 
@@ -47,77 +52,147 @@ diesel::table! {
         custom_list -> Text,
         uncached -> Text,
         hash_value -> Char,
-        creation_date -> Datetime,
+        creation_date -> Timestamp,
         meta -> Bool,
         go_term -> Char,
-        comparison_mode  -> Tinyint,
+        comparison_mode  -> SmallInt,
         segment_start -> Integer,
         segment_end -> Integer,
-        alignment_level -> TinyInt,
+        alignment_level -> SmallInt,
         views -> Bigint,
+        access_token -> Nullable<Char>,
+        base_hash -> Nullable<Char>,
+        state -> Varchar,
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(
-    cached_features,
-    candidate_lists,
-    jobs,
-    nodes,
-    requests,
-);
+diesel::table! {
+    scheduling_decisions (id) {
+        id -> Bigint,
+        request_id -> Bigint,
+        node_id -> SmallInt,
+        strategy -> Char,
+        ranking -> Text,
+        candidate_count -> Integer,
+        large_job -> Bool,
+        decision_date -> Timestamp,
+    }
+}
 
 diesel::table! {
-    joined_requests (id) {
+    node_files (node_id, file_path) {
+        node_id -> SmallInt,
+        file_path -> Varchar,
+        file_hash -> Char,
+        sync_date -> Timestamp,
+    }
+}
+
+diesel::table! {
+    admins (id) {
+        id -> Integer,
+        username -> Varchar,
+        password_hash -> Varchar,
+        active -> Bool,
+        creation_date -> Timestamp,
+    }
+}
+
+diesel::table! {
+    admin_sessions (token_hash) {
+        token_hash -> Char,
+        admin_id -> Integer,
+        creation_date -> Timestamp,
+        expiration_date -> Timestamp,
+    }
+}
+
+diesel::table! {
+    admin_audit_log (id) {
         id -> Bigint,
-        reference -> Char,
-        candidates_list_id -> Integer,
-        custom_list -> Text,
-        uncached -> Text,
-        hash_value -> Char,
-        creation_date -> Datetime,
-        meta -> Bool,
-        go_term -> Char,
-        comparison_mode  -> Tinyint,
-        segment_start -> Integer,
-        segment_end -> Integer, 
-        alignment_level -> TinyInt,
-        views -> Bigint,
-        list_name -> Nullable<Char>,
+        admin_id -> Integer,
+        actor -> Varchar,
+        action -> Varchar,
+        payload -> Text,
+        status_code -> Integer,
+        action_date -> Timestamp,
     }
 }
 
+diesel::table! {
+    api_keys (id) {
+        id -> Integer,
+        owner -> Varchar,
+        key_prefix -> Char,
+        key_hash -> Char,
+        daily_quota -> Integer,
+        active -> Bool,
+        admin_id -> Integer,
+        creation_date -> Timestamp,
+        last_used_date -> Nullable<Timestamp>,
+    }
+}
 
 diesel::table! {
-    finalized_requests (id) {
+    api_key_requests (id) {
         id -> Bigint,
-        reference -> Char,
-        candidates_list_id -> Integer,
-        custom_list -> Text,
-        uncached -> Text,
-        hash_value -> Char,
-        creation_date -> Datetime,
-        meta -> Bool,
-        go_term -> Char,
-        comparison_mode  -> Tinyint,
-        segment_start -> Integer,
-        segment_end -> Integer, 
-        alignment_level -> TinyInt,
-        views -> Bigint,
-        secure_hash -> Char,
-        list_name -> Nullable<Char>,
-        status_code -> Tinyint,
+        api_key_id -> Integer,
+        request_id -> Bigint,
+        submission_date -> Timestamp,
+    }
+}
+
+diesel::table! {
+    rate_limit_buckets (bucket_key) {
+        bucket_key -> Varchar,
+        tokens -> Double,
+        refill_time -> Bigint,
     }
 }
 
+diesel::table! {
+    batches (id) {
+        id -> Char,
+        api_key_id -> Nullable<Integer>,
+        creation_date -> Timestamp,
+    }
+}
 
 diesel::table! {
-    joined_jobs (id) {
-        id -> Bigint, 
-        hash_value -> Char, 
-        request_id -> BigInt,
-        node_id -> Tinyint,
-        node_ip -> Char,
-        node_domain -> Char,
-        comparison_mode -> Tinyint,
+    batch_requests (batch_id, request_id) {
+        batch_id -> Char,
+        request_id -> Bigint,
+        position -> Integer,
+    }
+}
+
+diesel::table! {
+    job_events (id) {
+        id -> Bigint,
+        request_id -> Bigint,
+        job_id -> Nullable<Bigint>,
+        from_state -> Varchar,
+        to_state -> Varchar,
+        reason -> Varchar,
+        event_date -> Timestamp,
     }
 }
+
+diesel::allow_tables_to_appear_in_same_query!(
+    admin_audit_log,
+    admin_sessions,
+    admins,
+    api_key_requests,
+    api_keys,
+    batch_requests,
+    batches,
+    cached_features,
+    candidate_lists,
+    job_events,
+    jobs,
+    node_files,
+    nodes,
+    rate_limit_buckets,
+    requests,
+    scheduling_decisions,
+);
@@ -1,18 +1,13 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Write, Seek};
-use std::path::{Path, self};
-use std::{fs, env, io};
+use std::path::{Path, PathBuf, self};
+use std::{fs, io};
 use anyhow::Result;
 use glob::{MatchOptions, glob_with};
 use unicode_segmentation::UnicodeSegmentation;
 use zip::write::FileOptions;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher}; 
 use tracing::debug;
-use dotenvy::dotenv;
-use serde::{Deserialize, Serialize};
-use reqwest::Client;
 use sha2::{Sha256, Digest};  
 use walkdir::{DirEntry, WalkDir};
 use regex::Regex;
@@ -109,11 +104,15 @@ pub fn get_substring(x : &String, max_characters: usize) -> String{
     x.clone()
 }
 
-// Compute a hash from an object
-pub fn calculate_hash<T: Hash>(t: &T) -> String { 
-    let mut s = DefaultHasher::new();
-    t.hash(&mut s);
-    s.finish().to_string()
+// Retrieve the number of structures from the title of a preset candidate list, e.g. "Human_PDB_Candidate_Set_v1 (161629 structures)"
+pub fn parse_list_size(list_name: &str) -> usize {
+    lazy_static! {
+        static ref SIZE_RE: Regex = Regex::new(r"\((\d+) structures\)").unwrap();
+    }
+    match SIZE_RE.captures(list_name).and_then(|captures| captures.get(1)) {
+        Some(size) => size.as_str().parse::<usize>().unwrap_or(0),
+        None => 0
+    }
 }
 
 pub fn check_structure_id(mut structure_id : &str) -> Result<&str> {
@@ -168,29 +167,6 @@ pub fn store_cached_structures(path: String)  -> Result<()> {
     block_on(DatabaseHandler::insert_structure_ids(&mut db_handler, &filelist ))
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct CaptchaResponse {
-    pub success: bool,
-    #[serde(rename="error-codes")]
-    pub error_codes: Option<Vec<String>>
-}
-
-pub async fn verify_captcha_token(token: String) -> Result<bool>{ 
-    dotenv().ok(); 
-    let captcha_secret = env::var("CAPTCHA_SECRET")?; 
-    let params = [("secret", &captcha_secret), ("response", &token)];
-    let client = Client::new();
-    let response = client.post("https://www.google.com/recaptcha/api/siteverify")
-            .form(&params)
-            .send().await?.text().await?;
-    let captcha_result: CaptchaResponse = serde_json::from_str(&response)?;
-    match captcha_result.error_codes {
-        Some(codes) => debug!("reCaptcha error: {:?}", codes ),
-        None => debug!("No error codes")
-    }
-    Ok(captcha_result.success)
-}
-
 pub fn compute_file_hash(path : &str) -> Result<String> {
     let mut file = fs::File::open(path)?;
     let mut hasher = Sha256::new();
@@ -199,6 +175,11 @@ pub fn compute_file_hash(path : &str) -> Result<String> {
     Ok(format!("{:x}", hash))
 }
 
+// SHA-256 hash of a chunk of a transfer
+pub fn compute_chunk_hash(data : &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
 // The following function is retrieved from: 
 // https://github.com/zip-rs/zip/blob/e32db515a2a4c7d04b0bf5851912a399a4cbff68/examples/write_dir.rs
 fn zip_dir<T>(
@@ -257,6 +238,30 @@ pub fn compress_directory(source_directory: &str, output_path: &str) -> Result<b
     Ok(result)
 }
 
+// Bundle files in a new archive without compressing them, replacing the previous bundles with the same prefix
+pub fn bundle_files(files: &[(String, PathBuf)], bundle_directory: &Path, bundle_path: &Path, bundle_prefix: &str) -> Result<()> {
+    fs::create_dir_all(bundle_directory)?;
+    for entry in fs::read_dir(bundle_directory)?.filter_map(|entry| entry.ok()) {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.starts_with(bundle_prefix) && file_name.ends_with(".zip") {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    // The bundle is written under a temporary name, so that it is never served incomplete
+    let temporary_path = bundle_path.with_extension(["zip.", &uuid::Uuid::new_v4().to_string(), ".tmp"].join(""));
+    let mut zip = zip::ZipWriter::new(File::create(&temporary_path)?);
+    let options = FileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .large_file(true);
+    for (name, file_path) in files {
+        zip.start_file(name.as_str(), options)?;
+        io::copy(&mut File::open(file_path)?, &mut zip)?;
+    }
+    zip.finish()?;
+    fs::rename(&temporary_path, bundle_path)?;
+    Ok(())
+}
+
 // Extract files with a specific extension from an archive
 pub fn extract_result_files(archive_path: &str, output_path: &str, extension: &str) -> Result<()> {
     let file_name = std::path::Path::new(archive_path);
@@ -301,6 +306,62 @@ pub fn extract_result_files(archive_path: &str, output_path: &str, extension: &s
     Ok(())
 }
 
+// List the files of an archive by their paths in the archive, along with their uncompressed sizes
+pub fn list_archive_files(archive_path: &Path) -> Result<Vec<(String, u64)>> {
+    let mut archive_file = zip::ZipArchive::new(File::open(archive_path)?)?;
+    let mut files = Vec::new();
+    for i in 0..archive_file.len() {
+        let compressed_file = archive_file.by_index(i)?;
+        // Entries with unsafe paths (e.g. containing "..") are skipped
+        if compressed_file.is_file() && compressed_file.enclosed_name().is_some() {
+            files.push((compressed_file.name().to_string(), compressed_file.size()));
+        }
+    }
+    Ok(files)
+}
+
+// Read a file of an archive by its path in the archive, if it exists
+pub fn read_archive_file(archive_path: &Path, file_path: &str) -> Result<Option<Vec<u8>>> {
+    let mut archive_file = zip::ZipArchive::new(File::open(archive_path)?)?;
+    let mut compressed_file = match archive_file.by_name(file_path) {
+        Ok(compressed_file) if compressed_file.is_file() => compressed_file,
+        Ok(_) | Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into())
+    };
+    let mut content = Vec::with_capacity(compressed_file.size() as usize);
+    compressed_file.read_to_end(&mut content)?;
+    Ok(Some(content))
+}
+
+// Extract all the files of an archive while keeping their relative directory structure
+pub fn extract_archive(archive_path: &str, output_path: &str) -> Result<()> {
+    let zipfile = std::fs::File::open(archive_path)?;
+    let mut archive_file = zip::ZipArchive::new(zipfile)?;
+
+    for i in 0..archive_file.len() {
+        let mut compressed_file = archive_file.by_index(i)?;
+        // Entries with unsafe paths (e.g. containing "..") are skipped
+        let internal_path = match compressed_file.enclosed_name() {
+            Some(path) => Path::new(output_path).join(path),
+            None => {
+                continue;
+            }
+        };
+        if compressed_file.is_dir() {
+            fs::create_dir_all(&internal_path)?;
+        }
+        else {
+            if let Some(parent) = internal_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut output_file = File::create(&internal_path)?;
+            io::copy(&mut compressed_file, &mut output_file)?;
+        }
+    }
+
+    Ok(())
+}
+
 // Retrieve html filenames for the quick view of the results of a request
 pub fn get_html_filenames(hash_value : &str, meta : &bool, go_term: &str, root_path: &str) -> Result<HashMap<String, Vec<String>>>
 {
@@ -345,7 +406,8 @@ pub fn get_html_filenames(hash_value : &str, meta : &bool, go_term: &str, root_p
                     if go_term.len() == 0{
                         continue
                     }
-                    if filename.contains(name_suffixes[3]) && filename.contains(go_term) { 
+                    // The colon of a GO identifier may be replaced in the filenames
+                    if filename.contains(name_suffixes[3]) && (filename.contains(go_term) || filename.contains(&go_term.replace(':', "_"))) { 
                         html_file_names.entry(String::from("goTerm")).or_insert(Vec::new()).push(filename.to_string());
                     }
                 }
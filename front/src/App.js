@@ -28,7 +28,7 @@ function App() {
                 <Route path="policies" element={<Policies />} /> 
                 <Route path="instructions" element={<Instructions />} /> 
                 <Route path="results" element={<Results />} />
-                <Route path="result/:hash/:reqid" element={<Result />} />  
+                <Route path="result/:token/:reqid" element={<Result />} />  
                 <Route path="contact" element={<Contact />} /> 
                 <Route path="citation" element={<Citation />} /> 
 
@@ -85,8 +85,8 @@ const Instructions = () => {
                 <li> Machaon's <b>meta-analysis</b> extends the comparisons on the finalist proteins with established metrics and methodologies.  
                     The results are enriched with metadata and connected with genomic information.</li>
 
-                <li> An additional step of meta-analysis is based on Gene Ontology information. You can provide a stem of a GO term 
-                    in order to trace relevant proteins in the top 100 results. The stem is searched in all 3 GO term types (biological 
+                <li> An additional step of meta-analysis is based on Gene Ontology information. You can provide a GO term identifier 
+                    (e.g. GO:0001525) in order to trace relevant proteins in the top 100 results. The term can be of any of the 3 GO term types (biological 
                     process, cellular component, molecular function). These relationships are also localized by protein 2D alignments 
                     that correlate parts of the reference structure with GO terms.</li>
                 <li>
@@ -11,7 +11,7 @@ const Navigation = () => {
  
     // Retrieve the status of the MachaonWeb network
     const fetchSystemData = async () => {
-        await axios.get(process.env.REACT_APP_BASE_URL + '/info')
+        await axios.get(process.env.REACT_APP_BASE_URL + '/api/v1/info')
         .then(function (response) {
             console.log(response);
             if (response.status === 200){ 
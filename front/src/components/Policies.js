@@ -11,12 +11,12 @@ const Policies = () => {
             <h3>Privacy</h3>
             We do not retain any personal information for the usage of this service and the results cannot be traced back to 
             those who requested them. However, the results are public and can be viewed by anyone who is aware of the corresponding link.
-            The identifier in the link is a non-encrypting hash of the request's details for caching purposes. Please be noted that 
+            The identifier in the link is a random access token that cannot be derived from the request's details. Please be noted that 
             Google Analytics are employed in this platform for usage statistics and resource planning.
             <br/><br/>
             <h3>Workload</h3>
-            In order to protect our resources and allow as many people as possible to access Machaon, we throttle the total rate of 
-            requests in a variable time interval. This means that the server cannot accept several requests at the same time. The 
+            In order to protect our resources and allow as many people as possible to access Machaon, we limit the rate of 
+            requests of each user. This means that a user cannot submit several requests in a short time interval. The 
             submission process is guarded by input validation and Google reCaptcha v3. All users are able to see the load of the 
             network and act accordingly and responsibly. If you need more from the method, please <Link to="/contact">contact us</Link> to 
             help you.
@@ -19,19 +19,8 @@ const RequestForm = () => {
     const [submission, setSubmission] = useState({ buttonText: 'Submit', formEnabled: true });
     const [validation, setValidation] = useState({ reference: null, customList: null, residueRange: null });
     
-    // Mapping between error codes and their corresponding messages
+    // Messages of a queued request and of an unknown error; rejected requests carry their own message
     const statusMessages = { '0' : 'Your request was queued. Please keep the following link and visit after a while:',
-                            '-1' : 'There is a problem with the provided reference id.',
-                            '-2' : 'Another request was just queued some moments ago. We throttle the total'+
-                                   ' request rate of all users due to limited resources. Please try a while later.',
-                            '-3' : 'The selected candidate list identifier is not present in the system.',
-                            '-4' : 'Please re-check the custom list of candidates.',
-                            '-5' : 'Please re-check the custom list of candidates.',
-                            '-6' : 'Please re-check the custom list of candidates.',
-                            '-7' : 'Please re-check the choice of residue range.',
-                            '-8' : 'Your request was not validated by reCaptcha. Please try again.',
-                            '-9' : 'Invalid option for comparison mode.',
-                            '-10' : 'Invalid option for segment alignment level.',
                             '1' : 'Unknown error. Please check your input or the status of the service.'};
 
     // Change button text on copy
@@ -39,11 +28,10 @@ const RequestForm = () => {
         setCopying(previous => ({ ...previous, buttonText: 'Copied'}));
     };
 
-    // Filter user input for Gene Ontology terms
+    // Filter user input for Gene Ontology terms (GO:NNNNNNN)
     const extractGOterm = (input_text) => {
-        const regex = /[A-Z|0-9|a-z|\s]+/g; 
-        let matched = input_text.match(regex);
-        setRequest(previous => ({ ...previous, goTerm: matched  !== null ? matched : ''})); 
+        const filtered = input_text.toUpperCase().replace(/[^GO:0-9]/g, '');
+        setRequest(previous => ({ ...previous, goTerm: filtered.substring(0, 10)})); 
     }
 
     // Filter user input for reference structure
@@ -179,7 +167,7 @@ const RequestForm = () => {
     const submitRequest = (event) => {
        handleReCaptchaVerify(); 
        setSubmission(previous => ({ ...previous, buttonText: 'Submitting, please wait...', formEnabled: false}));
-       axios.post(process.env.REACT_APP_BASE_URL + '/request', request)
+       axios.post(process.env.REACT_APP_BASE_URL + '/api/v1/requests', request)
       .then(function (response) {
         console.log(response);
         let status_code = '1';
@@ -187,7 +175,7 @@ const RequestForm = () => {
         if(response.status === 200) {
             status_code = response.data.status_code.toString();
             if(status_code === "0") { 
-                requestLink = process.env.REACT_APP_BASE_URL + '/result/' + response.data.hash.toString()+ "/" + response.data.request_id.toString(); 
+                requestLink = process.env.REACT_APP_BASE_URL + '/result/' + response.data.access_token.toString()+ "/" + response.data.request_id.toString(); 
             }
             if(!(status_code in statusMessages))
             {
@@ -199,13 +187,16 @@ const RequestForm = () => {
       })
       .catch(function (error) {
         console.log(error);
-        handleModalShow(statusMessages['1'], '');
+        // Errors of the API: { code, message, field }
+        const message = error.response && error.response.data && error.response.data.message 
+                        ? error.response.data.message : statusMessages['1'];
+        handleModalShow(message, '');
       })
     }
 
     // Retrieve and show the available preset lists for candidate structure sets 
     const fetchLists = async () => {
-        await axios.get(process.env.REACT_APP_BASE_URL + '/lists')
+        await axios.get(process.env.REACT_APP_BASE_URL + '/api/v1/lists')
         .then(function (response) {
             console.log(response);
             if (response.status === 200) { 
@@ -292,8 +283,8 @@ const RequestForm = () => {
                 
                 <Form.Group className='mb-3' controlId='form.Meta'>
                     <Form.Check name='meta' onChange={setChecked} type='checkbox' label='4. Perform meta-analysis (optional)' />
-                    <Form.Label>Provide a term for Gene Ontology Analysis (it can be left empty):</Form.Label>
-                    <Form.Control name='goTerm' type='text' maxLength={100} value={request.goTerm} onChange={setGoTerm} disabled={!request.meta} placeholder='e.g. angiogenesis' />
+                    <Form.Label>Provide a GO term identifier for Gene Ontology Analysis (it can be left empty):</Form.Label>
+                    <Form.Control name='goTerm' type='text' maxLength={10} value={request.goTerm} onChange={setGoTerm} disabled={!request.meta} placeholder='e.g. GO:0001525' />
                 </Form.Group> 
             </Form>
            <div className="submit-section">
@@ -1,5 +1,5 @@
 import React, { useEffect, useState } from 'react'; 
-import { Container, ListGroup } from 'react-bootstrap';
+import { Container, ListGroup, ProgressBar } from 'react-bootstrap';
 import axios from 'axios';
 import { useParams } from "react-router-dom";
 
@@ -26,11 +26,12 @@ const Result = () => {
 
     const [request, setRequest] = useState({ reference: null, customList: '', listName: '', goTerm: '', 
                                              meta: false, comparisonMode: 0, segmentStart: -1, segmentEnd: -1, 
-                                             secureHash: '', alignmentLevel: -1, statusCode: 0, creationDate: '-'});
+                                             secureHash: '', alignmentLevel: -1, statusCode: 0, creationDate: '-', hash: ''});
     const [files, setFiles] = useState({ cluster: [], top: [], topHuman: [], goTerm: [] });
+    const [progress, setProgress] = useState(null);
     const mode = ['whole', 'domain', 'segment'];
     const alignment_levels =  ['primary', 'secondary', 'hydrophobicity', 'mixed'];
-    const { hash, reqid } = useParams(); 
+    const { token, reqid } = useParams(); 
 
     // Mapping between error codes and their corresponding messages
     const statusMessages = {'0' : 'This request is being processed and is not available yet. Please visit again later.',
@@ -44,11 +45,14 @@ const Result = () => {
                                   'Please review your inputs and try again',
                             '9' : '[Request failed] Reference structure was not able to be retrieved. ' +
                                   'Please review the reference structure id and try again.',
+                            '-4' : '[Request failed] The request exceeded the maximum processing time. Please try again later.',
+                            '-5' : '[Request failed] The computing node of the request became unavailable. Please try again later.',
+                            '-7' : 'This request was cancelled.',
                             '-1' : '[Request failed] There was an unknown error. Please try again by checking your input or the status of the service. If the problem persists, please contact us.'};
 
     // Request the server for the results of a request
     const fetchResult = async () => {
-        await axios.get(process.env.REACT_APP_BASE_URL + '/resultdata/' + hash + '/'+ reqid)
+        await axios.get(process.env.REACT_APP_BASE_URL + '/api/v1/requests/' + token + '/'+ reqid)
         .then(function (response) {
             console.log(response);
             if (response.status === 200 && response.data.request.id > 0) { 
@@ -73,7 +77,8 @@ const Result = () => {
                            secureHash: response.data.request.secure_hash,
                            alignmentLevel: response.data.request.alignment_level,
                            statusCode: response.data.request.status_code,
-                           creationDate: formattedDate
+                           creationDate: formattedDate,
+                           hash: response.data.request.hash_value
                  }));
                  setFiles(previous => ({ ...previous, ...response.data.files }));
             }
@@ -89,11 +94,38 @@ const Result = () => {
     useEffect(() => {
         fetchResult();
     },[]);
+
+    // Follow the progress of the request until its job is completed
+    useEffect(() => {
+        let watching = false;
+        const source = new EventSource(process.env.REACT_APP_BASE_URL + '/api/v1/requests/' + token + '/' + reqid + '/progress');
+        source.onmessage = (event) => {
+            const update = JSON.parse(event.data);
+            if (update.completed) {
+                source.close();
+                setProgress(null);
+                // Reload the result if the request completed while watching
+                if (watching && update.stage === 'completed') fetchResult();
+            }
+            else {
+                watching = true;
+                setProgress(previous => ({ ...update, 
+                    log_lines: [...(previous !== null ? previous.log_lines : []), ...update.log_lines].slice(-20) }));
+            }
+        };
+        source.onerror = () => source.close();
+        return () => source.close();
+    },[]);
     
 
     return ( 
         <Container>
             <h1>Result</h1>
+            {progress !== null && <div className="progress-container">
+                <span className="list-item-label">{progress.stage === 'queued' ? 'Queued' : 'Stage: ' + progress.stage}</span>
+                <ProgressBar now={progress.percentage} label={progress.percentage + '%'} />
+                {progress.log_lines.length > 0 && <pre className="progress-log">{progress.log_lines.join('\n')}</pre>}
+            </div>}
             {(request.reference ?? 'null') === '' && <div className="no-result-message">
                 This request have not been processed yet or it does not exist. Please review your link or visit again a while later.
             </div>}
@@ -124,25 +156,25 @@ const Result = () => {
                 <span className='italics-note'>(Links open in a new tab or window.)</span>
                 <ul className="view-options">
                     <li style={{display: files.top.length > 0 ? 'list-item' : 'none'}}>
-                        {files.top.length === 1 && <QuickViewLink hash={hash} filename={files.top[0]} label={"Top 100"}/>}  
-                        {files.top.length > 1 && <QuickViewMultiLink hash={hash} filenames={files.top} label={"Top 100"} nameSplitOffset={3}/>}  
+                        {files.top.length === 1 && <QuickViewLink hash={request.hash} filename={files.top[0]} label={"Top 100"}/>}  
+                        {files.top.length > 1 && <QuickViewMultiLink hash={request.hash} filenames={files.top} label={"Top 100"} nameSplitOffset={3}/>}  
                     </li>
                     <li style={{display: files.topHuman.length > 0 ? 'list-item' : 'none'}}>
-                        {files.topHuman.length === 1 && <QuickViewLink hash={hash} filename={files.topHuman[0]} label={"Top 100 (Human only)"}/>} 
-                        {files.topHuman.length > 1 && <QuickViewMultiLink hash={hash} filenames={files.topHuman} label={"Top 100 (Human only)"} nameSplitOffset={2}/>} 
+                        {files.topHuman.length === 1 && <QuickViewLink hash={request.hash} filename={files.topHuman[0]} label={"Top 100 (Human only)"}/>} 
+                        {files.topHuman.length > 1 && <QuickViewMultiLink hash={request.hash} filenames={files.topHuman} label={"Top 100 (Human only)"} nameSplitOffset={2}/>} 
                     </li>
                     <li>
-                        {files.cluster.length === 1 && <QuickViewLink hash={hash} filename={files.cluster[0]} label={"Final cluster"}/>}  
-                        {files.cluster.length > 1 && <QuickViewMultiLink hash={hash} filenames={files.cluster} label={"Final cluster"} nameSplitOffset={2}/>}   
+                        {files.cluster.length === 1 && <QuickViewLink hash={request.hash} filename={files.cluster[0]} label={"Final cluster"}/>}  
+                        {files.cluster.length > 1 && <QuickViewMultiLink hash={request.hash} filenames={files.cluster} label={"Final cluster"} nameSplitOffset={2}/>}   
                     </li>
                     <li style={{display: files.goTerm.length > 0 ? 'list-item' : 'none'}}>
-                        {files.goTerm.length === 1 && <QuickViewLink hash={hash} filename={files.goTerm[0]} label={"Proteins associated with selected GO term"}/>}  
-                        {files.goTerm.length > 1 && <QuickViewMultiLink hash={hash} filenames={files.goTerm} label={"Proteins associated with selected GO term"} nameSplitOffset={2}/>} 
+                        {files.goTerm.length === 1 && <QuickViewLink hash={request.hash} filename={files.goTerm[0]} label={"Proteins associated with selected GO term"}/>}  
+                        {files.goTerm.length > 1 && <QuickViewMultiLink hash={request.hash} filenames={files.goTerm} label={"Proteins associated with selected GO term"} nameSplitOffset={2}/>} 
                     </li>
                 </ul>
                 <br/> 
                 <h3>Detailed Analysis</h3>
-                <a href={process.env.REACT_APP_BASE_URL + "/output/"+ hash + "/" + hash + ".zip"} >Download all outputs</a><br/>
+                <a href={process.env.REACT_APP_BASE_URL + "/api/v1/requests/" + token + "/" + reqid + "/download"} >Download all outputs</a><br/>
                 <div className="secure-hash">SHA-256 Hash: {request.secureHash}</div>
             </div> }
         </Container>